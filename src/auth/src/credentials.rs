@@ -12,12 +12,24 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-pub(crate) mod mds_credential;
-pub(crate) mod service_account_credential;
+pub mod api_key_credential;
+pub mod credential_chain;
+pub mod downscoped_credential;
+pub(crate) mod external_account_authorized_user_credential;
+pub(crate) mod external_account_credential;
+pub mod id_token_credential;
+pub mod impersonated_credential;
+pub mod installed_app;
+pub mod mds_credential;
+pub mod service_account_credential;
+pub mod signer;
 pub(crate) mod user_credential;
 pub(crate) mod util;
 
+use crate::credentials::credential_chain::{CredentialChain, Resolved};
 use crate::errors::CredentialError;
+use crate::retry::{ExponentialBackoff, RetryPolicy, RetryTokenProvider};
+use crate::token::TokenProvider;
 use crate::Result;
 use http::header::{HeaderName, HeaderValue};
 use std::future::Future;
@@ -53,6 +65,23 @@ pub(crate) const QUOTA_PROJECT_KEY: &str = "x-goog-user-project";
 /// service is available in many Google Cloud environments, including
 /// [Google Compute Engine], and [Google Kubernetes Engine].
 ///
+/// # Tracing
+///
+/// The credentials emit [tracing] events when they refresh a token, cached
+/// tokens do not generate any events. Each refresh starts with a
+/// `token refresh started` event at the `DEBUG` level, and ends with either:
+///
+/// * `token refresh succeeded` at the `INFO` level, with the `elapsed_ms`,
+///   `source_type`, and `expires_at` fields.
+/// * `token refresh failed` at the `WARN` level, with the `elapsed_ms`,
+///   `retryable`, and `error` fields.
+///
+/// Attempts that fail and are retried emit `DEBUG` events. All the events
+/// include a `provider` field naming the internal type refreshing the token,
+/// its value is informational and may change. The events never include the
+/// token value.
+///
+/// [tracing]: https://docs.rs/tracing
 /// [credentials-link]: https://cloud.google.com/docs/authentication#credentials
 /// [token-link]: https://cloud.google.com/docs/authentication#token
 /// [Metadata Service]: https://cloud.google.com/compute/docs/metadata/overview
@@ -83,6 +112,47 @@ where
 }
 
 impl Credential {
+    /// Creates a [Credential] from its JSON representation.
+    ///
+    /// The JSON object has the same format as the Application Default
+    /// Credentials files, e.g., a service account key, or the file created by
+    /// `gcloud auth application-default login`. The `type` field determines
+    /// the credential type.
+    ///
+    /// # Example
+    /// ```
+    /// # use gcp_sdk_auth::credentials::Credential;
+    /// let json = serde_json::json!({
+    ///     "type": "authorized_user",
+    ///     "client_id": "my-client-id",
+    ///     "client_secret": "my-client-secret",
+    ///     "refresh_token": "my-refresh-token",
+    /// });
+    /// let credential = Credential::from_json(json)?;
+    /// # Ok::<(), gcp_sdk_auth::errors::CredentialError>(())
+    /// ```
+    pub fn from_json(js: serde_json::Value) -> Result<Credential> {
+        creds_from(js, &CredentialOptions::default())
+    }
+
+    /// Creates a [Credential] from the bytes of its JSON representation.
+    ///
+    /// See [Credential::from_json] for the format.
+    pub fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> Result<Credential> {
+        let js = serde_json::from_slice(bytes.as_ref()).map_err(CredentialError::non_retryable)?;
+        Self::from_json(js)
+    }
+
+    /// Creates a [Credential] from a reader of its JSON representation.
+    ///
+    /// The reader is consumed synchronously. Prefer [Credential::from_bytes]
+    /// for readers that may block, e.g., files, after reading them with a
+    /// non-blocking API.
+    pub fn from_reader<R: std::io::Read>(reader: R) -> Result<Credential> {
+        let js = serde_json::from_reader(reader).map_err(CredentialError::non_retryable)?;
+        Self::from_json(js)
+    }
+
     pub async fn get_token(&self) -> Result<crate::token::Token> {
         self.inner.get_token().await
     }
@@ -94,6 +164,39 @@ impl Credential {
     pub async fn get_universe_domain(&self) -> Option<String> {
         self.inner.get_universe_domain().await
     }
+
+    /// Returns the project associated with these credentials, if known.
+    ///
+    /// Service account credentials return the `project_id` in their key.
+    /// Metadata service credentials return the project of the VM (or node
+    /// pool). Other credential types return `None`.
+    pub async fn get_project_id(&self) -> Option<String> {
+        self.inner.get_project_id().await
+    }
+
+    /// Returns a [Signer][signer::Signer] with the identity of these
+    /// credentials.
+    ///
+    /// Service account, impersonated service account, and metadata service
+    /// credentials can sign blobs. Other credential types return an error.
+    pub fn signer(&self) -> Result<signer::Signer> {
+        self.inner.signer().ok_or_else(|| {
+            CredentialError::non_retryable(
+                "these credentials cannot sign blobs, use service account, impersonated service account, or metadata service credentials",
+            )
+        })
+    }
+
+    /// Revokes the long-lived credentials, e.g., a refresh token.
+    ///
+    /// After a successful call, these credentials, and any other copies of
+    /// the same credentials, can no longer refresh their access tokens.
+    /// Currently only external account authorized user (workforce identity
+    /// federation) credentials with a `revoke_url` support revocation. Other
+    /// credential types return an error.
+    pub async fn revoke(&self) -> Result<()> {
+        self.inner.revoke().await
+    }
 }
 
 /// Represents a [Credential] used to obtain auth
@@ -180,7 +283,25 @@ pub(crate) mod dynamic {
 
         /// Retrieves the universe domain associated with the credential, if any.
         async fn get_universe_domain(&self) -> Option<String> {
-            Some("googleapis.com".to_string())
+            Some(crate::credentials::util::DEFAULT_UNIVERSE_DOMAIN.to_string())
+        }
+
+        /// Retrieves the project associated with the credential, if any.
+        async fn get_project_id(&self) -> Option<String> {
+            None
+        }
+
+        /// Returns a signer with the identity of these credentials, if
+        /// supported.
+        fn signer(&self) -> Option<super::signer::Signer> {
+            None
+        }
+
+        /// Revokes the long-lived credentials, if supported.
+        async fn revoke(&self) -> Result<()> {
+            Err(crate::errors::CredentialError::non_retryable(
+                "these credentials cannot be revoked, use external account authorized user credentials",
+            ))
         }
     }
 
@@ -249,12 +370,203 @@ pub(crate) mod dynamic {
 /// [gcloud auth application-default]: https://cloud.google.com/sdk/gcloud/reference/auth/application-default
 /// [gke-link]: https://cloud.google.com/kubernetes-engine
 pub async fn create_access_token_credential() -> Result<Credential> {
-    let contents = match load_adc()? {
-        AdcContents::Contents(contents) => contents,
-        AdcContents::FallbackToMds => return Ok(mds_credential::new()),
-    };
-    let js: serde_json::Value =
-        serde_json::from_str(&contents).map_err(CredentialError::non_retryable)?;
+    Builder::new().build().await
+}
+
+/// A builder for access token [Credential]s.
+///
+/// Like [create_access_token_credential], the credentials are loaded from the
+/// [Application Default Credentials (ADC)][ADC-link]. The builder configures
+/// options that apply to all the credential types:
+///
+/// - The [OAuth 2.0 scopes] of the access tokens.
+/// - A [quota project], sent in the `x-goog-user-project` header. This
+///   overrides any quota project set in the ADC file.
+/// - The universe domain, overriding any value in the ADC file.
+/// - The endpoint used to fetch or refresh the access tokens.
+/// - The endpoint of the service that receives the access tokens.
+/// - The retry and backoff policies for the requests that fetch access tokens.
+///
+/// # Example
+/// ```
+/// # use gcp_sdk_auth::credentials::Builder;
+/// # use gcp_sdk_auth::errors::CredentialError;
+/// # tokio_test::block_on(async {
+/// let credential = Builder::new()
+///     .with_scopes(["https://www.googleapis.com/auth/pubsub"])
+///     .with_quota_project_id("my-project")
+///     .build()
+///     .await?;
+/// # Ok::<(), CredentialError>(())
+/// # });
+/// ```
+///
+/// [ADC-link]: https://cloud.google.com/docs/authentication/application-default-credentials
+/// [OAuth 2.0 scopes]: https://developers.google.com/identity/protocols/oauth2/scopes
+/// [quota project]: https://cloud.google.com/docs/quotas/quota-project
+#[derive(Clone, Debug, Default)]
+pub struct Builder {
+    options: CredentialOptions,
+    chain: Option<CredentialChain>,
+}
+
+impl Builder {
+    /// Creates a builder with the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the [OAuth 2.0 scopes] of the access tokens.
+    ///
+    /// The default is `https://www.googleapis.com/auth/cloud-platform`. User
+    /// credentials use the scopes granted when the user logged in, unless
+    /// this is set.
+    ///
+    /// [OAuth 2.0 scopes]: https://developers.google.com/identity/protocols/oauth2/scopes
+    pub fn with_scopes<I, S>(mut self, v: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.options.scopes = Some(v.into_iter().map(|s| s.into()).collect());
+        self
+    }
+
+    /// Sets the [quota project] for requests using these credentials.
+    ///
+    /// This overrides the quota project in the ADC file, if any.
+    ///
+    /// [quota project]: https://cloud.google.com/docs/quotas/quota-project
+    pub fn with_quota_project_id<T: Into<String>>(mut self, v: T) -> Self {
+        self.options.quota_project_id = Some(v.into());
+        self
+    }
+
+    /// Sets the universe domain of the credentials.
+    ///
+    /// This overrides the universe domain in the ADC file, if any. The
+    /// default is `googleapis.com`.
+    pub fn with_universe_domain<T: Into<String>>(mut self, v: T) -> Self {
+        self.options.universe_domain = Some(v.into());
+        self
+    }
+
+    /// Sets the endpoint used to fetch access tokens.
+    ///
+    /// The meaning of the endpoint depends on the credential type:
+    /// - User credentials refresh their access tokens at this URL.
+    /// - Service account credentials exchange signed assertions at this URL,
+    ///   if configured to do so.
+    /// - Impersonated service account credentials call the
+    ///   `generateAccessToken` API at this endpoint, e.g.
+    ///   `https://iamcredentials.googleapis.com`.
+    /// - External account credentials exchange tokens with the Security Token
+    ///   Service at this URL.
+    /// - External account authorized user credentials refresh their access
+    ///   tokens at this URL.
+    /// - The metadata service credentials use this as the root URL of the
+    ///   metadata service, e.g.
+    ///   `http://metadata.google.internal/computeMetadata/v1`.
+    pub fn with_token_endpoint<T: Into<String>>(mut self, v: T) -> Self {
+        self.options.token_endpoint = Some(v.into());
+        self
+    }
+
+    /// Sets the endpoint of the service that receives the access tokens.
+    ///
+    /// Service account credentials use self-signed JWTs restricted to this
+    /// service, with the audience derived from the endpoint as described in
+    /// [AccessSpecifier::from_endpoint]. If [with_scopes][Builder::with_scopes]
+    /// is also set, the scopes take precedence. Other credential types ignore
+    /// this option.
+    ///
+    /// [AccessSpecifier::from_endpoint]: service_account_credential::AccessSpecifier::from_endpoint
+    pub fn with_service_endpoint<T: Into<String>>(mut self, v: T) -> Self {
+        self.options.service_endpoint = Some(v.into());
+        self
+    }
+
+    /// Sets the retry policy for the requests that fetch access tokens.
+    ///
+    /// Only retryable errors, e.g. a metadata service that is not ready yet,
+    /// are retried. See [RetryPolicy] for the defaults.
+    pub fn with_retry_policy(mut self, v: RetryPolicy) -> Self {
+        self.options.retry_policy = v;
+        self
+    }
+
+    /// Sets the backoff policy between attempts to fetch access tokens.
+    pub fn with_backoff_policy(mut self, v: ExponentialBackoff) -> Self {
+        self.options.backoff_policy = v;
+        self
+    }
+
+    /// Sets the providers used to find the credentials.
+    ///
+    /// The default is [CredentialChain::default()], the Application Default
+    /// Credentials providers.
+    pub fn with_chain(mut self, v: CredentialChain) -> Self {
+        self.chain = Some(v);
+        self
+    }
+
+    /// Returns a [Credential] with the configured options.
+    pub async fn build(self) -> Result<Credential> {
+        self.resolve().await.map(Resolved::into_credential)
+    }
+
+    /// Returns the credentials with the configured options, and a report
+    /// explaining which provider produced them.
+    ///
+    /// # Example
+    /// ```
+    /// # use gcp_sdk_auth::credentials::Builder;
+    /// # use gcp_sdk_auth::errors::CredentialError;
+    /// # tokio_test::block_on(async {
+    /// let resolved = Builder::new().resolve().await?;
+    /// println!("{}", resolved.report());
+    /// let credential = resolved.into_credential();
+    /// # Ok::<(), CredentialError>(())
+    /// # });
+    /// ```
+    pub async fn resolve(self) -> Result<Resolved> {
+        self.chain
+            .unwrap_or_default()
+            .resolve_with(&self.options)
+            .await
+    }
+}
+
+/// The options shared by all credential types.
+///
+/// Each credential type applies these options on top of the values in its
+/// configuration, if any.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct CredentialOptions {
+    pub(crate) scopes: Option<Vec<String>>,
+    pub(crate) quota_project_id: Option<String>,
+    pub(crate) universe_domain: Option<String>,
+    pub(crate) token_endpoint: Option<String>,
+    pub(crate) service_endpoint: Option<String>,
+    pub(crate) retry_policy: RetryPolicy,
+    pub(crate) backoff_policy: ExponentialBackoff,
+}
+
+impl CredentialOptions {
+    /// Retries the requests of `provider` with the configured policies.
+    pub(crate) fn retrying<T: TokenProvider>(&self, provider: T) -> RetryTokenProvider<T> {
+        RetryTokenProvider::new(
+            provider,
+            self.retry_policy.clone(),
+            self.backoff_policy.clone(),
+        )
+    }
+}
+
+/// Creates a [Credential] from its JSON representation.
+///
+/// The `type` field in the JSON object determines the credential type.
+pub(crate) fn creds_from(js: serde_json::Value, options: &CredentialOptions) -> Result<Credential> {
     let cred_type = js
         .get("type")
         .ok_or_else(|| CredentialError::non_retryable("Failed to parse Application Default Credentials (ADC). No `type` field found."))?
@@ -262,7 +574,13 @@ pub async fn create_access_token_credential() -> Result<Credential> {
         .ok_or_else(|| CredentialError::non_retryable("Failed to parse Application Default Credentials (ADC). `type` field is not a string.")
         )?;
     match cred_type {
-        "authorized_user" => user_credential::creds_from(js),
+        "authorized_user" => user_credential::creds_from(js, options),
+        "service_account" => service_account_credential::creds_from(js, options),
+        "impersonated_service_account" => impersonated_credential::creds_from(js, options),
+        "external_account" => external_account_credential::creds_from(js, options),
+        "external_account_authorized_user" => {
+            external_account_authorized_user_credential::creds_from(js, options)
+        }
         _ => Err(CredentialError::non_retryable(format!(
             "Unimplemented credential type: {cred_type}"
         ))),
@@ -288,15 +606,25 @@ fn path_not_found(path: String) -> CredentialError {
         ))
 }
 
-fn load_adc() -> Result<AdcContents> {
+async fn load_adc() -> Result<AdcContents> {
     match adc_path() {
         None => Ok(AdcContents::FallbackToMds),
-        Some(AdcPath::FromEnv(path)) => match std::fs::read_to_string(&path) {
+        Some(path) => read_adc(path).await,
+    }
+}
+
+/// Reads the ADC file at `path`.
+///
+/// A missing file at the well-known path is not an error, the metadata
+/// service credentials are used instead.
+async fn read_adc(path: AdcPath) -> Result<AdcContents> {
+    match path {
+        AdcPath::FromEnv(path) => match tokio::fs::read_to_string(&path).await {
             Ok(contents) => Ok(AdcContents::Contents(contents)),
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(path_not_found(path)),
             Err(e) => Err(CredentialError::non_retryable(e)),
         },
-        Some(AdcPath::WellKnown(path)) => match std::fs::read_to_string(path) {
+        AdcPath::WellKnown(path) => match tokio::fs::read_to_string(path).await {
             Ok(contents) => Ok(AdcContents::Contents(contents)),
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(AdcContents::FallbackToMds),
             Err(e) => Err(CredentialError::non_retryable(e)),
@@ -318,7 +646,7 @@ fn adc_path() -> Option<AdcPath> {
 ///
 /// [AIP-4113]: https://google.aip.dev/auth/4113
 #[cfg(target_os = "windows")]
-fn adc_well_known_path() -> Option<String> {
+pub(crate) fn adc_well_known_path() -> Option<String> {
     std::env::var("APPDATA")
         .ok()
         .map(|root| root + "/gcloud/application_default_credentials.json")
@@ -328,7 +656,7 @@ fn adc_well_known_path() -> Option<String> {
 ///
 /// [AIP-4113]: https://google.aip.dev/auth/4113
 #[cfg(not(target_os = "windows"))]
-fn adc_well_known_path() -> Option<String> {
+pub(crate) fn adc_well_known_path() -> Option<String> {
     std::env::var("HOME")
         .ok()
         .map(|root| root + "/.config/gcloud/application_default_credentials.json")
@@ -345,10 +673,13 @@ fn adc_well_known_path() -> Option<String> {
 pub mod testing {
     use crate::credentials::dynamic::CredentialTrait;
     use crate::credentials::Credential;
+    use crate::errors::CredentialError;
     use crate::token::Token;
     use crate::Result;
-    use http::header::{HeaderName, HeaderValue};
-    use std::sync::Arc;
+    use http::header::{HeaderName, HeaderValue, AUTHORIZATION};
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
 
     /// A simple credentials implementation to use in tests where authentication does not matter.
     ///
@@ -381,6 +712,165 @@ pub mod testing {
             None
         }
     }
+
+    /// A configurable fake credential, to use in tests where authentication matters.
+    ///
+    /// The fake returns a scripted sequence of tokens and errors. Each call to
+    /// fetch a token, or the headers derived from a token, consumes the next
+    /// step in the script. Once the script is exhausted the last step repeats.
+    /// An empty script always returns a "Bearer" token, with
+    /// "test-only-token" as the value.
+    ///
+    /// The fake records how many times it was called. Clones of a
+    /// `FakeCredential`, and any [Credential] built from it, share the same
+    /// script and counter.
+    ///
+    /// # Example
+    /// ```
+    /// # use gcp_sdk_auth::credentials::testing::FakeCredential;
+    /// # use gcp_sdk_auth::token::Token;
+    /// # tokio_test::block_on(async {
+    /// let fake = FakeCredential::new()
+    ///     .with_retryable_error("try again")
+    ///     .with_token(Token {
+    ///         token: "token-1".to_string(),
+    ///         token_type: "Bearer".to_string(),
+    ///         expires_at: None,
+    ///         metadata: None,
+    ///     });
+    /// let credential = fake.build();
+    /// assert!(credential.get_token().await.is_err());
+    /// assert_eq!(credential.get_token().await?.token, "token-1");
+    /// assert_eq!(fake.call_count(), 2);
+    /// # Ok::<(), gcp_sdk_auth::errors::CredentialError>(())
+    /// # });
+    /// ```
+    #[derive(Clone, Debug, Default)]
+    pub struct FakeCredential {
+        state: Arc<FakeState>,
+        headers: Vec<(HeaderName, HeaderValue)>,
+        universe_domain: Option<String>,
+    }
+
+    #[derive(Debug, Default)]
+    struct FakeState {
+        steps: Mutex<VecDeque<Step>>,
+        calls: AtomicUsize,
+    }
+
+    #[derive(Clone, Debug)]
+    enum Step {
+        Token(Token),
+        Error { is_retryable: bool, message: String },
+    }
+
+    impl FakeCredential {
+        /// Creates a fake credential with an empty script.
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Appends a successful step returning `token` to the script.
+        ///
+        /// Use the `expires_at` field to test how callers handle token
+        /// expiration and rotation.
+        pub fn with_token(self, token: Token) -> Self {
+            self.push(Step::Token(token))
+        }
+
+        /// Appends a step returning a retryable [CredentialError] to the script.
+        pub fn with_retryable_error<T: Into<String>>(self, message: T) -> Self {
+            self.push(Step::Error {
+                is_retryable: true,
+                message: message.into(),
+            })
+        }
+
+        /// Appends a step returning a non-retryable [CredentialError] to the script.
+        pub fn with_non_retryable_error<T: Into<String>>(self, message: T) -> Self {
+            self.push(Step::Error {
+                is_retryable: false,
+                message: message.into(),
+            })
+        }
+
+        /// Adds a header returned by `get_headers()`, after the `Authorization` header.
+        pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+            self.headers.push((name, value));
+            self
+        }
+
+        /// Sets the universe domain reported by the credential.
+        ///
+        /// By default, the fake does not report a universe domain.
+        pub fn with_universe_domain<T: Into<String>>(mut self, universe_domain: T) -> Self {
+            self.universe_domain = Some(universe_domain.into());
+            self
+        }
+
+        /// Returns how many times a token, or the headers, were requested.
+        pub fn call_count(&self) -> usize {
+            self.state.calls.load(Ordering::SeqCst)
+        }
+
+        /// Returns a [Credential] backed by this fake.
+        pub fn build(&self) -> Credential {
+            Credential {
+                inner: Arc::new(self.clone()),
+            }
+        }
+
+        fn push(self, step: Step) -> Self {
+            self.state.steps.lock().unwrap().push_back(step);
+            self
+        }
+
+        fn next(&self) -> Result<Token> {
+            self.state.calls.fetch_add(1, Ordering::SeqCst);
+            let step = {
+                let mut steps = self.state.steps.lock().unwrap();
+                if steps.len() > 1 {
+                    steps.pop_front()
+                } else {
+                    steps.front().cloned()
+                }
+            };
+            match step {
+                None => Ok(Token {
+                    token: "test-only-token".to_string(),
+                    token_type: "Bearer".to_string(),
+                    expires_at: None,
+                    metadata: None,
+                }),
+                Some(Step::Token(token)) => Ok(token),
+                Some(Step::Error {
+                    is_retryable,
+                    message,
+                }) => Err(CredentialError::new(is_retryable, message.into())),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl CredentialTrait for FakeCredential {
+        async fn get_token(&self) -> Result<Token> {
+            self.next()
+        }
+
+        async fn get_headers(&self) -> Result<Vec<(HeaderName, HeaderValue)>> {
+            let token = self.next()?;
+            let mut value = HeaderValue::from_str(&format!("{} {}", token.token_type, token.token))
+                .map_err(CredentialError::non_retryable)?;
+            value.set_sensitive(true);
+            let mut headers = vec![(AUTHORIZATION, value)];
+            headers.extend(self.headers.iter().cloned());
+            Ok(headers)
+        }
+
+        async fn get_universe_domain(&self) -> Option<String> {
+            self.universe_domain.clone()
+        }
+    }
 }
 
 #[cfg(test)]
@@ -388,6 +878,7 @@ mod test {
     use super::*;
     use scoped_env::ScopedEnv;
     use std::error::Error;
+    type TestResult = std::result::Result<(), Box<dyn Error>>;
 
     #[cfg(target_os = "windows")]
     #[test]
@@ -460,49 +951,234 @@ mod test {
         );
     }
 
-    #[test]
+    #[tokio::test]
     #[serial_test::serial]
-    fn load_adc_no_well_known_path_fallback_to_mds() {
+    async fn load_adc_no_well_known_path_fallback_to_mds() {
         let _e1 = ScopedEnv::remove("GOOGLE_APPLICATION_CREDENTIALS");
         let _e2 = ScopedEnv::remove("HOME"); // For posix
         let _e3 = ScopedEnv::remove("APPDATA"); // For windows
-        assert_eq!(load_adc().unwrap(), AdcContents::FallbackToMds);
+        assert_eq!(load_adc().await.unwrap(), AdcContents::FallbackToMds);
     }
 
-    #[test]
+    #[tokio::test]
     #[serial_test::serial]
-    fn load_adc_no_file_at_well_known_path_fallback_to_mds() {
+    async fn load_adc_no_file_at_well_known_path_fallback_to_mds() {
         // Create a new temp directory. There is not an ADC file in here.
         let dir = tempfile::TempDir::new().unwrap();
         let path = dir.path().to_str().unwrap();
         let _e1 = ScopedEnv::remove("GOOGLE_APPLICATION_CREDENTIALS");
         let _e2 = ScopedEnv::set("HOME", path); // For posix
         let _e3 = ScopedEnv::set("APPDATA", path); // For windows
-        assert_eq!(load_adc().unwrap(), AdcContents::FallbackToMds);
+        assert_eq!(load_adc().await.unwrap(), AdcContents::FallbackToMds);
     }
 
-    #[test]
+    #[tokio::test]
     #[serial_test::serial]
-    fn load_adc_no_file_at_env_is_error() {
+    async fn load_adc_no_file_at_env_is_error() {
         let _e = ScopedEnv::set("GOOGLE_APPLICATION_CREDENTIALS", "file-does-not-exist.json");
-        let err = load_adc().err().unwrap();
+        let err = load_adc().await.err().unwrap();
         let msg = err.source().unwrap().to_string();
         assert!(msg.contains("Failed to load Application Default Credentials"));
         assert!(msg.contains("file-does-not-exist.json"));
         assert!(msg.contains("GOOGLE_APPLICATION_CREDENTIALS"));
     }
 
-    #[test]
+    #[tokio::test]
     #[serial_test::serial]
-    fn load_adc_success() {
+    async fn load_adc_success() {
         let file = tempfile::NamedTempFile::new().unwrap();
         let path = file.into_temp_path();
         std::fs::write(&path, "contents").expect("Unable to write to temporary file.");
         let _e = ScopedEnv::set("GOOGLE_APPLICATION_CREDENTIALS", path.to_str().unwrap());
 
         assert_eq!(
-            load_adc().unwrap(),
+            load_adc().await.unwrap(),
             AdcContents::Contents("contents".to_string())
         );
     }
+
+    fn user_json() -> serde_json::Value {
+        serde_json::json!({
+            "type": "authorized_user",
+            "client_id": "test-client-id",
+            "client_secret": "test-client-secret",
+            "refresh_token": "test-refresh-token",
+        })
+    }
+
+    #[test]
+    fn from_json() {
+        let creds = Credential::from_json(user_json()).unwrap();
+        let fmt = format!("{creds:?}");
+        assert!(fmt.contains("UserCredential"), "{fmt}");
+
+        let err = Credential::from_json(serde_json::json!({"type": "unknown"}))
+            .err()
+            .unwrap();
+        assert!(!err.is_retryable(), "{err}");
+    }
+
+    #[test]
+    fn from_bytes() {
+        let bytes = user_json().to_string().into_bytes();
+        let creds = Credential::from_bytes(&bytes).unwrap();
+        let fmt = format!("{creds:?}");
+        assert!(fmt.contains("UserCredential"), "{fmt}");
+
+        let err = Credential::from_bytes("not json").err().unwrap();
+        assert!(!err.is_retryable(), "{err}");
+    }
+
+    #[test]
+    fn from_reader() {
+        let contents = user_json().to_string();
+        let creds = Credential::from_reader(contents.as_bytes()).unwrap();
+        let fmt = format!("{creds:?}");
+        assert!(fmt.contains("UserCredential"), "{fmt}");
+
+        let err = Credential::from_reader("{".as_bytes()).err().unwrap();
+        assert!(!err.is_retryable(), "{err}");
+    }
+
+    #[test]
+    fn signer_not_supported() {
+        let creds = Credential::from_json(user_json()).unwrap();
+        let err = creds.signer().err().unwrap();
+        assert!(!err.is_retryable(), "{err}");
+        assert!(err.to_string().contains("cannot sign"), "{err}");
+    }
+
+    #[test]
+    fn builder_retry_options() {
+        use crate::retry::{ExponentialBackoff, RetryPolicy};
+        let builder = Builder::new();
+        assert_eq!(builder.options.retry_policy, RetryPolicy::default());
+        assert_eq!(
+            builder.options.backoff_policy,
+            ExponentialBackoff::default()
+        );
+
+        let backoff =
+            ExponentialBackoff::new().with_initial_delay(std::time::Duration::from_secs(1));
+        let builder = Builder::new()
+            .with_retry_policy(RetryPolicy::never())
+            .with_backoff_policy(backoff.clone());
+        assert_eq!(builder.options.retry_policy, RetryPolicy::never());
+        assert_eq!(builder.options.backoff_policy, backoff);
+    }
+
+    #[tokio::test]
+    async fn project_id_not_supported() -> TestResult {
+        let creds = Credential::from_json(user_json())?;
+        assert_eq!(creds.get_project_id().await, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn revoke_not_supported() -> TestResult {
+        let creds = Credential::from_json(user_json())?;
+        let err = creds.revoke().await.err().unwrap();
+        assert!(!err.is_retryable(), "{err}");
+        assert!(err.to_string().contains("cannot be revoked"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn external_account_authorized_user_from_json() -> TestResult {
+        let json = serde_json::json!({
+            "type": "external_account_authorized_user",
+            "client_id": "test-client-id",
+            "client_secret": "test-client-secret",
+            "refresh_token": "test-refresh-token",
+            "token_url": "https://sts.googleapis.com/v1/oauthtoken",
+        });
+        let creds = Credential::from_json(json)?;
+        let fmt = format!("{creds:?}");
+        assert!(fmt.contains("ExternalAuthorizedUserCredential"), "{fmt}");
+        Ok(())
+    }
+
+    fn fake_token(token: &str) -> crate::token::Token {
+        crate::token::Token {
+            token: token.to_string(),
+            token_type: "Bearer".to_string(),
+            expires_at: None,
+            metadata: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn fake_credential_default() -> TestResult {
+        let fake = testing::FakeCredential::new();
+        let creds = fake.build();
+        assert_eq!(creds.get_token().await?, fake_token("test-only-token"));
+        assert_eq!(creds.get_universe_domain().await, None);
+        assert_eq!(fake.call_count(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fake_credential_script() -> TestResult {
+        let expires_at = time::OffsetDateTime::now_utc() + time::Duration::minutes(5);
+        let token_1 = crate::token::Token {
+            expires_at: Some(expires_at),
+            ..fake_token("token-1")
+        };
+        let fake = testing::FakeCredential::new()
+            .with_retryable_error("try again")
+            .with_token(token_1.clone())
+            .with_non_retryable_error("give up")
+            .with_token(fake_token("token-2"));
+        let creds = fake.build();
+
+        let err = creds.get_token().await.err().unwrap();
+        assert!(err.is_retryable(), "{err}");
+        assert!(err.to_string().contains("try again"), "{err}");
+        assert_eq!(creds.get_token().await?, token_1);
+        let err = creds.get_token().await.err().unwrap();
+        assert!(!err.is_retryable(), "{err}");
+        assert!(err.to_string().contains("give up"), "{err}");
+        // The last step repeats once the script is exhausted.
+        assert_eq!(creds.get_token().await?, fake_token("token-2"));
+        assert_eq!(creds.get_token().await?, fake_token("token-2"));
+        assert_eq!(fake.call_count(), 5);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fake_credential_headers() -> TestResult {
+        let fake = testing::FakeCredential::new()
+            .with_token(fake_token("token-1"))
+            .with_retryable_error("try again")
+            .with_header(
+                HeaderName::from_static("x-goog-user-project"),
+                HeaderValue::from_static("test-project"),
+            )
+            .with_universe_domain("example.com");
+        let creds = fake.build();
+
+        let headers = creds.get_headers().await?;
+        assert_eq!(
+            headers,
+            vec![
+                (
+                    http::header::AUTHORIZATION,
+                    HeaderValue::from_static("Bearer token-1")
+                ),
+                (
+                    HeaderName::from_static("x-goog-user-project"),
+                    HeaderValue::from_static("test-project")
+                ),
+            ]
+        );
+        assert!(headers[0].1.is_sensitive());
+        let err = creds.get_headers().await.err().unwrap();
+        assert!(err.is_retryable(), "{err}");
+        assert_eq!(
+            creds.get_universe_domain().await.as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(fake.call_count(), 2);
+        Ok(())
+    }
 }
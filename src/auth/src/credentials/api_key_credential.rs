@@ -0,0 +1,138 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [API key] credentials.
+//!
+//! Some Google Cloud services accept API keys instead of (or in addition to)
+//! OAuth2 access tokens. API keys identify the calling project, but not the
+//! caller, and are sent using the `x-goog-api-key` header.
+//!
+//! [API key]: https://cloud.google.com/docs/authentication/api-keys
+
+use crate::credentials::dynamic::CredentialTrait;
+use crate::credentials::{Credential, Result};
+use crate::errors::CredentialError;
+use crate::token::Token;
+use http::header::{HeaderName, HeaderValue};
+use std::sync::Arc;
+
+const API_KEY_HEADER_KEY: &str = "x-goog-api-key";
+
+/// Create credentials that authenticate requests with an [API key].
+///
+/// The returned [Credential] sends the key in the `x-goog-api-key` header. The
+/// header value is marked as sensitive. API keys are not tied to a universe
+/// domain, [Credential::get_universe_domain] returns `None`.
+///
+/// # Example
+/// ```
+/// # use gcp_sdk_auth::credentials::api_key_credential::create_api_key_credential;
+/// let credential = create_api_key_credential("my-api-key");
+/// ```
+///
+/// [API key]: https://cloud.google.com/docs/authentication/api-keys
+pub fn create_api_key_credential<T: Into<String>>(api_key: T) -> Credential {
+    Credential {
+        inner: Arc::new(ApiKeyCredential {
+            api_key: api_key.into(),
+        }),
+    }
+}
+
+struct ApiKeyCredential {
+    api_key: String,
+}
+
+impl std::fmt::Debug for ApiKeyCredential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiKeyCredential")
+            .field("api_key", &"[censored]")
+            .finish()
+    }
+}
+
+#[async_trait::async_trait]
+impl CredentialTrait for ApiKeyCredential {
+    async fn get_token(&self) -> Result<Token> {
+        Ok(Token {
+            token: self.api_key.clone(),
+            token_type: "ApiKey".to_string(),
+            expires_at: None,
+            metadata: None,
+        })
+    }
+
+    async fn get_headers(&self) -> Result<Vec<(HeaderName, HeaderValue)>> {
+        let mut value =
+            HeaderValue::from_str(&self.api_key).map_err(CredentialError::non_retryable)?;
+        value.set_sensitive(true);
+        Ok(vec![(HeaderName::from_static(API_KEY_HEADER_KEY), value)])
+    }
+
+    // API keys are not tied to a universe domain, they can be used with any
+    // client.
+    async fn get_universe_domain(&self) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    #[tokio::test]
+    async fn get_token() -> TestResult {
+        let creds = create_api_key_credential("test-api-key");
+        let token = creds.get_token().await?;
+        assert_eq!(token.token, "test-api-key");
+        assert_eq!(token.token_type, "ApiKey");
+        assert_eq!(token.expires_at, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_headers() -> TestResult {
+        let creds = create_api_key_credential("test-api-key");
+        let headers = creds.get_headers().await?;
+        assert_eq!(headers.len(), 1, "{headers:?}");
+        let (name, value) = &headers[0];
+        assert_eq!(name, API_KEY_HEADER_KEY);
+        assert_eq!(value, HeaderValue::from_static("test-api-key"));
+        assert!(value.is_sensitive());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_headers_invalid_key() {
+        let creds = create_api_key_credential("bad\nkey");
+        let e = creds.get_headers().await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+    }
+
+    #[tokio::test]
+    async fn universe_domain() {
+        let creds = create_api_key_credential("test-api-key");
+        assert_eq!(creds.get_universe_domain().await, None);
+    }
+
+    #[test]
+    fn debug_does_not_leak_key() {
+        let creds = create_api_key_credential("test-api-key");
+        let fmt = format!("{creds:?}");
+        assert!(fmt.contains("ApiKeyCredential"), "{fmt}");
+        assert!(!fmt.contains("test-api-key"), "{fmt}");
+    }
+}
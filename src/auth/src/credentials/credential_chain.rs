@@ -0,0 +1,643 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An ordered chain of credential providers.
+//!
+//! [Application Default Credentials (ADC)][ADC-link] try several sources of
+//! credentials, in order, and use the first one that is available. A
+//! [CredentialChain] makes this process configurable and explainable: the
+//! application can choose (and reorder) the providers, add its own, and
+//! inspect a [ChainReport] describing why each provider was selected, skipped,
+//! or failed.
+//!
+//! # Example
+//! ```
+//! # use gcp_sdk_auth::credentials::credential_chain::CredentialChain;
+//! # use gcp_sdk_auth::errors::CredentialError;
+//! # tokio_test::block_on(async {
+//! let resolved = CredentialChain::default().resolve().await?;
+//! println!("using credentials from {}", resolved.provider());
+//! println!("{}", resolved.report());
+//! # Ok::<(), CredentialError>(())
+//! # });
+//! ```
+//!
+//! [ADC-link]: https://cloud.google.com/docs/authentication/application-default-credentials
+
+use crate::credentials::mds_credential::{self, Probed};
+use crate::credentials::{
+    adc_well_known_path, creds_from, read_adc, AdcContents, AdcPath, Credential, CredentialOptions,
+    Result,
+};
+use crate::errors::CredentialError;
+use std::sync::Arc;
+use std::time::Duration;
+
+const ENV_VAR: &str = "GOOGLE_APPLICATION_CREDENTIALS";
+
+/// The result of a [CredentialProvider].
+#[derive(Debug)]
+pub enum Provided {
+    /// The provider produced credentials, the chain stops.
+    Credential(Credential),
+    /// The provider is not applicable, the chain continues with the next
+    /// provider. The string explains why, e.g., "the file does not exist".
+    Skipped(String),
+}
+
+/// A source of credentials in a [CredentialChain].
+///
+/// Applications implement this trait to add their own providers to a chain.
+/// A provider returns [Provided::Skipped] if it does not apply, and an error
+/// if it applies, but cannot produce credentials. Errors stop the chain.
+///
+/// # Example
+/// ```
+/// # use gcp_sdk_auth::credentials::credential_chain::{CredentialProvider, Provided};
+/// # use gcp_sdk_auth::credentials::api_key_credential::create_api_key_credential;
+/// # use gcp_sdk_auth::errors::CredentialError;
+/// #[derive(Debug)]
+/// struct ApiKeyFromEnv;
+///
+/// #[async_trait::async_trait]
+/// impl CredentialProvider for ApiKeyFromEnv {
+///     fn name(&self) -> String {
+///         "MY_API_KEY".to_string()
+///     }
+///     async fn provide(&self) -> Result<Provided, CredentialError> {
+///         Ok(match std::env::var("MY_API_KEY") {
+///             Ok(key) => Provided::Credential(create_api_key_credential(key)),
+///             Err(_) => Provided::Skipped("MY_API_KEY is not set".to_string()),
+///         })
+///     }
+/// }
+/// ```
+#[async_trait::async_trait]
+pub trait CredentialProvider: std::fmt::Debug + Send + Sync {
+    /// A short description of the provider, used in the [ChainReport].
+    fn name(&self) -> String;
+
+    /// Produces the credentials, or explains why the provider does not apply.
+    async fn provide(&self) -> Result<Provided>;
+}
+
+/// An ordered list of credential providers.
+///
+/// [CredentialChain::default()] returns the providers used by
+/// [Application Default Credentials][ADC-link], in order:
+/// 1. The file in the `GOOGLE_APPLICATION_CREDENTIALS` environment variable.
+/// 2. The well-known file created by
+///    [gcloud auth application-default login][gcloud].
+/// 3. The metadata service.
+///
+/// [CredentialChain::new()] returns an empty chain, where the application
+/// adds providers in the order of its choosing.
+///
+/// [ADC-link]: https://cloud.google.com/docs/authentication/application-default-credentials
+/// [gcloud]: https://cloud.google.com/sdk/gcloud/reference/auth/application-default/login
+#[derive(Clone, Debug)]
+pub struct CredentialChain {
+    providers: Vec<Source>,
+    metadata_service_timeout: Duration,
+}
+
+#[derive(Clone, Debug)]
+enum Source {
+    EnvironmentFile,
+    WellKnownFile,
+    MetadataService,
+    Custom(Arc<dyn CredentialProvider>),
+}
+
+impl Default for CredentialChain {
+    fn default() -> Self {
+        Self::new()
+            .with_environment_file()
+            .with_well_known_file()
+            .with_metadata_service()
+    }
+}
+
+impl CredentialChain {
+    /// Creates an empty chain.
+    pub fn new() -> Self {
+        Self {
+            providers: Vec::new(),
+            metadata_service_timeout: mds_credential::DEFAULT_PROBE_TIMEOUT,
+        }
+    }
+
+    /// Adds a provider for the file in the `GOOGLE_APPLICATION_CREDENTIALS`
+    /// environment variable.
+    ///
+    /// The provider is skipped if the environment variable is not set. It
+    /// fails if the file does not exist.
+    pub fn with_environment_file(mut self) -> Self {
+        self.providers.push(Source::EnvironmentFile);
+        self
+    }
+
+    /// Adds a provider for the well-known file created by
+    /// `gcloud auth application-default login`.
+    ///
+    /// The provider is skipped if the file does not exist.
+    pub fn with_well_known_file(mut self) -> Self {
+        self.providers.push(Source::WellKnownFile);
+        self
+    }
+
+    /// Adds a provider for the metadata service credentials.
+    ///
+    /// The provider contacts the metadata service. It is skipped if the
+    /// metadata service cannot be reached, e.g. outside Google Cloud. It fails
+    /// if the metadata service does not respond within the
+    /// [timeout][CredentialChain::with_metadata_service_timeout], or responds
+    /// with an error. Timeouts and server errors are retryable.
+    pub fn with_metadata_service(mut self) -> Self {
+        self.providers.push(Source::MetadataService);
+        self
+    }
+
+    /// Sets how long the metadata service provider waits for a response.
+    ///
+    /// The default is 3 seconds.
+    pub fn with_metadata_service_timeout(mut self, v: Duration) -> Self {
+        self.metadata_service_timeout = v;
+        self
+    }
+
+    /// Adds a custom provider.
+    pub fn with_provider<P: CredentialProvider + 'static>(mut self, v: P) -> Self {
+        self.providers.push(Source::Custom(Arc::new(v)));
+        self
+    }
+
+    /// Tries each provider, in order, and returns the first credentials found.
+    ///
+    /// If no provider produces credentials, or if a provider fails, the
+    /// source of the returned error is a [ChainReport].
+    pub async fn resolve(&self) -> Result<Resolved> {
+        self.resolve_with(&CredentialOptions::default()).await
+    }
+
+    pub(crate) async fn resolve_with(&self, options: &CredentialOptions) -> Result<Resolved> {
+        let mut report = ChainReport::default();
+        for source in &self.providers {
+            let provider = source.name();
+            match source.provide(options, self.metadata_service_timeout).await {
+                Ok(Provided::Credential(credential)) => {
+                    report.push(provider.clone(), Outcome::Selected);
+                    return Ok(Resolved {
+                        credential,
+                        provider,
+                        report,
+                    });
+                }
+                Ok(Provided::Skipped(reason)) => report.push(provider, Outcome::Skipped(reason)),
+                Err(e) => {
+                    let retryable = e.is_retryable();
+                    report.push(provider, Outcome::Failed(e.to_string()));
+                    return Err(CredentialError::new(retryable, Box::new(report)));
+                }
+            }
+        }
+        Err(CredentialError::non_retryable(report))
+    }
+}
+
+impl Source {
+    fn name(&self) -> String {
+        match self {
+            Self::EnvironmentFile => format!("{ENV_VAR} file"),
+            Self::WellKnownFile => "gcloud well-known file".to_string(),
+            Self::MetadataService => "metadata service".to_string(),
+            Self::Custom(p) => p.name(),
+        }
+    }
+
+    async fn provide(&self, options: &CredentialOptions, timeout: Duration) -> Result<Provided> {
+        let path = match self {
+            Self::EnvironmentFile => match std::env::var(ENV_VAR) {
+                Ok(path) => AdcPath::FromEnv(path),
+                Err(_) => return Ok(Provided::Skipped(format!("{ENV_VAR} is not set"))),
+            },
+            Self::WellKnownFile => match adc_well_known_path() {
+                Some(path) => AdcPath::WellKnown(path),
+                None => {
+                    return Ok(Provided::Skipped(
+                        "cannot determine the well-known file path".to_string(),
+                    ))
+                }
+            },
+            Self::MetadataService => {
+                return Ok(match mds_credential::probe(options, timeout).await? {
+                    Probed::Available(credential) => Provided::Credential(credential),
+                    Probed::Unreachable(reason) => Provided::Skipped(reason),
+                });
+            }
+            Self::Custom(p) => return p.provide().await,
+        };
+        let skipped = match &path {
+            AdcPath::WellKnown(p) => format!("{p} does not exist"),
+            AdcPath::FromEnv(_) => String::new(),
+        };
+        match read_adc(path).await? {
+            AdcContents::FallbackToMds => Ok(Provided::Skipped(skipped)),
+            AdcContents::Contents(contents) => {
+                let js: serde_json::Value =
+                    serde_json::from_str(&contents).map_err(CredentialError::non_retryable)?;
+                creds_from(js, options).map(Provided::Credential)
+            }
+        }
+    }
+}
+
+/// The credentials found by a [CredentialChain].
+#[derive(Debug)]
+pub struct Resolved {
+    credential: Credential,
+    provider: String,
+    report: ChainReport,
+}
+
+impl Resolved {
+    /// The credentials.
+    pub fn credential(&self) -> &Credential {
+        &self.credential
+    }
+
+    /// Consumes the result, returning the credentials.
+    pub fn into_credential(self) -> Credential {
+        self.credential
+    }
+
+    /// The name of the provider that produced the credentials.
+    pub fn provider(&self) -> &str {
+        &self.provider
+    }
+
+    /// What happened with each provider tried by the chain.
+    pub fn report(&self) -> &ChainReport {
+        &self.report
+    }
+}
+
+/// Explains how a [CredentialChain] selected its credentials, or why it failed.
+///
+/// The report contains one [Attempt] for each provider tried, in order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChainReport {
+    attempts: Vec<Attempt>,
+}
+
+impl ChainReport {
+    /// The providers tried, in order.
+    pub fn attempts(&self) -> &[Attempt] {
+        &self.attempts
+    }
+
+    /// The name of the provider selected, if any.
+    pub fn selected(&self) -> Option<&str> {
+        self.attempts
+            .iter()
+            .find(|a| a.outcome == Outcome::Selected)
+            .map(|a| a.provider.as_str())
+    }
+
+    fn push(&mut self, provider: String, outcome: Outcome) {
+        self.attempts.push(Attempt { provider, outcome });
+    }
+}
+
+impl std::fmt::Display for ChainReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.attempts.is_empty() {
+            return write!(f, "the credential chain has no providers");
+        }
+        if self.selected().is_none() {
+            write!(f, "no credentials found in the credential chain:")?;
+        } else {
+            write!(f, "credential chain:")?;
+        }
+        for (i, a) in self.attempts.iter().enumerate() {
+            write!(f, "\n  {}. {}: ", i + 1, a.provider)?;
+            match &a.outcome {
+                Outcome::Selected => write!(f, "selected")?,
+                Outcome::Skipped(reason) => write!(f, "skipped, {reason}")?,
+                Outcome::Failed(error) => write!(f, "failed, {error}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ChainReport {}
+
+/// A provider tried by a [CredentialChain].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attempt {
+    provider: String,
+    outcome: Outcome,
+}
+
+impl Attempt {
+    /// The name of the provider.
+    pub fn provider(&self) -> &str {
+        &self.provider
+    }
+
+    /// What happened when the provider was tried.
+    pub fn outcome(&self) -> &Outcome {
+        &self.outcome
+    }
+}
+
+/// The outcome of a provider in a [CredentialChain].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    /// The provider produced the credentials.
+    Selected,
+    /// The provider did not apply, with an explanation.
+    Skipped(String),
+    /// The provider failed, with the error message.
+    Failed(String),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::credentials::api_key_credential::create_api_key_credential;
+    use scoped_env::ScopedEnv;
+    use std::error::Error;
+
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    #[derive(Debug)]
+    struct Fake(Option<&'static str>);
+
+    #[async_trait::async_trait]
+    impl CredentialProvider for Fake {
+        fn name(&self) -> String {
+            format!("fake-{}", self.0.unwrap_or("skipped"))
+        }
+        async fn provide(&self) -> Result<Provided> {
+            Ok(match self.0 {
+                Some(key) => Provided::Credential(create_api_key_credential(key)),
+                None => Provided::Skipped("not configured".to_string()),
+            })
+        }
+    }
+
+    #[derive(Debug)]
+    struct Failing;
+
+    #[async_trait::async_trait]
+    impl CredentialProvider for Failing {
+        fn name(&self) -> String {
+            "failing".to_string()
+        }
+        async fn provide(&self) -> Result<Provided> {
+            Err(CredentialError::retryable("try again"))
+        }
+    }
+
+    fn report_from(e: &CredentialError) -> &ChainReport {
+        e.source()
+            .and_then(|s| s.downcast_ref::<ChainReport>())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn custom_providers() -> TestResult {
+        let chain = CredentialChain::new()
+            .with_provider(Fake(None))
+            .with_provider(Fake(Some("key-1")))
+            .with_provider(Fake(Some("key-2")));
+        let resolved = chain.resolve().await?;
+        assert_eq!(resolved.provider(), "fake-key-1");
+        assert_eq!(resolved.report().selected(), Some("fake-key-1"));
+        assert_eq!(
+            resolved.report().attempts(),
+            &[
+                Attempt {
+                    provider: "fake-skipped".to_string(),
+                    outcome: Outcome::Skipped("not configured".to_string())
+                },
+                Attempt {
+                    provider: "fake-key-1".to_string(),
+                    outcome: Outcome::Selected
+                },
+            ]
+        );
+        let token = resolved.into_credential().get_token().await?;
+        assert_eq!(token.token, "key-1");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn provider_failure_stops_chain() {
+        let chain = CredentialChain::new()
+            .with_provider(Fake(None))
+            .with_provider(Failing)
+            .with_provider(Fake(Some("key-1")));
+        let e = chain.resolve().await.err().unwrap();
+        assert!(e.is_retryable(), "{e}");
+        let report = report_from(&e);
+        assert_eq!(report.selected(), None);
+        assert_eq!(report.attempts().len(), 2, "{report:?}");
+        assert!(
+            matches!(report.attempts()[1].outcome(), Outcome::Failed(m) if m.contains("try again")),
+            "{report:?}"
+        );
+        let fmt = e.to_string();
+        assert!(fmt.contains("2. failing: failed"), "{fmt}");
+    }
+
+    #[tokio::test]
+    async fn exhausted_chain() {
+        let e = CredentialChain::new().resolve().await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("no providers"), "{e}");
+
+        let chain = CredentialChain::new().with_provider(Fake(None));
+        let e = chain.resolve().await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        let fmt = report_from(&e).to_string();
+        assert!(fmt.contains("no credentials found"), "{fmt}");
+        assert!(
+            fmt.contains("1. fake-skipped: skipped, not configured"),
+            "{fmt}"
+        );
+    }
+
+    // Starts a fake metadata service, returns its host.
+    async fn start_metadata_service() -> (String, tokio::task::JoinHandle<()>) {
+        let info = || async {
+            axum::Json(serde_json::json!({
+                "email": "test@test.com",
+                "scopes": ["scope1"],
+            }))
+        };
+        let app = axum::Router::new().route(
+            "/computeMetadata/v1/instance/service-accounts/default/",
+            axum::routing::get(info),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (addr.to_string(), server)
+    }
+
+    // Returns a host where nothing listens.
+    async fn unreachable_host() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[serial_test::serial]
+    async fn default_chain_fallback_to_mds() -> TestResult {
+        let (host, _server) = start_metadata_service().await;
+        let _e1 = ScopedEnv::remove(ENV_VAR);
+        let _e2 = ScopedEnv::set("HOME", "/does/not/exist");
+        let _e3 = ScopedEnv::set("APPDATA", "/does/not/exist");
+        let _e4 = ScopedEnv::set("GCE_METADATA_HOST", &host);
+        let resolved = CredentialChain::default().resolve().await?;
+        assert_eq!(resolved.provider(), "metadata service");
+        let attempts = resolved.report().attempts();
+        assert_eq!(attempts.len(), 3, "{attempts:?}");
+        assert_eq!(
+            attempts[0].outcome(),
+            &Outcome::Skipped(format!("{ENV_VAR} is not set"))
+        );
+        assert!(
+            matches!(attempts[1].outcome(), Outcome::Skipped(m) if m.contains("/does/not/exist")),
+            "{attempts:?}"
+        );
+        let fmt = format!("{:?}", resolved.credential());
+        assert!(fmt.contains("MDSCredential"), "{fmt}");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[serial_test::serial]
+    async fn default_chain_mds_unreachable() {
+        let host = unreachable_host().await;
+        let _e1 = ScopedEnv::remove(ENV_VAR);
+        let _e2 = ScopedEnv::set("HOME", "/does/not/exist");
+        let _e3 = ScopedEnv::set("APPDATA", "/does/not/exist");
+        let _e4 = ScopedEnv::set("GCE_METADATA_HOST", &host);
+        let e = CredentialChain::default().resolve().await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        let report = report_from(&e);
+        assert_eq!(report.attempts().len(), 3, "{report:?}");
+        assert!(
+            matches!(report.attempts()[2].outcome(), Outcome::Skipped(m) if m.contains("cannot connect") && m.contains(&host)),
+            "{report:?}"
+        );
+        let fmt = report.to_string();
+        assert!(fmt.contains("no credentials found"), "{fmt}");
+        assert!(fmt.contains("3. metadata service: skipped"), "{fmt}");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[serial_test::serial]
+    async fn mds_unreachable_falls_through() -> TestResult {
+        let host = unreachable_host().await;
+        let _e = ScopedEnv::set("GCE_METADATA_HOST", &host);
+        let chain = CredentialChain::new()
+            .with_metadata_service()
+            .with_provider(Fake(Some("key-1")));
+        let resolved = chain.resolve().await?;
+        assert_eq!(resolved.provider(), "fake-key-1");
+        let attempts = resolved.report().attempts();
+        assert_eq!(attempts.len(), 2, "{attempts:?}");
+        assert_eq!(attempts[0].provider(), "metadata service");
+        assert!(
+            matches!(attempts[0].outcome(), Outcome::Skipped(m) if m.contains("cannot connect")),
+            "{attempts:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[serial_test::serial]
+    async fn mds_timeout_stops_chain() -> TestResult {
+        // The listener accepts connections, but never responds.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let host = listener.local_addr()?.to_string();
+        let _e = ScopedEnv::set("GCE_METADATA_HOST", &host);
+        let chain = CredentialChain::new()
+            .with_metadata_service()
+            .with_metadata_service_timeout(Duration::from_millis(200))
+            .with_provider(Fake(Some("key-1")));
+        let e = chain.resolve().await.err().unwrap();
+        assert!(e.is_retryable(), "{e}");
+        let report = report_from(&e);
+        assert_eq!(report.attempts().len(), 1, "{report:?}");
+        assert!(
+            matches!(report.attempts()[0].outcome(), Outcome::Failed(m) if m.contains("did not respond within 200ms")),
+            "{report:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn default_chain_environment_file() -> TestResult {
+        let contents = serde_json::json!({
+            "type": "authorized_user",
+            "client_id": "test-client-id",
+            "client_secret": "test-client-secret",
+            "refresh_token": "test-refresh-token",
+        });
+        let file = tempfile::NamedTempFile::new()?;
+        std::fs::write(file.path(), contents.to_string())?;
+        let _e = ScopedEnv::set(ENV_VAR, file.path().to_str().unwrap());
+        let resolved = CredentialChain::default().resolve().await?;
+        assert_eq!(resolved.provider(), format!("{ENV_VAR} file"));
+        assert_eq!(resolved.report().attempts().len(), 1);
+        let fmt = format!("{:?}", resolved.credential());
+        assert!(fmt.contains("UserCredential"), "{fmt}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn default_chain_missing_environment_file() {
+        let _e = ScopedEnv::set(ENV_VAR, "/does/not/exist.json");
+        let e = CredentialChain::default().resolve().await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        let report = report_from(&e);
+        assert_eq!(report.attempts().len(), 1, "{report:?}");
+        assert!(
+            matches!(report.attempts()[0].outcome(), Outcome::Failed(m) if m.contains("/does/not/exist.json")),
+            "{report:?}"
+        );
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn reordered_chain() -> TestResult {
+        let _e = ScopedEnv::set(ENV_VAR, "/does/not/exist.json");
+        let chain = CredentialChain::new()
+            .with_provider(Fake(Some("key-1")))
+            .with_environment_file();
+        let resolved = chain.resolve().await?;
+        assert_eq!(resolved.provider(), "fake-key-1");
+        Ok(())
+    }
+}
@@ -0,0 +1,624 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Downscoped credentials with [Credential Access Boundaries].
+//!
+//! A Credential Access Boundary restricts the Cloud Storage permissions of an
+//! access token. Applications use downscoped tokens to hand short-lived,
+//! restricted credentials to less trusted components, for example, a process
+//! that should only read objects under a specific prefix.
+//!
+//! The [Builder] creates credentials that exchange the token of a source
+//! credential for a downscoped token using the Security Token Service (STS).
+//! The downscoped tokens are cached, and refreshed automatically.
+//!
+//! Applications that create many downscoped tokens can use a
+//! [ClientSideGenerator]. It fetches an *intermediary* token from STS, and
+//! then creates downscoped tokens locally, without a round trip to STS for
+//! each token.
+//!
+//! # Example
+//! ```
+//! # use gcp_sdk_auth::credentials::create_access_token_credential;
+//! # use gcp_sdk_auth::credentials::downscoped_credential::{
+//! #     AccessBoundary, AccessBoundaryRule, AvailabilityCondition, Builder,
+//! # };
+//! # use gcp_sdk_auth::errors::CredentialError;
+//! # tokio_test::block_on(async {
+//! let rule = AccessBoundaryRule::new(
+//!     "//storage.googleapis.com/projects/_/buckets/my-bucket",
+//!     ["inRole:roles/storage.objectViewer"],
+//! )
+//! .with_availability_condition(AvailabilityCondition::new(
+//!     "resource.name.startsWith('projects/_/buckets/my-bucket/objects/reports/')",
+//! ));
+//! let source = create_access_token_credential().await?;
+//! let credential = Builder::new(source, AccessBoundary::new([rule])).build()?;
+//! # Ok::<(), CredentialError>(())
+//! # });
+//! ```
+//!
+//! [Credential Access Boundaries]: https://cloud.google.com/iam/docs/downscoping-short-lived-credentials
+
+mod client_side;
+
+use crate::credentials::dynamic::CredentialTrait;
+use crate::credentials::util::build_headers;
+use crate::credentials::util::sts::{self, ExchangeTokenRequest};
+use crate::credentials::{Credential, Result};
+use crate::errors::CredentialError;
+use crate::retry::RetryTokenProvider;
+use crate::token::{Token, TokenProvider};
+use crate::token_cache::TokenCache;
+use http::header::{HeaderName, HeaderValue};
+use std::sync::Arc;
+
+pub use client_side::ClientSideGenerator;
+
+// STS rejects access boundaries with more rules.
+const MAX_RULES: usize = 10;
+
+const ROLE_PREFIX: &str = "inRole:";
+
+/// A Credential Access Boundary.
+///
+/// An access boundary contains between 1 and 10 [AccessBoundaryRule]s. The
+/// downscoped token can only use the permissions granted by some rule.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessBoundary {
+    access_boundary_rules: Vec<AccessBoundaryRule>,
+}
+
+impl AccessBoundary {
+    /// Creates an access boundary from its rules.
+    pub fn new<I>(rules: I) -> Self
+    where
+        I: IntoIterator<Item = AccessBoundaryRule>,
+    {
+        Self {
+            access_boundary_rules: rules.into_iter().collect(),
+        }
+    }
+
+    /// Returns the rules in this access boundary.
+    pub fn rules(&self) -> &[AccessBoundaryRule] {
+        &self.access_boundary_rules
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.access_boundary_rules.is_empty() {
+            return Err(CredentialError::non_retryable(
+                "an access boundary requires at least one rule",
+            ));
+        }
+        if self.access_boundary_rules.len() > MAX_RULES {
+            return Err(CredentialError::non_retryable(format!(
+                "an access boundary can have at most {MAX_RULES} rules, got {}",
+                self.access_boundary_rules.len()
+            )));
+        }
+        self.access_boundary_rules
+            .iter()
+            .try_for_each(AccessBoundaryRule::validate)
+    }
+
+    // The `options` parameter for the STS token exchange.
+    fn to_options(&self) -> Result<String> {
+        serde_json::to_string(&serde_json::json!({ "accessBoundary": self }))
+            .map_err(CredentialError::non_retryable)
+    }
+}
+
+/// A rule in a Credential Access Boundary.
+///
+/// Each rule names a resource, the roles available on that resource, and
+/// optionally a condition further restricting the resources (e.g. objects)
+/// where the roles are available.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessBoundaryRule {
+    available_resource: String,
+    available_permissions: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    availability_condition: Option<AvailabilityCondition>,
+}
+
+impl AccessBoundaryRule {
+    /// Creates a rule for `available_resource`.
+    ///
+    /// The resource is the full resource name of a Cloud Storage bucket, e.g.
+    /// `//storage.googleapis.com/projects/_/buckets/my-bucket`. The
+    /// permissions are roles, prefixed with `inRole:`, e.g.
+    /// `inRole:roles/storage.objectViewer`.
+    pub fn new<T, I, S>(available_resource: T, available_permissions: I) -> Self
+    where
+        T: Into<String>,
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            available_resource: available_resource.into(),
+            available_permissions: available_permissions
+                .into_iter()
+                .map(|s| s.into())
+                .collect(),
+            availability_condition: None,
+        }
+    }
+
+    /// Restricts the rule to the resources matching a condition.
+    pub fn with_availability_condition(mut self, v: AvailabilityCondition) -> Self {
+        self.availability_condition = Some(v);
+        self
+    }
+
+    /// Returns the resource this rule applies to.
+    pub fn available_resource(&self) -> &str {
+        &self.available_resource
+    }
+
+    /// Returns the roles available on the resource.
+    pub fn available_permissions(&self) -> &[String] {
+        &self.available_permissions
+    }
+
+    /// Returns the availability condition, if any.
+    pub fn availability_condition(&self) -> Option<&AvailabilityCondition> {
+        self.availability_condition.as_ref()
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.available_resource.is_empty() {
+            return Err(CredentialError::non_retryable(
+                "the available resource of an access boundary rule must not be empty",
+            ));
+        }
+        if self.available_permissions.is_empty() {
+            return Err(CredentialError::non_retryable(format!(
+                "the access boundary rule for {} has no available permissions",
+                self.available_resource
+            )));
+        }
+        if let Some(p) = self
+            .available_permissions
+            .iter()
+            .find(|p| !p.starts_with(ROLE_PREFIX))
+        {
+            return Err(CredentialError::non_retryable(format!(
+                "available permissions must start with `{ROLE_PREFIX}`, got {p}"
+            )));
+        }
+        if self
+            .availability_condition
+            .as_ref()
+            .is_some_and(|c| c.expression.is_empty())
+        {
+            return Err(CredentialError::non_retryable(format!(
+                "the availability condition for {} has an empty expression",
+                self.available_resource
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// A condition restricting where an [AccessBoundaryRule] applies.
+///
+/// The condition is a [CEL] expression, e.g.
+/// `resource.name.startsWith('projects/_/buckets/my-bucket/objects/reports/')`.
+///
+/// [CEL]: https://cloud.google.com/iam/docs/conditions-overview#cel
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct AvailabilityCondition {
+    expression: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+}
+
+impl AvailabilityCondition {
+    /// Creates a condition from a CEL expression.
+    pub fn new<T: Into<String>>(expression: T) -> Self {
+        Self {
+            expression: expression.into(),
+            title: None,
+            description: None,
+        }
+    }
+
+    /// Sets a short title for the condition.
+    pub fn with_title<T: Into<String>>(mut self, v: T) -> Self {
+        self.title = Some(v.into());
+        self
+    }
+
+    /// Sets a description for the condition.
+    pub fn with_description<T: Into<String>>(mut self, v: T) -> Self {
+        self.description = Some(v.into());
+        self
+    }
+
+    /// Returns the CEL expression.
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+}
+
+/// A builder for downscoped [Credential]s.
+///
+/// The credentials exchange the token from the `source` credentials for a
+/// token restricted by the access boundary.
+#[derive(Clone, Debug)]
+pub struct Builder {
+    source: Credential,
+    access_boundary: AccessBoundary,
+    endpoint: String,
+}
+
+impl Builder {
+    /// Creates a builder to downscope the `source` credentials.
+    pub fn new(source: Credential, access_boundary: AccessBoundary) -> Self {
+        Self {
+            source,
+            access_boundary,
+            endpoint: sts::STS_ENDPOINT.to_string(),
+        }
+    }
+
+    /// Sets the Security Token Service endpoint.
+    ///
+    /// The default is `https://sts.googleapis.com/v1/token`.
+    pub fn with_endpoint<T: Into<String>>(mut self, v: T) -> Self {
+        self.endpoint = v.into();
+        self
+    }
+
+    /// Returns a [Credential] with the configured options.
+    ///
+    /// Returns an error if the access boundary is invalid.
+    pub fn build(self) -> Result<Credential> {
+        self.access_boundary.validate()?;
+        let token_provider = DownscopedTokenProvider {
+            source: self.source.clone(),
+            options: self.access_boundary.to_options()?,
+            endpoint: self.endpoint,
+        };
+        Ok(Credential {
+            inner: Arc::new(DownscopedCredential {
+                token_provider: TokenCache::new(RetryTokenProvider::with_defaults(token_provider)),
+                source: self.source,
+            }),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct DownscopedTokenProvider {
+    source: Credential,
+    // The access boundary, serialized as the STS `options` parameter.
+    options: String,
+    endpoint: String,
+}
+
+#[async_trait::async_trait]
+impl TokenProvider for DownscopedTokenProvider {
+    async fn get_token(&self) -> Result<Token> {
+        let source_token = self.source.get_token().await?;
+        let request = ExchangeTokenRequest {
+            requested_token_type: sts::ACCESS_TOKEN_TYPE.to_string(),
+            subject_token: source_token.token,
+            subject_token_type: sts::ACCESS_TOKEN_TYPE.to_string(),
+            options: Some(self.options.clone()),
+            ..Default::default()
+        };
+        let response = sts::exchange_token(&self.endpoint, &request).await?;
+        Ok(with_source_expiration(
+            response.into_token(),
+            source_token.expires_at,
+        ))
+    }
+}
+
+// STS may omit the lifetime of downscoped tokens. In that case they expire
+// with the source token.
+fn with_source_expiration(mut token: Token, source: Option<time::OffsetDateTime>) -> Token {
+    if token.expires_at.is_none() {
+        token.expires_at = source;
+    }
+    token
+}
+
+/// Credentials returning downscoped tokens.
+#[derive(Debug)]
+struct DownscopedCredential<T>
+where
+    T: TokenProvider,
+{
+    token_provider: T,
+    source: Credential,
+}
+
+#[async_trait::async_trait]
+impl<T> CredentialTrait for DownscopedCredential<T>
+where
+    T: TokenProvider,
+{
+    async fn get_token(&self) -> Result<Token> {
+        self.token_provider.get_token().await
+    }
+
+    async fn get_headers(&self) -> Result<Vec<(HeaderName, HeaderValue)>> {
+        let token = self.get_token().await?;
+        build_headers(&token, None)
+    }
+
+    async fn get_universe_domain(&self) -> Option<String> {
+        self.source.get_universe_domain().await
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use axum::extract::Form;
+    use http::header::AUTHORIZATION;
+    use http::StatusCode;
+    use std::collections::HashMap;
+    use std::error::Error;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use time::OffsetDateTime;
+    use tokio::task::JoinHandle;
+
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    pub(crate) type StsRequests = Arc<Mutex<Vec<HashMap<String, String>>>>;
+
+    #[derive(Debug)]
+    pub(crate) struct FakeSource {
+        pub(crate) expires_at: Option<OffsetDateTime>,
+    }
+
+    #[async_trait::async_trait]
+    impl CredentialTrait for FakeSource {
+        async fn get_token(&self) -> Result<Token> {
+            Ok(Token {
+                token: "source-token".to_string(),
+                token_type: "Bearer".to_string(),
+                expires_at: self.expires_at,
+                metadata: None,
+            })
+        }
+
+        async fn get_headers(&self) -> Result<Vec<(HeaderName, HeaderValue)>> {
+            unimplemented!()
+        }
+
+        async fn get_universe_domain(&self) -> Option<String> {
+            Some("test-universe-domain".to_string())
+        }
+    }
+
+    pub(crate) fn fake_source(expires_at: Option<OffsetDateTime>) -> Credential {
+        Credential {
+            inner: Arc::new(FakeSource { expires_at }),
+        }
+    }
+
+    pub(crate) fn test_rule() -> AccessBoundaryRule {
+        AccessBoundaryRule::new(
+            "//storage.googleapis.com/projects/_/buckets/test-bucket",
+            ["inRole:roles/storage.objectViewer"],
+        )
+    }
+
+    // Starts a fake STS server. Returns an (endpoint, server) pair.
+    pub(crate) async fn start(
+        response_code: StatusCode,
+        response_body: String,
+        requests: StsRequests,
+    ) -> (String, JoinHandle<()>) {
+        let handler = move |Form(form): Form<HashMap<String, String>>| async move {
+            requests.lock().unwrap().push(form);
+            (response_code, response_body)
+        };
+        let app = axum::Router::new().route("/v1/token", axum::routing::post(handler));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        (
+            format!("http://{}:{}/v1/token", addr.ip(), addr.port()),
+            server,
+        )
+    }
+
+    #[test]
+    fn access_boundary_serialization() -> TestResult {
+        let boundary = AccessBoundary::new([
+            test_rule().with_availability_condition(
+                AvailabilityCondition::new(
+                    "resource.name.startsWith('projects/_/buckets/test-bucket/objects/a/')",
+                )
+                .with_title("prefix")
+                .with_description("only objects under a/"),
+            ),
+            AccessBoundaryRule::new(
+                "//storage.googleapis.com/projects/_/buckets/other-bucket",
+                ["inRole:roles/storage.objectAdmin"],
+            ),
+        ]);
+        let got: serde_json::Value = serde_json::from_str(&boundary.to_options()?)?;
+        let want = serde_json::json!({
+            "accessBoundary": {
+                "accessBoundaryRules": [
+                    {
+                        "availableResource": "//storage.googleapis.com/projects/_/buckets/test-bucket",
+                        "availablePermissions": ["inRole:roles/storage.objectViewer"],
+                        "availabilityCondition": {
+                            "expression": "resource.name.startsWith('projects/_/buckets/test-bucket/objects/a/')",
+                            "title": "prefix",
+                            "description": "only objects under a/",
+                        },
+                    },
+                    {
+                        "availableResource": "//storage.googleapis.com/projects/_/buckets/other-bucket",
+                        "availablePermissions": ["inRole:roles/storage.objectAdmin"],
+                    },
+                ]
+            }
+        });
+        assert_eq!(got, want);
+        Ok(())
+    }
+
+    #[test]
+    fn access_boundary_validation() {
+        assert!(AccessBoundary::new([test_rule()]).validate().is_ok());
+
+        let invalid = [
+            AccessBoundary::new([]),
+            AccessBoundary::new(std::iter::repeat_n(test_rule(), MAX_RULES + 1)),
+            AccessBoundary::new([AccessBoundaryRule::new(
+                "",
+                ["inRole:roles/storage.objectViewer"],
+            )]),
+            AccessBoundary::new([AccessBoundaryRule::new(
+                "//storage.googleapis.com/projects/_/buckets/test-bucket",
+                Vec::<String>::new(),
+            )]),
+            AccessBoundary::new([AccessBoundaryRule::new(
+                "//storage.googleapis.com/projects/_/buckets/test-bucket",
+                ["roles/storage.objectViewer"],
+            )]),
+            AccessBoundary::new([
+                test_rule().with_availability_condition(AvailabilityCondition::new(""))
+            ]),
+        ];
+        for boundary in invalid {
+            let e = boundary.validate().err().unwrap();
+            assert!(!e.is_retryable(), "{e}");
+        }
+    }
+
+    #[test]
+    fn builder_invalid_boundary() {
+        let e = Builder::new(fake_source(None), AccessBoundary::new([]))
+            .build()
+            .err()
+            .unwrap();
+        assert!(e.to_string().contains("at least one rule"), "{e}");
+    }
+
+    fn sts_response(expires_in: Option<u64>) -> String {
+        serde_json::json!({
+            "access_token": "downscoped-token",
+            "issued_token_type": sts::ACCESS_TOKEN_TYPE,
+            "token_type": "Bearer",
+            "expires_in": expires_in,
+        })
+        .to_string()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn exchange_success() -> TestResult {
+        let requests = StsRequests::default();
+        let (endpoint, _server) =
+            start(StatusCode::OK, sts_response(Some(3600)), requests.clone()).await;
+        let boundary = AccessBoundary::new([test_rule()]);
+        let creds = Builder::new(fake_source(None), boundary.clone())
+            .with_endpoint(endpoint)
+            .build()?;
+
+        let now = OffsetDateTime::now_utc();
+        let token = creds.get_token().await?;
+        assert_eq!(token.token, "downscoped-token");
+        assert!(token
+            .expires_at
+            .is_some_and(|e| e > now + Duration::from_secs(3500)));
+        let headers = creds.get_headers().await?;
+        assert_eq!(
+            headers,
+            vec![(
+                AUTHORIZATION,
+                HeaderValue::from_static("Bearer downscoped-token")
+            )]
+        );
+        assert!(headers[0].1.is_sensitive());
+        assert_eq!(
+            creds.get_universe_domain().await.as_deref(),
+            Some("test-universe-domain")
+        );
+
+        // The token is cached.
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 1, "{requests:?}");
+        let request = &requests[0];
+        assert_eq!(
+            request.get("grant_type").map(String::as_str),
+            Some(sts::TOKEN_EXCHANGE_GRANT_TYPE)
+        );
+        assert_eq!(
+            request.get("subject_token").map(String::as_str),
+            Some("source-token")
+        );
+        assert_eq!(
+            request.get("subject_token_type").map(String::as_str),
+            Some(sts::ACCESS_TOKEN_TYPE)
+        );
+        assert_eq!(
+            request.get("requested_token_type").map(String::as_str),
+            Some(sts::ACCESS_TOKEN_TYPE)
+        );
+        assert_eq!(request.get("options"), Some(&boundary.to_options()?));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn exchange_uses_source_expiration() -> TestResult {
+        let (endpoint, _server) =
+            start(StatusCode::OK, sts_response(None), StsRequests::default()).await;
+        let expires_at = OffsetDateTime::now_utc() + Duration::from_secs(1800);
+        let creds = Builder::new(
+            fake_source(Some(expires_at)),
+            AccessBoundary::new([test_rule()]),
+        )
+        .with_endpoint(endpoint)
+        .build()?;
+        let token = creds.get_token().await?;
+        assert_eq!(token.expires_at, Some(expires_at));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn exchange_error() -> TestResult {
+        let (endpoint, _server) = start(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "try again".to_string(),
+            StsRequests::default(),
+        )
+        .await;
+        let creds = Builder::new(fake_source(None), AccessBoundary::new([test_rule()]))
+            .with_endpoint(endpoint)
+            .build()?;
+        let e = creds.get_token().await.err().unwrap();
+        assert!(e.is_retryable(), "{e}");
+        assert!(e.source().unwrap().to_string().contains("try again"), "{e}");
+        Ok(())
+    }
+}
@@ -0,0 +1,607 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Client-side generation of downscoped tokens.
+//!
+//! STS returns an *intermediary* token and a session key. A downscoped token
+//! is the intermediary token, followed by the access boundary, serialized as a
+//! protocol buffer and encrypted with the session key. The session key is a
+//! [Tink] keyset with a single AES-GCM key.
+//!
+//! [Tink]: https://developers.google.com/tink
+
+use super::{with_source_expiration, AccessBoundary};
+use crate::credentials::util::sts::{self, ExchangeTokenRequest};
+use crate::credentials::{Credential, Result};
+use crate::errors::CredentialError;
+use crate::retry::RetryTokenProvider;
+use crate::token::{Token, TokenProvider};
+use crate::token_cache::TokenCache;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes128Gcm, Aes256Gcm};
+use base64::prelude::{Engine as _, BASE64_STANDARD, BASE64_URL_SAFE};
+use std::collections::HashMap;
+
+const SESSION_KEY: &str = "access_boundary_session_key";
+
+const AES_GCM_KEY_TYPE_URL: &str = "type.googleapis.com/google.crypto.tink.AesGcmKey";
+
+// Values of the Tink `KeyStatusType` and `OutputPrefixType` enums.
+const KEY_STATUS_ENABLED: u64 = 1;
+const OUTPUT_PREFIX_TINK: u64 = 1;
+const OUTPUT_PREFIX_LEGACY: u64 = 2;
+const OUTPUT_PREFIX_RAW: u64 = 3;
+const OUTPUT_PREFIX_CRUNCHY: u64 = 4;
+
+/// Generates downscoped tokens locally.
+///
+/// The generator fetches an intermediary token from the Security Token Service
+/// (STS), using the token from the source credentials. The intermediary token
+/// is cached and refreshed as needed. Each call to
+/// [generate_token][ClientSideGenerator::generate_token] creates a new
+/// downscoped token without contacting STS.
+///
+/// Availability conditions must be compiled before they are included in a
+/// client-side token, and this crate does not include a [CEL] compiler. Use
+/// the server-side [Builder][super::Builder] for access boundaries with
+/// availability conditions.
+///
+/// # Example
+/// ```
+/// # use gcp_sdk_auth::credentials::create_access_token_credential;
+/// # use gcp_sdk_auth::credentials::downscoped_credential::{
+/// #     AccessBoundary, AccessBoundaryRule, ClientSideGenerator,
+/// # };
+/// # use gcp_sdk_auth::errors::CredentialError;
+/// # async fn sample() -> Result<(), CredentialError> {
+/// let source = create_access_token_credential().await?;
+/// let generator = ClientSideGenerator::new(source);
+/// let rule = AccessBoundaryRule::new(
+///     "//storage.googleapis.com/projects/_/buckets/my-bucket",
+///     ["inRole:roles/storage.objectViewer"],
+/// );
+/// let token = generator.generate_token(&AccessBoundary::new([rule])).await?;
+/// # Ok(()) }
+/// ```
+///
+/// [CEL]: https://cloud.google.com/iam/docs/conditions-overview#cel
+#[derive(Debug)]
+pub struct ClientSideGenerator {
+    source: Credential,
+    intermediary: TokenCache<RetryTokenProvider<IntermediaryTokenProvider>>,
+}
+
+impl ClientSideGenerator {
+    /// Creates a generator using the `source` credentials.
+    pub fn new(source: Credential) -> Self {
+        Self::from_parts(source, sts::STS_ENDPOINT.to_string())
+    }
+
+    /// Sets the Security Token Service endpoint.
+    ///
+    /// The default is `https://sts.googleapis.com/v1/token`.
+    pub fn with_endpoint<T: Into<String>>(self, v: T) -> Self {
+        Self::from_parts(self.source, v.into())
+    }
+
+    fn from_parts(source: Credential, endpoint: String) -> Self {
+        let provider = IntermediaryTokenProvider {
+            source: source.clone(),
+            endpoint,
+        };
+        Self {
+            source,
+            intermediary: TokenCache::new(RetryTokenProvider::with_defaults(provider)),
+        }
+    }
+
+    /// Creates a downscoped token restricted by `access_boundary`.
+    ///
+    /// The token expires with the intermediary token.
+    pub async fn generate_token(&self, access_boundary: &AccessBoundary) -> Result<Token> {
+        access_boundary.validate()?;
+        if let Some(rule) = access_boundary
+            .rules()
+            .iter()
+            .find(|r| r.availability_condition().is_some())
+        {
+            return Err(CredentialError::non_retryable(format!(
+                "client-side downscoping does not support availability conditions, found one in the rule for {}",
+                rule.available_resource()
+            )));
+        }
+        let intermediary = self.intermediary.get_token().await?;
+        let session_key = intermediary
+            .metadata
+            .as_ref()
+            .and_then(|m| m.get(SESSION_KEY))
+            .ok_or_else(|| CredentialError::non_retryable("missing access boundary session key"))?;
+        let key = AeadKey::from_session_key(session_key)?;
+        let restriction = encode_access_boundary(access_boundary);
+        let encrypted = key.encrypt(&restriction)?;
+        Ok(Token {
+            token: format!(
+                "{}.{}",
+                intermediary.token,
+                BASE64_URL_SAFE.encode(encrypted)
+            ),
+            token_type: intermediary.token_type,
+            expires_at: intermediary.expires_at,
+            metadata: None,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct IntermediaryTokenProvider {
+    source: Credential,
+    endpoint: String,
+}
+
+#[async_trait::async_trait]
+impl TokenProvider for IntermediaryTokenProvider {
+    async fn get_token(&self) -> Result<Token> {
+        let source_token = self.source.get_token().await?;
+        let request = ExchangeTokenRequest {
+            requested_token_type: sts::ACCESS_BOUNDARY_INTERMEDIARY_TOKEN_TYPE.to_string(),
+            subject_token: source_token.token,
+            subject_token_type: sts::ACCESS_TOKEN_TYPE.to_string(),
+            ..Default::default()
+        };
+        let mut response = sts::exchange_token(&self.endpoint, &request).await?;
+        let session_key = response.access_boundary_session_key.take().ok_or_else(|| {
+            CredentialError::non_retryable(
+                "the intermediary token response does not include an access boundary session key",
+            )
+        })?;
+        let mut token = with_source_expiration(response.into_token(), source_token.expires_at);
+        token.metadata = Some(HashMap::from([(SESSION_KEY.to_string(), session_key)]));
+        Ok(token)
+    }
+}
+
+// Serializes the access boundary as a `ClientSideAccessBoundary` proto:
+//
+// message ClientSideAccessBoundary {
+//   repeated ClientSideAccessBoundaryRule access_boundary_rules = 1;
+// }
+// message ClientSideAccessBoundaryRule {
+//   string available_resource = 1;
+//   repeated string available_permissions = 2;
+//   CheckedExpr compiled_availability_condition = 3;
+// }
+fn encode_access_boundary(access_boundary: &AccessBoundary) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for rule in access_boundary.rules() {
+        let mut r = Vec::new();
+        proto::put_bytes(&mut r, 1, rule.available_resource().as_bytes());
+        for p in rule.available_permissions() {
+            proto::put_bytes(&mut r, 2, p.as_bytes());
+        }
+        proto::put_bytes(&mut buf, 1, &r);
+    }
+    buf
+}
+
+/// The primary key in a Tink keyset.
+#[derive(PartialEq)]
+struct AeadKey {
+    prefix: Vec<u8>,
+    key: Vec<u8>,
+}
+
+impl std::fmt::Debug for AeadKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AeadKey")
+            .field("prefix", &self.prefix)
+            .field("key", &"[censored]")
+            .finish()
+    }
+}
+
+impl AeadKey {
+    // Parses a base64-encoded, binary Tink keyset:
+    //
+    // message Keyset { uint32 primary_key_id = 1; repeated Key key = 2; }
+    // message Key {
+    //   KeyData key_data = 1;
+    //   KeyStatusType status = 2;
+    //   uint32 key_id = 3;
+    //   OutputPrefixType output_prefix_type = 4;
+    // }
+    // message KeyData { string type_url = 1; bytes value = 2; ... }
+    // message AesGcmKey { uint32 version = 1; bytes key_value = 3; }
+    fn from_session_key(session_key: &str) -> Result<Self> {
+        let keyset = BASE64_STANDARD
+            .decode(session_key)
+            .map_err(CredentialError::non_retryable)?;
+        let mut primary_key_id = 0;
+        let mut keys = Vec::new();
+        for field in proto::fields(&keyset)? {
+            match field? {
+                (1, proto::Value::Varint(v)) => primary_key_id = v,
+                (2, proto::Value::Bytes(b)) => keys.push(b),
+                _ => {}
+            }
+        }
+        for key in keys {
+            let (mut key_data, mut status, mut key_id, mut output_prefix) = (None, 0, 0, 0);
+            for field in proto::fields(key)? {
+                match field? {
+                    (1, proto::Value::Bytes(b)) => key_data = Some(b),
+                    (2, proto::Value::Varint(v)) => status = v,
+                    (3, proto::Value::Varint(v)) => key_id = v,
+                    (4, proto::Value::Varint(v)) => output_prefix = v,
+                    _ => {}
+                }
+            }
+            if key_id != primary_key_id || status != KEY_STATUS_ENABLED {
+                continue;
+            }
+            let key_data = key_data.ok_or_else(|| invalid_key("missing key data"))?;
+            return Self::from_key_data(key_data, key_id as u32, output_prefix);
+        }
+        Err(invalid_key("missing primary key"))
+    }
+
+    fn from_key_data(key_data: &[u8], key_id: u32, output_prefix: u64) -> Result<Self> {
+        let (mut type_url, mut value) = (&[][..], &[][..]);
+        for field in proto::fields(key_data)? {
+            match field? {
+                (1, proto::Value::Bytes(b)) => type_url = b,
+                (2, proto::Value::Bytes(b)) => value = b,
+                _ => {}
+            }
+        }
+        if type_url != AES_GCM_KEY_TYPE_URL.as_bytes() {
+            return Err(invalid_key(format!(
+                "unsupported key type {}",
+                String::from_utf8_lossy(type_url)
+            )));
+        }
+        let mut key = None;
+        for field in proto::fields(value)? {
+            if let (3, proto::Value::Bytes(b)) = field? {
+                key = Some(b.to_vec());
+            }
+        }
+        let key = key
+            .filter(|k| k.len() == 16 || k.len() == 32)
+            .ok_or_else(|| invalid_key("invalid AES-GCM key"))?;
+        let prefix = match output_prefix {
+            OUTPUT_PREFIX_TINK => [&[1_u8][..], &key_id.to_be_bytes()].concat(),
+            OUTPUT_PREFIX_LEGACY | OUTPUT_PREFIX_CRUNCHY => {
+                [&[0_u8][..], &key_id.to_be_bytes()].concat()
+            }
+            OUTPUT_PREFIX_RAW => Vec::new(),
+            p => return Err(invalid_key(format!("unsupported output prefix type {p}"))),
+        };
+        Ok(Self { prefix, key })
+    }
+
+    // Encrypts `plaintext` using the Tink AES-GCM ciphertext format:
+    // `prefix || iv || ciphertext || tag`.
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        fn seal<C: KeyInit + Aead + AeadCore>(key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+            let cipher = C::new_from_slice(key).map_err(CredentialError::non_retryable)?;
+            let nonce = C::generate_nonce(&mut OsRng);
+            let ciphertext = cipher
+                .encrypt(&nonce, plaintext)
+                .map_err(|_| CredentialError::non_retryable("cannot encrypt access boundary"))?;
+            Ok([nonce.as_slice(), &ciphertext].concat())
+        }
+        let sealed = match self.key.len() {
+            16 => seal::<Aes128Gcm>(&self.key, plaintext)?,
+            _ => seal::<Aes256Gcm>(&self.key, plaintext)?,
+        };
+        Ok([self.prefix.as_slice(), &sealed].concat())
+    }
+}
+
+fn invalid_key<T: std::fmt::Display>(msg: T) -> CredentialError {
+    CredentialError::non_retryable(format!("invalid access boundary session key: {msg}"))
+}
+
+/// A minimal protocol buffers wire format encoder and decoder.
+mod proto {
+    use crate::errors::CredentialError;
+    use crate::Result;
+
+    pub(super) enum Value<'a> {
+        Varint(u64),
+        Bytes(&'a [u8]),
+    }
+
+    pub(super) fn put_varint(buf: &mut Vec<u8>, mut v: u64) {
+        while v >= 0x80 {
+            buf.push((v as u8) | 0x80);
+            v >>= 7;
+        }
+        buf.push(v as u8);
+    }
+
+    pub(super) fn put_bytes(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+        put_varint(buf, (field << 3) | 2);
+        put_varint(buf, bytes.len() as u64);
+        buf.extend_from_slice(bytes);
+    }
+
+    fn malformed() -> CredentialError {
+        CredentialError::non_retryable("malformed protocol buffer")
+    }
+
+    fn varint(buf: &[u8], pos: &mut usize) -> Result<u64> {
+        let mut v = 0_u64;
+        for shift in (0..64).step_by(7) {
+            let b = *buf.get(*pos).ok_or_else(malformed)?;
+            *pos += 1;
+            v |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(malformed())
+    }
+
+    /// Returns an iterator over the `(field number, value)` pairs in `buf`.
+    ///
+    /// Only the varint and length-delimited wire types are supported.
+    pub(super) fn fields(buf: &[u8]) -> Result<impl Iterator<Item = Result<(u64, Value<'_>)>>> {
+        let mut pos = 0;
+        Ok(std::iter::from_fn(move || {
+            if pos >= buf.len() {
+                return None;
+            }
+            let next = (|| {
+                let tag = varint(buf, &mut pos)?;
+                match tag & 0x7 {
+                    0 => Ok((tag >> 3, Value::Varint(varint(buf, &mut pos)?))),
+                    2 => {
+                        let len = varint(buf, &mut pos)? as usize;
+                        let end = pos.checked_add(len).filter(|e| *e <= buf.len());
+                        let end = end.ok_or_else(malformed)?;
+                        let bytes = &buf[pos..end];
+                        pos = end;
+                        Ok((tag >> 3, Value::Bytes(bytes)))
+                    }
+                    _ => Err(malformed()),
+                }
+            })();
+            if next.is_err() {
+                // Stop after the first error.
+                pos = buf.len();
+            }
+            Some(next)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::{fake_source, start, test_rule, StsRequests};
+    use super::super::{AccessBoundaryRule, AvailabilityCondition};
+    use super::*;
+    use aes_gcm::Nonce;
+    use http::StatusCode;
+    use std::time::Duration;
+    use time::OffsetDateTime;
+
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    const TEST_KEY: [u8; 32] = [7; 32];
+
+    // Creates a base64-encoded Tink keyset with a single AES-GCM key.
+    fn session_key(key_id: u64, output_prefix: u64, key: &[u8]) -> String {
+        let mut aes_gcm_key = Vec::new();
+        proto::put_varint(&mut aes_gcm_key, 1 << 3);
+        proto::put_varint(&mut aes_gcm_key, 0);
+        proto::put_bytes(&mut aes_gcm_key, 3, key);
+        let mut key_data = Vec::new();
+        proto::put_bytes(&mut key_data, 1, AES_GCM_KEY_TYPE_URL.as_bytes());
+        proto::put_bytes(&mut key_data, 2, &aes_gcm_key);
+        let mut key = Vec::new();
+        proto::put_bytes(&mut key, 1, &key_data);
+        for (field, value) in [(2, KEY_STATUS_ENABLED), (3, key_id), (4, output_prefix)] {
+            proto::put_varint(&mut key, field << 3);
+            proto::put_varint(&mut key, value);
+        }
+        let mut keyset = Vec::new();
+        proto::put_varint(&mut keyset, 1 << 3);
+        proto::put_varint(&mut keyset, key_id);
+        proto::put_bytes(&mut keyset, 2, &key);
+        BASE64_STANDARD.encode(keyset)
+    }
+
+    fn sts_response(session_key: Option<String>) -> String {
+        serde_json::json!({
+            "access_token": "intermediary-token",
+            "issued_token_type": sts::ACCESS_BOUNDARY_INTERMEDIARY_TOKEN_TYPE,
+            "token_type": "Bearer",
+            "expires_in": 3600,
+            "access_boundary_session_key": session_key,
+        })
+        .to_string()
+    }
+
+    fn decode_rules(restriction: &[u8]) -> Result<Vec<(String, Vec<String>)>> {
+        let mut rules = Vec::new();
+        for field in proto::fields(restriction)? {
+            let (1, proto::Value::Bytes(rule)) = field? else {
+                panic!("unexpected field in access boundary");
+            };
+            let (mut resource, mut permissions) = (String::new(), Vec::new());
+            for field in proto::fields(rule)? {
+                match field? {
+                    (1, proto::Value::Bytes(b)) => resource = String::from_utf8_lossy(b).into(),
+                    (2, proto::Value::Bytes(b)) => {
+                        permissions.push(String::from_utf8_lossy(b).into())
+                    }
+                    _ => panic!("unexpected field in access boundary rule"),
+                }
+            }
+            rules.push((resource, permissions));
+        }
+        Ok(rules)
+    }
+
+    #[test]
+    fn proto_varint_roundtrip() -> TestResult {
+        for v in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut buf = Vec::new();
+            proto::put_varint(&mut buf, 1 << 3);
+            proto::put_varint(&mut buf, v);
+            let fields = proto::fields(&buf)?.collect::<Result<Vec<_>>>()?;
+            assert!(matches!(fields[..], [(1, proto::Value::Varint(got))] if got == v));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn proto_malformed() -> TestResult {
+        // A length-delimited field longer than the buffer.
+        let buf = [(1 << 3) | 2, 10, 1, 2];
+        assert!(proto::fields(&buf)?.any(|f| f.is_err()));
+        // An unsupported wire type.
+        let buf = [(1 << 3) | 5, 0, 0, 0, 0];
+        assert!(proto::fields(&buf)?.any(|f| f.is_err()));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_session_key() -> TestResult {
+        let key =
+            AeadKey::from_session_key(&session_key(0x01020304, OUTPUT_PREFIX_TINK, &TEST_KEY))?;
+        assert_eq!(
+            key,
+            AeadKey {
+                prefix: vec![1, 1, 2, 3, 4],
+                key: TEST_KEY.to_vec()
+            }
+        );
+        let key = AeadKey::from_session_key(&session_key(42, OUTPUT_PREFIX_RAW, &[1; 16]))?;
+        assert_eq!(key.prefix, Vec::<u8>::new());
+        let key = AeadKey::from_session_key(&session_key(42, OUTPUT_PREFIX_LEGACY, &[1; 16]))?;
+        assert_eq!(key.prefix, vec![0, 0, 0, 0, 42]);
+        assert!(!format!("{key:?}").contains("[1, 1"), "{key:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn parse_session_key_errors() {
+        for bad in [
+            "not base64!".to_string(),
+            session_key(42, OUTPUT_PREFIX_TINK, &[1; 20]),
+            session_key(42, 0, &TEST_KEY),
+            BASE64_STANDARD.encode([8, 42]),
+        ] {
+            let e = AeadKey::from_session_key(&bad).err().unwrap();
+            assert!(!e.is_retryable(), "{e}");
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn generate_token() -> TestResult {
+        let requests = StsRequests::default();
+        let key = session_key(123, OUTPUT_PREFIX_TINK, &TEST_KEY);
+        let (endpoint, _server) =
+            start(StatusCode::OK, sts_response(Some(key)), requests.clone()).await;
+        let generator = ClientSideGenerator::new(fake_source(None)).with_endpoint(endpoint);
+
+        let boundary = AccessBoundary::new([
+            test_rule(),
+            AccessBoundaryRule::new(
+                "//storage.googleapis.com/projects/_/buckets/other-bucket",
+                [
+                    "inRole:roles/storage.objectAdmin",
+                    "inRole:roles/storage.objectViewer",
+                ],
+            ),
+        ]);
+        let now = OffsetDateTime::now_utc();
+        let token = generator.generate_token(&boundary).await?;
+        assert_eq!(token.token_type, "Bearer");
+        assert!(token
+            .expires_at
+            .is_some_and(|e| e > now + Duration::from_secs(3500)));
+        let (intermediary, restriction) = token.token.rsplit_once('.').unwrap();
+        assert_eq!(intermediary, "intermediary-token");
+
+        let encrypted = BASE64_URL_SAFE.decode(restriction)?;
+        assert_eq!(&encrypted[..5], &[1, 0, 0, 0, 123]);
+        let (nonce, ciphertext) = encrypted[5..].split_at(12);
+        let cipher = Aes256Gcm::new_from_slice(&TEST_KEY)?;
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "cannot decrypt")?;
+        assert_eq!(
+            decode_rules(&plaintext)?,
+            vec![
+                (
+                    "//storage.googleapis.com/projects/_/buckets/test-bucket".to_string(),
+                    vec!["inRole:roles/storage.objectViewer".to_string()]
+                ),
+                (
+                    "//storage.googleapis.com/projects/_/buckets/other-bucket".to_string(),
+                    vec![
+                        "inRole:roles/storage.objectAdmin".to_string(),
+                        "inRole:roles/storage.objectViewer".to_string()
+                    ]
+                ),
+            ]
+        );
+
+        // A second token reuses the intermediary token, and uses a new nonce.
+        let second = generator.generate_token(&boundary).await?;
+        assert_ne!(second.token, token.token);
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 1, "{requests:?}");
+        assert_eq!(
+            requests[0].get("requested_token_type").map(String::as_str),
+            Some(sts::ACCESS_BOUNDARY_INTERMEDIARY_TOKEN_TYPE)
+        );
+        assert_eq!(
+            requests[0].get("subject_token").map(String::as_str),
+            Some("source-token")
+        );
+        assert_eq!(requests[0].get("options"), None);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn generate_token_errors() -> TestResult {
+        let (endpoint, _server) =
+            start(StatusCode::OK, sts_response(None), StsRequests::default()).await;
+        let generator = ClientSideGenerator::new(fake_source(None)).with_endpoint(endpoint);
+
+        let e = generator
+            .generate_token(&AccessBoundary::new([]))
+            .await
+            .err()
+            .unwrap();
+        assert!(e.to_string().contains("at least one rule"), "{e}");
+
+        let boundary = AccessBoundary::new([
+            test_rule().with_availability_condition(AvailabilityCondition::new("true"))
+        ]);
+        let e = generator.generate_token(&boundary).await.err().unwrap();
+        assert!(e.to_string().contains("availability conditions"), "{e}");
+
+        // The STS response is missing the session key.
+        let boundary = AccessBoundary::new([test_rule()]);
+        let e = generator.generate_token(&boundary).await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("session key"), "{e}");
+        Ok(())
+    }
+}
@@ -0,0 +1,586 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Workforce Identity Federation] user credentials.
+//!
+//! Users who sign in with `gcloud auth login --login-config` through a
+//! workforce identity pool get a refresh token from the
+//! [Security Token Service] (STS). gcloud saves the refresh token, along with
+//! the OAuth client used to obtain it, in an ADC file with
+//! `"type": "external_account_authorized_user"`. These credentials refresh
+//! the access tokens at the STS token URL.
+//!
+//! [Workforce Identity Federation]: https://cloud.google.com/iam/docs/workforce-identity-federation
+//! [Security Token Service]: https://cloud.google.com/iam/docs/reference/sts/rest
+
+use crate::credentials::dynamic::CredentialTrait;
+use crate::credentials::util::http_client;
+use crate::credentials::util::{build_headers, DEFAULT_UNIVERSE_DOMAIN};
+use crate::credentials::{Credential, CredentialOptions, Result};
+use crate::errors::{is_retryable, CredentialError};
+use crate::token::{metadata_keys, Token, TokenProvider};
+use crate::token_cache::TokenCache;
+use http::header::{HeaderName, HeaderValue};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use time::OffsetDateTime;
+
+const REFRESH_TOKEN_GRANT_TYPE: &str = "refresh_token";
+
+pub(crate) fn creds_from(js: serde_json::Value, options: &CredentialOptions) -> Result<Credential> {
+    let config = serde_json::from_value::<ExternalAccountAuthorizedUser>(js)
+        .map_err(CredentialError::non_retryable)?;
+    let client = ClientAuth {
+        client_id: config.client_id,
+        client_secret: config.client_secret,
+        refresh_token: Arc::new(Mutex::new(config.refresh_token)),
+    };
+    let token_provider = ExternalAuthorizedUserTokenProvider {
+        client: client.clone(),
+        token_url: options.token_endpoint.clone().unwrap_or(config.token_url),
+        scopes: options.scopes.as_ref().map(|v| v.join(" ")),
+    };
+    Ok(Credential {
+        inner: Arc::new(ExternalAuthorizedUserCredential {
+            token_provider: TokenCache::new(options.retrying(token_provider)),
+            client,
+            revoke_url: config.revoke_url,
+            quota_project_id: options.quota_project_id.clone().or(config.quota_project_id),
+            universe_domain: options.universe_domain.clone().or(config.universe_domain),
+        }),
+    })
+}
+
+/// The OAuth client, and the refresh token it obtained.
+///
+/// STS may rotate the refresh token. The token provider saves any new refresh
+/// token, and the credential revokes the latest one.
+#[derive(Clone)]
+struct ClientAuth {
+    client_id: String,
+    client_secret: String,
+    refresh_token: Arc<Mutex<String>>,
+}
+
+impl ClientAuth {
+    fn refresh_token(&self) -> String {
+        self.refresh_token
+            .lock()
+            .expect("refresh token lock is poisoned")
+            .clone()
+    }
+
+    fn set_refresh_token(&self, v: String) {
+        *self
+            .refresh_token
+            .lock()
+            .expect("refresh token lock is poisoned") = v;
+    }
+
+    /// Sends a form to `url`, authenticated with the client id and secret.
+    ///
+    /// On errors, `context` is prepended to the response body.
+    async fn post<F: serde::Serialize>(
+        &self,
+        url: &str,
+        form: &F,
+        context: &str,
+    ) -> Result<reqwest::Response> {
+        let client = http_client::client();
+        let resp = client
+            .post(url)
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .form(form)
+            .send()
+            .await
+            .map_err(CredentialError::retryable)?;
+        if resp.status().is_success() {
+            return Ok(resp);
+        }
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| CredentialError::new(is_retryable(status), e.into()))?;
+        Err(CredentialError::new(
+            is_retryable(status),
+            Box::from(format!("{context} {body}")),
+        ))
+    }
+}
+
+impl std::fmt::Debug for ClientAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientAuth")
+            .field("client_id", &self.client_id)
+            .field("client_secret", &"[censored]")
+            .field("refresh_token", &"[censored]")
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+struct ExternalAuthorizedUserTokenProvider {
+    client: ClientAuth,
+    token_url: String,
+    // If set, restricts the refreshed access tokens to these scopes.
+    scopes: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl TokenProvider for ExternalAuthorizedUserTokenProvider {
+    async fn get_token(&self) -> Result<Token> {
+        let refresh_token = self.client.refresh_token();
+        let form = RefreshTokenForm {
+            grant_type: REFRESH_TOKEN_GRANT_TYPE,
+            refresh_token: &refresh_token,
+            scope: self.scopes.as_deref(),
+        };
+        let resp = self
+            .client
+            .post(&self.token_url, &form, "Failed to fetch token.")
+            .await?;
+        let response = resp.json::<RefreshTokenResponse>().await.map_err(|e| {
+            let retryable = !e.is_decode();
+            CredentialError::new(retryable, e.into())
+        })?;
+        if let Some(rotated) = response.refresh_token {
+            self.client.set_refresh_token(rotated);
+        }
+        let token = Token {
+            token: response.access_token,
+            token_type: response.token_type,
+            expires_at: response
+                .expires_in
+                .map(|d| OffsetDateTime::now_utc() + Duration::from_secs(d)),
+            metadata: None,
+        };
+        Ok(token
+            .with_metadata(
+                metadata_keys::SCOPES,
+                response.scope.or_else(|| self.scopes.clone()),
+            )
+            .with_metadata(
+                metadata_keys::SOURCE_TYPE,
+                Some("external_account_authorized_user"),
+            ))
+    }
+}
+
+#[derive(Debug)]
+struct ExternalAuthorizedUserCredential<T>
+where
+    T: TokenProvider,
+{
+    token_provider: T,
+    client: ClientAuth,
+    revoke_url: Option<String>,
+    quota_project_id: Option<String>,
+    universe_domain: Option<String>,
+}
+
+#[async_trait::async_trait]
+impl<T> CredentialTrait for ExternalAuthorizedUserCredential<T>
+where
+    T: TokenProvider,
+{
+    async fn get_token(&self) -> Result<Token> {
+        self.token_provider.get_token().await
+    }
+
+    async fn get_headers(&self) -> Result<Vec<(HeaderName, HeaderValue)>> {
+        let token = self.get_token().await?;
+        build_headers(&token, self.quota_project_id.as_deref())
+    }
+
+    async fn get_universe_domain(&self) -> Option<String> {
+        Some(
+            self.universe_domain
+                .clone()
+                .unwrap_or_else(|| DEFAULT_UNIVERSE_DOMAIN.to_string()),
+        )
+    }
+
+    async fn revoke(&self) -> Result<()> {
+        let url = self.revoke_url.as_deref().ok_or_else(|| {
+            CredentialError::non_retryable(
+                "these credentials cannot be revoked, the configuration has no `revoke_url`",
+            )
+        })?;
+        let refresh_token = self.client.refresh_token();
+        let form = RevokeForm {
+            token: &refresh_token,
+            token_type_hint: REFRESH_TOKEN_GRANT_TYPE,
+        };
+        self.client
+            .post(url, &form, "Failed to revoke refresh token.")
+            .await
+            .map(|_| ())
+    }
+}
+
+/// The configuration in `external_account_authorized_user` ADC files.
+#[derive(Debug, PartialEq, serde::Deserialize)]
+struct ExternalAccountAuthorizedUser {
+    client_id: String,
+    client_secret: String,
+    refresh_token: String,
+    token_url: String,
+    #[serde(default)]
+    revoke_url: Option<String>,
+    #[serde(default)]
+    quota_project_id: Option<String>,
+    #[serde(default)]
+    universe_domain: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct RefreshTokenForm<'a> {
+    grant_type: &'a str,
+    refresh_token: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<&'a str>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct RevokeForm<'a> {
+    token: &'a str,
+    token_type_hint: &'a str,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+struct RefreshTokenResponse {
+    access_token: String,
+    #[serde(default = "default_token_type")]
+    token_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_in: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refresh_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+}
+
+fn default_token_type() -> String {
+    "Bearer".to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::credentials::QUOTA_PROJECT_KEY;
+    use axum::extract::{Form, State};
+    use http::header::AUTHORIZATION;
+    use http::{HeaderMap, StatusCode};
+    use std::collections::HashMap;
+    use std::error::Error;
+    use tokio::task::JoinHandle;
+
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    // The basic authentication header for "test-client-id:test-client-secret".
+    const BASIC_AUTH: &str = "Basic dGVzdC1jbGllbnQtaWQ6dGVzdC1jbGllbnQtc2VjcmV0";
+
+    type Requests = Arc<Mutex<Vec<(String, HashMap<String, String>)>>>;
+
+    // Starts a fake STS server. Returns the endpoint, the requests it
+    // received, and the server handle.
+    //
+    // The server returns `responses` in order, repeating the last one.
+    async fn start(responses: Vec<(StatusCode, String)>) -> (String, Requests, JoinHandle<()>) {
+        let requests: Requests = Arc::new(Mutex::new(Vec::new()));
+        let responses = Arc::new(Mutex::new(responses));
+        let handler = move |path: &'static str| {
+            let responses = responses.clone();
+            move |State(requests): State<Requests>,
+                  headers: HeaderMap,
+                  Form(form): Form<HashMap<String, String>>| {
+                let responses = responses.clone();
+                async move {
+                    assert_eq!(
+                        headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok()),
+                        Some(BASIC_AUTH)
+                    );
+                    requests.lock().unwrap().push((path.to_string(), form));
+                    let mut responses = responses.lock().unwrap();
+                    if responses.len() > 1 {
+                        responses.remove(0)
+                    } else {
+                        responses[0].clone()
+                    }
+                }
+            }
+        };
+        let app = axum::Router::new()
+            .route("/v1/oauthtoken", axum::routing::post(handler("token")))
+            .route("/v1/revoke", axum::routing::post(handler("revoke")))
+            .with_state(requests.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (format!("http://{addr}"), requests, server)
+    }
+
+    fn config(endpoint: &str) -> serde_json::Value {
+        serde_json::json!({
+            "type": "external_account_authorized_user",
+            "audience": "//iam.googleapis.com/locations/global/workforcePools/test-pool/providers/test-provider",
+            "client_id": "test-client-id",
+            "client_secret": "test-client-secret",
+            "refresh_token": "test-refresh-token",
+            "token_url": format!("{endpoint}/v1/oauthtoken"),
+            "token_info_url": format!("{endpoint}/v1/introspect"),
+            "revoke_url": format!("{endpoint}/v1/revoke"),
+            "quota_project_id": "test-project",
+        })
+    }
+
+    fn token_response(access_token: &str, refresh_token: Option<&str>) -> (StatusCode, String) {
+        let response = RefreshTokenResponse {
+            access_token: access_token.to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in: Some(3600),
+            refresh_token: refresh_token.map(str::to_string),
+            scope: None,
+        };
+        (StatusCode::OK, serde_json::to_string(&response).unwrap())
+    }
+
+    #[test]
+    fn config_from_json() {
+        let json = serde_json::json!({
+            "type": "external_account_authorized_user",
+            "client_id": "test-client-id",
+            "client_secret": "test-client-secret",
+            "refresh_token": "test-refresh-token",
+            "token_url": "https://sts.googleapis.com/v1/oauthtoken",
+        });
+        let parsed = serde_json::from_value::<ExternalAccountAuthorizedUser>(json).unwrap();
+        assert_eq!(parsed.token_url, "https://sts.googleapis.com/v1/oauthtoken");
+        assert_eq!(parsed.revoke_url, None);
+        assert_eq!(parsed.quota_project_id, None);
+        assert_eq!(parsed.universe_domain, None);
+
+        let full = config("https://sts.googleapis.com");
+        for required_field in ["client_id", "client_secret", "refresh_token", "token_url"] {
+            let mut json = full.clone();
+            json[required_field].take();
+            let e = creds_from(json, &CredentialOptions::default())
+                .err()
+                .unwrap();
+            assert!(!e.is_retryable(), "{e}");
+        }
+    }
+
+    #[test]
+    fn debug_censors_secrets() -> TestResult {
+        let creds = creds_from(
+            config("https://sts.googleapis.com"),
+            &CredentialOptions::default(),
+        )?;
+        let fmt = format!("{creds:?}");
+        assert!(fmt.contains("test-client-id"), "{fmt}");
+        assert!(!fmt.contains("test-client-secret"), "{fmt}");
+        assert!(!fmt.contains("test-refresh-token"), "{fmt}");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn get_headers_success() -> TestResult {
+        let (endpoint, requests, _server) =
+            start(vec![token_response("test-access-token", None)]).await;
+        let creds = creds_from(config(&endpoint), &CredentialOptions::default())?;
+
+        let now = OffsetDateTime::now_utc();
+        let token = creds.get_token().await?;
+        assert_eq!(token.token, "test-access-token");
+        assert_eq!(token.token_type, "Bearer");
+        assert!(token
+            .expires_at
+            .is_some_and(|d| d >= now + Duration::from_secs(3600)));
+        assert_eq!(token.scopes(), None);
+        assert_eq!(
+            token.source_type(),
+            Some("external_account_authorized_user")
+        );
+
+        let headers = creds.get_headers().await?;
+        assert!(headers
+            .iter()
+            .any(|(k, v)| k == AUTHORIZATION && v == "Bearer test-access-token"));
+        assert!(headers
+            .iter()
+            .any(|(k, v)| k == QUOTA_PROJECT_KEY && v == "test-project"));
+        assert_eq!(
+            creds.get_universe_domain().await.as_deref(),
+            Some(DEFAULT_UNIVERSE_DOMAIN)
+        );
+
+        // The token is cached.
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 1, "{requests:?}");
+        let (path, form) = &requests[0];
+        assert_eq!(path, "token");
+        assert_eq!(
+            form.get("grant_type").map(String::as_str),
+            Some("refresh_token")
+        );
+        assert_eq!(
+            form.get("refresh_token").map(String::as_str),
+            Some("test-refresh-token")
+        );
+        assert_eq!(form.get("scope"), None);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn creds_from_with_options() -> TestResult {
+        let (endpoint, requests, _server) =
+            start(vec![token_response("test-access-token", None)]).await;
+        let options = CredentialOptions {
+            scopes: Some(vec!["scope1".to_string(), "scope2".to_string()]),
+            quota_project_id: Some("override-project".to_string()),
+            universe_domain: Some("test-universe-domain".to_string()),
+            token_endpoint: Some(format!("{endpoint}/v1/oauthtoken")),
+            ..Default::default()
+        };
+        let creds = creds_from(config("https://unused.googleapis.com"), &options)?;
+        let headers = creds.get_headers().await?;
+        assert!(headers
+            .iter()
+            .any(|(k, v)| k == QUOTA_PROJECT_KEY && v == "override-project"));
+        assert_eq!(
+            creds.get_universe_domain().await.as_deref(),
+            Some("test-universe-domain")
+        );
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(
+            requests[0].1.get("scope").map(String::as_str),
+            Some("scope1 scope2")
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn rotated_refresh_token_is_used() -> TestResult {
+        let (endpoint, requests, _server) = start(vec![
+            token_response("token-1", Some("rotated-refresh-token")),
+            (StatusCode::OK, String::new()),
+        ])
+        .await;
+        let client = ClientAuth {
+            client_id: "test-client-id".to_string(),
+            client_secret: "test-client-secret".to_string(),
+            refresh_token: Arc::new(Mutex::new("test-refresh-token".to_string())),
+        };
+        let creds = ExternalAuthorizedUserCredential {
+            token_provider: ExternalAuthorizedUserTokenProvider {
+                client: client.clone(),
+                token_url: format!("{endpoint}/v1/oauthtoken"),
+                scopes: None,
+            },
+            client,
+            revoke_url: Some(format!("{endpoint}/v1/revoke")),
+            quota_project_id: None,
+            universe_domain: None,
+        };
+        assert_eq!(creds.get_token().await?.token, "token-1");
+        creds.revoke().await?;
+
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 2, "{requests:?}");
+        let (path, form) = &requests[1];
+        assert_eq!(path, "revoke");
+        assert_eq!(
+            form.get("token").map(String::as_str),
+            Some("rotated-refresh-token")
+        );
+        assert_eq!(
+            form.get("token_type_hint").map(String::as_str),
+            Some("refresh_token")
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn revoke_success() -> TestResult {
+        let (endpoint, requests, _server) = start(vec![(StatusCode::OK, String::new())]).await;
+        let creds = creds_from(config(&endpoint), &CredentialOptions::default())?;
+        creds.revoke().await?;
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 1, "{requests:?}");
+        let (path, form) = &requests[0];
+        assert_eq!(path, "revoke");
+        assert_eq!(
+            form.get("token").map(String::as_str),
+            Some("test-refresh-token")
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn revoke_without_url() -> TestResult {
+        let mut json = config("https://sts.googleapis.com");
+        json["revoke_url"].take();
+        let creds = creds_from(json, &CredentialOptions::default())?;
+        let e = creds.revoke().await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("revoke_url"), "{e}");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn token_retryable_error() -> TestResult {
+        let (endpoint, _requests, _server) =
+            start(vec![(StatusCode::SERVICE_UNAVAILABLE, "try again".into())]).await;
+        let creds = creds_from(config(&endpoint), &CredentialOptions::default())?;
+        let e = creds.get_token().await.err().unwrap();
+        assert!(e.is_retryable(), "{e}");
+        assert!(e.source().unwrap().to_string().contains("try again"), "{e}");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn token_nonretryable_error() -> TestResult {
+        let (endpoint, _requests, _server) =
+            start(vec![(StatusCode::UNAUTHORIZED, "epic fail".into())]).await;
+        let creds = creds_from(config(&endpoint), &CredentialOptions::default())?;
+        let e = creds.get_token().await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(e.source().unwrap().to_string().contains("epic fail"), "{e}");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn token_malformed_response_is_nonretryable() -> TestResult {
+        let (endpoint, _requests, _server) = start(vec![(StatusCode::OK, "bad json".into())]).await;
+        let creds = creds_from(config(&endpoint), &CredentialOptions::default())?;
+        let e = creds.get_token().await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn revoke_error() -> TestResult {
+        let (endpoint, _requests, _server) =
+            start(vec![(StatusCode::BAD_REQUEST, "invalid token".into())]).await;
+        let creds = creds_from(config(&endpoint), &CredentialOptions::default())?;
+        let e = creds.revoke().await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("invalid token"), "{e}");
+        Ok(())
+    }
+}
@@ -0,0 +1,860 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Workload Identity Federation] credentials.
+//!
+//! Workloads running outside Google Cloud obtain a *subject token* from their
+//! identity provider (e.g. a Kubernetes service account token, or an OIDC ID
+//! token). These credentials exchange the subject token for a Google Cloud
+//! access token using the [Security Token Service] (STS). Optionally, the
+//! access token from STS is used to impersonate a service account.
+//!
+//! The configuration for these credentials is created with
+//! `gcloud iam workload-identity-pools create-cred-config`, and uses
+//! `"type": "external_account"`.
+//!
+//! [Workload Identity Federation]: https://cloud.google.com/iam/docs/workload-identity-federation
+//! [Security Token Service]: https://cloud.google.com/iam/docs/reference/sts/rest
+
+mod aws;
+mod executable;
+
+use crate::credentials::dynamic::CredentialTrait;
+use crate::credentials::impersonated_credential::{impersonated_email, ImpersonatedTokenProvider};
+use crate::credentials::util::http_client;
+use crate::credentials::util::sts::{self, ExchangeTokenRequest};
+use crate::credentials::util::{build_headers, DEFAULT_SCOPES, DEFAULT_UNIVERSE_DOMAIN};
+use crate::credentials::{Credential, CredentialOptions, Result};
+use crate::errors::{is_retryable, CredentialError};
+use crate::token::{metadata_keys, Token, TokenProvider};
+use crate::token_cache::TokenCache;
+use aws::{AwsConfig, AwsSource};
+use executable::{ExecutableConfig, ExecutableSource};
+use http::header::{HeaderName, HeaderValue};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+const SOURCE_TYPE: &str = "external_account";
+
+pub(crate) fn creds_from(js: serde_json::Value, options: &CredentialOptions) -> Result<Credential> {
+    let config =
+        serde_json::from_value::<ExternalAccount>(js).map_err(CredentialError::non_retryable)?;
+    let universe_domain = options
+        .universe_domain
+        .clone()
+        .or(config.universe_domain)
+        .unwrap_or_else(|| DEFAULT_UNIVERSE_DOMAIN.to_string());
+    let scopes: Vec<String> = options
+        .scopes
+        .clone()
+        .unwrap_or_else(|| DEFAULT_SCOPES.map(str::to_string).to_vec());
+    let quota_project_id = options.quota_project_id.clone().or(config.quota_project_id);
+    let context = SourceContext {
+        audience: config.audience.clone(),
+        subject_token_type: config.subject_token_type.clone(),
+        impersonated_email: config
+            .service_account_impersonation_url
+            .as_deref()
+            .and_then(impersonated_email),
+    };
+    // When impersonating a service account the federated token must be able
+    // to call the IAM Credentials API, the user scopes apply to the
+    // impersonated token only.
+    let sts_scopes = match &config.service_account_impersonation_url {
+        None => scopes.clone(),
+        Some(_) => DEFAULT_SCOPES.map(str::to_string).to_vec(),
+    };
+    let token_provider = ExternalAccountTokenProvider {
+        audience: config.audience,
+        subject_token_type: config.subject_token_type,
+        token_url: options.token_endpoint.clone().unwrap_or(config.token_url),
+        scopes: sts_scopes,
+        client_auth: config.client_id.zip(config.client_secret),
+        workforce_pool_user_project: config.workforce_pool_user_project,
+        source: SubjectTokenSource::new(config.credential_source, context)?,
+    };
+
+    let Some(url) = config.service_account_impersonation_url else {
+        return Ok(Credential {
+            inner: Arc::new(ExternalAccountCredential {
+                token_provider: TokenCache::new(options.retrying(token_provider)),
+                quota_project_id,
+                universe_domain,
+            }),
+        });
+    };
+
+    // The (federated) access token returned by STS is used as the source
+    // credentials to impersonate a service account. The federated token is
+    // only used to refresh the impersonated token, which is cached, so the
+    // federated token does not need its own cache.
+    let source = Credential {
+        inner: Arc::new(ExternalAccountCredential {
+            token_provider,
+            quota_project_id: None,
+            universe_domain: universe_domain.clone(),
+        }),
+    };
+    let lifetime = config
+        .service_account_impersonation
+        .and_then(|o| o.token_lifetime_seconds)
+        .map(Duration::from_secs);
+    let token_provider = ImpersonatedTokenProvider::new(source, url, scopes, lifetime, SOURCE_TYPE);
+    Ok(Credential {
+        inner: Arc::new(ExternalAccountCredential {
+            token_provider: TokenCache::new(options.retrying(token_provider)),
+            quota_project_id,
+            universe_domain,
+        }),
+    })
+}
+
+/// The representation of external account credentials in configuration files.
+#[derive(Debug, PartialEq, serde::Deserialize)]
+struct ExternalAccount {
+    audience: String,
+    subject_token_type: String,
+    #[serde(default = "default_token_url")]
+    token_url: String,
+    credential_source: CredentialSourceConfig,
+    service_account_impersonation_url: Option<String>,
+    service_account_impersonation: Option<ServiceAccountImpersonationOptions>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    quota_project_id: Option<String>,
+    workforce_pool_user_project: Option<String>,
+    universe_domain: Option<String>,
+}
+
+fn default_token_url() -> String {
+    sts::STS_ENDPOINT.to_string()
+}
+
+#[derive(Debug, PartialEq, serde::Deserialize)]
+struct ServiceAccountImpersonationOptions {
+    token_lifetime_seconds: Option<u64>,
+}
+
+/// The information about the external account available to subject token
+/// sources.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct SourceContext {
+    audience: String,
+    subject_token_type: String,
+    impersonated_email: Option<String>,
+}
+
+/// The `credential_source` field in the configuration file.
+///
+/// The fields present determine where the subject token comes from.
+#[derive(Debug, Default, PartialEq, serde::Deserialize)]
+struct CredentialSourceConfig {
+    file: Option<String>,
+    url: Option<String>,
+    headers: Option<HashMap<String, String>>,
+    format: Option<FormatConfig>,
+    executable: Option<ExecutableConfig>,
+    environment_id: Option<String>,
+    region_url: Option<String>,
+    regional_cred_verification_url: Option<String>,
+    imdsv2_session_token_url: Option<String>,
+}
+
+#[derive(Debug, PartialEq, serde::Deserialize)]
+struct FormatConfig {
+    #[serde(rename = "type")]
+    format_type: String,
+    subject_token_field_name: Option<String>,
+}
+
+/// How to extract the subject token from a file or HTTP response.
+#[derive(Clone, Debug, PartialEq)]
+enum SubjectTokenFormat {
+    /// The contents are the subject token.
+    Text,
+    /// The subject token is a field in a JSON object.
+    Json(String),
+}
+
+impl SubjectTokenFormat {
+    fn new(config: Option<FormatConfig>) -> Result<Self> {
+        let Some(config) = config else {
+            return Ok(Self::Text);
+        };
+        match (config.format_type.as_str(), config.subject_token_field_name) {
+            ("text", _) => Ok(Self::Text),
+            ("json", Some(field)) => Ok(Self::Json(field)),
+            ("json", None) => Err(CredentialError::non_retryable(
+                "the `subject_token_field_name` is required for `json` subject token formats",
+            )),
+            (t, _) => Err(CredentialError::non_retryable(format!(
+                "unsupported subject token format `{t}`, expected `text` or `json`"
+            ))),
+        }
+    }
+
+    fn extract(&self, contents: &str) -> Result<String> {
+        let token = match self {
+            Self::Text => contents.trim().to_string(),
+            Self::Json(field) => {
+                let value = serde_json::from_str::<serde_json::Value>(contents)
+                    .map_err(CredentialError::non_retryable)?;
+                value
+                    .get(field)
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string)
+                    .ok_or_else(|| {
+                        CredentialError::non_retryable(format!(
+                            "missing or invalid `{field}` field in the subject token response"
+                        ))
+                    })?
+            }
+        };
+        if token.is_empty() {
+            return Err(CredentialError::non_retryable("the subject token is empty"));
+        }
+        Ok(token)
+    }
+}
+
+/// Where to find the subject token.
+#[derive(Clone, Debug, PartialEq)]
+enum SubjectTokenSource {
+    File {
+        path: String,
+        format: SubjectTokenFormat,
+    },
+    Url {
+        url: String,
+        headers: HashMap<String, String>,
+        format: SubjectTokenFormat,
+    },
+    Executable(ExecutableSource),
+    Aws(AwsSource),
+}
+
+impl SubjectTokenSource {
+    fn new(config: CredentialSourceConfig, context: SourceContext) -> Result<Self> {
+        // The AWS sources use the `url` field for the security credentials.
+        if let Some(environment_id) = config.environment_id {
+            let aws = AwsConfig {
+                environment_id,
+                region_url: config.region_url,
+                url: config.url,
+                regional_cred_verification_url: config.regional_cred_verification_url,
+                imdsv2_session_token_url: config.imdsv2_session_token_url,
+            };
+            return Ok(Self::Aws(AwsSource::new(aws, context)?));
+        }
+        let format = SubjectTokenFormat::new(config.format)?;
+        match (config.file, config.url, config.executable) {
+            (Some(path), None, None) => Ok(Self::File { path, format }),
+            (None, Some(url), None) => Ok(Self::Url {
+                url,
+                headers: config.headers.unwrap_or_default(),
+                format,
+            }),
+            (None, None, Some(executable)) => Ok(Self::Executable(ExecutableSource::new(
+                executable, context,
+            )?)),
+            (None, None, None) => Err(CredentialError::non_retryable(
+                "unsupported `credential_source`, expected a `file`, `url`, or `executable` source",
+            )),
+            _ => Err(CredentialError::non_retryable(
+                "the `credential_source` must contain only one of `file`, `url`, or `executable`",
+            )),
+        }
+    }
+
+    async fn subject_token(&self) -> Result<String> {
+        match self {
+            Self::File { path, format } => {
+                let contents = tokio::fs::read_to_string(path).await.map_err(|e| {
+                    CredentialError::non_retryable(format!(
+                        "cannot read the subject token file {path}: {e}"
+                    ))
+                })?;
+                format.extract(&contents)
+            }
+            Self::Url {
+                url,
+                headers,
+                format,
+            } => {
+                let client = http_client::client();
+                let mut builder = client.get(url);
+                for (name, value) in headers {
+                    builder = builder.header(name, value);
+                }
+                let resp = builder.send().await.map_err(CredentialError::retryable)?;
+                let status = resp.status();
+                let body = resp
+                    .text()
+                    .await
+                    .map_err(|e| CredentialError::new(is_retryable(status), e.into()))?;
+                if !status.is_success() {
+                    return Err(CredentialError::new(
+                        is_retryable(status),
+                        Box::from(format!("Failed to fetch subject token. {body}")),
+                    ));
+                }
+                format.extract(&body)
+            }
+            Self::Executable(executable) => executable.subject_token().await,
+            Self::Aws(aws) => aws.subject_token().await,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ExternalAccountTokenProvider {
+    audience: String,
+    subject_token_type: String,
+    token_url: String,
+    scopes: Vec<String>,
+    client_auth: Option<(String, String)>,
+    workforce_pool_user_project: Option<String>,
+    source: SubjectTokenSource,
+}
+
+#[async_trait::async_trait]
+impl TokenProvider for ExternalAccountTokenProvider {
+    async fn get_token(&self) -> Result<Token> {
+        let subject_token = self.source.subject_token().await?;
+        // The workforce pool user project is only used when the client is not
+        // authenticated.
+        let options = match (&self.client_auth, &self.workforce_pool_user_project) {
+            (None, Some(project)) => {
+                Some(serde_json::json!({ "userProject": project }).to_string())
+            }
+            _ => None,
+        };
+        let request = ExchangeTokenRequest {
+            audience: Some(self.audience.clone()),
+            scope: self.scopes.clone(),
+            requested_token_type: sts::ACCESS_TOKEN_TYPE.to_string(),
+            subject_token,
+            subject_token_type: self.subject_token_type.clone(),
+            options,
+            client_auth: self.client_auth.clone(),
+        };
+        let response = sts::exchange_token(&self.token_url, &request).await?;
+        Ok(response
+            .into_token()
+            .with_metadata(metadata_keys::SCOPES, Some(self.scopes.join(" ")))
+            .with_metadata(metadata_keys::SOURCE_TYPE, Some(SOURCE_TYPE)))
+    }
+}
+
+#[derive(Debug)]
+struct ExternalAccountCredential<T>
+where
+    T: TokenProvider,
+{
+    token_provider: T,
+    quota_project_id: Option<String>,
+    universe_domain: String,
+}
+
+#[async_trait::async_trait]
+impl<T> CredentialTrait for ExternalAccountCredential<T>
+where
+    T: TokenProvider,
+{
+    async fn get_token(&self) -> Result<Token> {
+        self.token_provider.get_token().await
+    }
+
+    async fn get_headers(&self) -> Result<Vec<(HeaderName, HeaderValue)>> {
+        let token = self.get_token().await?;
+        build_headers(&token, self.quota_project_id.as_deref())
+    }
+
+    async fn get_universe_domain(&self) -> Option<String> {
+        Some(self.universe_domain.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::credentials::util::sts::ExchangeTokenResponse;
+    use crate::credentials::QUOTA_PROJECT_KEY;
+    use axum::extract::{Form, Json};
+    use http::header::AUTHORIZATION;
+    use http::{HeaderMap, StatusCode};
+    use std::error::Error;
+    use std::sync::Mutex;
+    use tokio::task::JoinHandle;
+
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    const JWT_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:jwt";
+    const AUDIENCE: &str = "//iam.googleapis.com/projects/123/locations/global/workloadIdentityPools/test-pool/providers/test-provider";
+
+    #[test]
+    fn parse_config() -> TestResult {
+        let json = serde_json::json!({
+            "type": "external_account",
+            "audience": AUDIENCE,
+            "subject_token_type": JWT_TOKEN_TYPE,
+            "token_url": "https://sts.googleapis.com/v1/token",
+            "service_account_impersonation_url": "https://iamcredentials.googleapis.com/v1/projects/-/serviceAccounts/sa@p.iam.gserviceaccount.com:generateAccessToken",
+            "service_account_impersonation": { "token_lifetime_seconds": 600 },
+            "credential_source": {
+                "url": "http://169.254.169.254/token",
+                "headers": { "Metadata": "True" },
+                "format": { "type": "json", "subject_token_field_name": "access_token" }
+            },
+            "quota_project_id": "test-project",
+        });
+        let config = serde_json::from_value::<ExternalAccount>(json)?;
+        assert_eq!(config.audience, AUDIENCE);
+        assert_eq!(
+            config.service_account_impersonation,
+            Some(ServiceAccountImpersonationOptions {
+                token_lifetime_seconds: Some(600)
+            })
+        );
+        let source = SubjectTokenSource::new(config.credential_source, SourceContext::default())?;
+        assert_eq!(
+            source,
+            SubjectTokenSource::Url {
+                url: "http://169.254.169.254/token".to_string(),
+                headers: HashMap::from([("Metadata".to_string(), "True".to_string())]),
+                format: SubjectTokenFormat::Json("access_token".to_string()),
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_config_defaults() -> TestResult {
+        let json = serde_json::json!({
+            "type": "external_account",
+            "audience": AUDIENCE,
+            "subject_token_type": JWT_TOKEN_TYPE,
+            "credential_source": { "file": "/var/run/token" },
+        });
+        let config = serde_json::from_value::<ExternalAccount>(json)?;
+        assert_eq!(config.token_url, sts::STS_ENDPOINT);
+        assert_eq!(config.service_account_impersonation_url, None);
+        let source = SubjectTokenSource::new(config.credential_source, SourceContext::default())?;
+        assert_eq!(
+            source,
+            SubjectTokenSource::File {
+                path: "/var/run/token".to_string(),
+                format: SubjectTokenFormat::Text,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_credential_source() {
+        let config = CredentialSourceConfig::default();
+        assert!(SubjectTokenSource::new(config, SourceContext::default()).is_err());
+
+        let config = CredentialSourceConfig {
+            file: Some("a".to_string()),
+            url: Some("b".to_string()),
+            ..Default::default()
+        };
+        assert!(SubjectTokenSource::new(config, SourceContext::default()).is_err());
+
+        let config = CredentialSourceConfig {
+            file: Some("a".to_string()),
+            format: Some(FormatConfig {
+                format_type: "json".to_string(),
+                subject_token_field_name: None,
+            }),
+            ..Default::default()
+        };
+        assert!(SubjectTokenSource::new(config, SourceContext::default()).is_err());
+
+        let config = CredentialSourceConfig {
+            file: Some("a".to_string()),
+            format: Some(FormatConfig {
+                format_type: "xml".to_string(),
+                subject_token_field_name: None,
+            }),
+            ..Default::default()
+        };
+        let e = SubjectTokenSource::new(config, SourceContext::default())
+            .err()
+            .unwrap();
+        assert!(e.to_string().contains("xml"), "{e}");
+    }
+
+    #[test]
+    fn parse_executable_config() -> TestResult {
+        let json = serde_json::json!({
+            "type": "external_account",
+            "audience": AUDIENCE,
+            "subject_token_type": JWT_TOKEN_TYPE,
+            "credential_source": {
+                "executable": {
+                    "command": "/usr/bin/broker --flag",
+                    "timeout_millis": 5000,
+                    "output_file": "/tmp/output.json",
+                }
+            },
+        });
+        let config = serde_json::from_value::<ExternalAccount>(json)?;
+        let source = SubjectTokenSource::new(config.credential_source, SourceContext::default())?;
+        assert!(
+            matches!(source, SubjectTokenSource::Executable(_)),
+            "{source:?}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn impersonated_email_from_url() {
+        assert_eq!(
+            impersonated_email("https://iamcredentials.googleapis.com/v1/projects/-/serviceAccounts/sa@p.iam.gserviceaccount.com:generateAccessToken"),
+            Some("sa@p.iam.gserviceaccount.com".to_string())
+        );
+        assert_eq!(impersonated_email("https://example.com/bad"), None);
+    }
+
+    #[test]
+    fn extract_subject_token() -> TestResult {
+        assert_eq!(SubjectTokenFormat::Text.extract("abc\n")?, "abc");
+        let json = SubjectTokenFormat::Json("id_token".to_string());
+        assert_eq!(json.extract(r#"{"id_token": "abc"}"#)?, "abc");
+        assert!(json.extract(r#"{"other": "abc"}"#).is_err());
+        assert!(json.extract(r#"{"id_token": 42}"#).is_err());
+        assert!(json.extract("not json").is_err());
+        assert!(SubjectTokenFormat::Text.extract("  \n").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn file_subject_token() -> TestResult {
+        let file = tempfile::NamedTempFile::new()?;
+        let path = file.into_temp_path();
+        std::fs::write(&path, r#"{"access_token": "file-token"}"#)?;
+        let source = SubjectTokenSource::File {
+            path: path.to_str().unwrap().to_string(),
+            format: SubjectTokenFormat::Json("access_token".to_string()),
+        };
+        assert_eq!(source.subject_token().await?, "file-token");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn file_subject_token_missing_file() {
+        let source = SubjectTokenSource::File {
+            path: "file-does-not-exist.json".to_string(),
+            format: SubjectTokenFormat::Text,
+        };
+        let e = source.subject_token().await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("file-does-not-exist.json"), "{e}");
+    }
+
+    // The authorization header and body of each IAM request.
+    type IamRequests = Arc<Mutex<Vec<(Option<String>, serde_json::Value)>>>;
+
+    #[derive(Clone, Debug, Default)]
+    struct ServerState {
+        subject_token_status: Option<StatusCode>,
+        sts_requests: Arc<Mutex<Vec<HashMap<String, String>>>>,
+        iam_requests: IamRequests,
+    }
+
+    // Starts a server running locally, implementing the subject token, STS,
+    // and IAM endpoints. Returns an (endpoint, server) pair.
+    async fn start(state: ServerState) -> (String, JoinHandle<()>) {
+        let subject_status = state.subject_token_status.unwrap_or(StatusCode::OK);
+        let subject = move |headers: HeaderMap| async move {
+            assert_eq!(
+                headers.get("metadata").map(|v| v.to_str().unwrap()),
+                Some("True")
+            );
+            (subject_status, "url-subject-token")
+        };
+        let sts_requests = state.sts_requests.clone();
+        let sts = move |Form(form): Form<HashMap<String, String>>| async move {
+            sts_requests.lock().unwrap().push(form);
+            let response = ExchangeTokenResponse {
+                access_token: "federated-token".to_string(),
+                issued_token_type: sts::ACCESS_TOKEN_TYPE.to_string(),
+                token_type: "Bearer".to_string(),
+                expires_in: Some(3600),
+                refresh_token: None,
+                access_boundary_session_key: None,
+            };
+            Json(response)
+        };
+        let iam_requests = state.iam_requests.clone();
+        let iam = move |headers: HeaderMap, Json(body): Json<serde_json::Value>| async move {
+            let auth = headers
+                .get(AUTHORIZATION)
+                .map(|v| v.to_str().unwrap().to_string());
+            iam_requests.lock().unwrap().push((auth, body));
+            Json(serde_json::json!({
+                "accessToken": "impersonated-token",
+                "expireTime": "2099-01-01T00:00:00Z",
+            }))
+        };
+        let app = axum::Router::new()
+            .route("/subject", axum::routing::get(subject))
+            .route("/v1/token", axum::routing::post(sts))
+            .route(
+                "/v1/projects/-/serviceAccounts/{sa}",
+                axum::routing::post(iam),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        (format!("http://{}:{}", addr.ip(), addr.port()), server)
+    }
+
+    fn test_config(endpoint: &str) -> serde_json::Value {
+        serde_json::json!({
+            "type": "external_account",
+            "audience": AUDIENCE,
+            "subject_token_type": JWT_TOKEN_TYPE,
+            "token_url": format!("{endpoint}/v1/token"),
+            "credential_source": {
+                "url": format!("{endpoint}/subject"),
+                "headers": { "Metadata": "True" },
+            },
+            "quota_project_id": "test-project",
+        })
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn url_source_exchange() -> TestResult {
+        let state = ServerState::default();
+        let (endpoint, _server) = start(state.clone()).await;
+        let creds = creds_from(test_config(&endpoint), &CredentialOptions::default())?;
+
+        let token = creds.get_token().await?;
+        assert_eq!(token.token, "federated-token");
+        assert_eq!(token.token_type, "Bearer");
+        assert!(token.expires_at.is_some());
+        assert_eq!(token.scopes(), Some(DEFAULT_SCOPES.to_vec()));
+        assert_eq!(token.source_type(), Some("external_account"));
+        assert_eq!(token.principal_email(), None);
+
+        let headers = creds.get_headers().await?;
+        assert_eq!(
+            headers,
+            vec![
+                (
+                    AUTHORIZATION,
+                    HeaderValue::from_static("Bearer federated-token")
+                ),
+                (
+                    HeaderName::from_static(QUOTA_PROJECT_KEY),
+                    HeaderValue::from_static("test-project")
+                ),
+            ]
+        );
+        assert_eq!(
+            creds.get_universe_domain().await,
+            Some("googleapis.com".to_string())
+        );
+
+        // The token is cached.
+        let requests = state.sts_requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 1, "{requests:?}");
+        let expected = HashMap::from([
+            ("grant_type", sts::TOKEN_EXCHANGE_GRANT_TYPE),
+            ("audience", AUDIENCE),
+            ("scope", DEFAULT_SCOPES[0]),
+            ("requested_token_type", sts::ACCESS_TOKEN_TYPE),
+            ("subject_token", "url-subject-token"),
+            ("subject_token_type", JWT_TOKEN_TYPE),
+        ])
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect::<HashMap<_, _>>();
+        assert_eq!(requests[0], expected);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn url_source_exchange_with_options() -> TestResult {
+        let state = ServerState::default();
+        let (endpoint, _server) = start(state.clone()).await;
+        let mut config = test_config(&endpoint);
+        config["token_url"] = "https://sts.example.com/v1/token".into();
+        let options = CredentialOptions {
+            scopes: Some(vec!["scope1".to_string(), "scope2".to_string()]),
+            quota_project_id: Some("override-project".to_string()),
+            universe_domain: Some("test-universe-domain".to_string()),
+            token_endpoint: Some(format!("{endpoint}/v1/token")),
+            ..Default::default()
+        };
+        let creds = creds_from(config, &options)?;
+
+        let headers = creds.get_headers().await?;
+        assert_eq!(
+            headers,
+            vec![
+                (
+                    AUTHORIZATION,
+                    HeaderValue::from_static("Bearer federated-token")
+                ),
+                (
+                    HeaderName::from_static(QUOTA_PROJECT_KEY),
+                    HeaderValue::from_static("override-project")
+                ),
+            ]
+        );
+        assert_eq!(
+            creds.get_universe_domain().await.as_deref(),
+            Some("test-universe-domain")
+        );
+        let requests = state.sts_requests.lock().unwrap().clone();
+        assert_eq!(
+            requests[0].get("scope").map(String::as_str),
+            Some("scope1 scope2")
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn workforce_pool_user_project() -> TestResult {
+        let state = ServerState::default();
+        let (endpoint, _server) = start(state.clone()).await;
+        let mut config = test_config(&endpoint);
+        config["workforce_pool_user_project"] = "test-user-project".into();
+        let creds = creds_from(config, &CredentialOptions::default())?;
+        creds.get_token().await?;
+
+        let requests = state.sts_requests.lock().unwrap().clone();
+        assert_eq!(
+            requests[0].get("options").map(String::as_str),
+            Some(r#"{"userProject":"test-user-project"}"#)
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn url_source_error() -> TestResult {
+        let state = ServerState {
+            subject_token_status: Some(StatusCode::SERVICE_UNAVAILABLE),
+            ..Default::default()
+        };
+        let (endpoint, _server) = start(state.clone()).await;
+        let creds = creds_from(test_config(&endpoint), &CredentialOptions::default())?;
+        let e = creds.get_token().await.err().unwrap();
+        assert!(e.is_retryable(), "{e}");
+        assert!(
+            e.source().unwrap().to_string().contains("subject token"),
+            "{e}"
+        );
+        assert!(state.sts_requests.lock().unwrap().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn impersonation() -> TestResult {
+        let state = ServerState::default();
+        let (endpoint, _server) = start(state.clone()).await;
+        let mut config = test_config(&endpoint);
+        config["service_account_impersonation_url"] = format!(
+            "{endpoint}/v1/projects/-/serviceAccounts/sa@p.iam.gserviceaccount.com:generateAccessToken"
+        )
+        .into();
+        config["service_account_impersonation"] =
+            serde_json::json!({ "token_lifetime_seconds": 600 });
+        let creds = creds_from(config, &CredentialOptions::default())?;
+
+        let token = creds.get_token().await?;
+        assert_eq!(token.token, "impersonated-token");
+        let token = creds.get_token().await?;
+        assert_eq!(token.token, "impersonated-token");
+        assert_eq!(token.source_type(), Some("external_account"));
+        assert_eq!(
+            token.principal_email(),
+            Some("sa@p.iam.gserviceaccount.com")
+        );
+
+        assert_eq!(state.sts_requests.lock().unwrap().len(), 1);
+        let requests = state.iam_requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 1, "{requests:?}");
+        let (auth, body) = &requests[0];
+        assert_eq!(auth.as_deref(), Some("Bearer federated-token"));
+        assert_eq!(
+            body,
+            &serde_json::json!({ "scope": DEFAULT_SCOPES, "lifetime": "600s" })
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn impersonation_with_scopes() -> TestResult {
+        let state = ServerState::default();
+        let (endpoint, _server) = start(state.clone()).await;
+        let mut config = test_config(&endpoint);
+        config["service_account_impersonation_url"] = format!(
+            "{endpoint}/v1/projects/-/serviceAccounts/sa@p.iam.gserviceaccount.com:generateAccessToken"
+        )
+        .into();
+        let options = CredentialOptions {
+            scopes: Some(vec![
+                "https://www.googleapis.com/auth/devstorage.read_only".to_string()
+            ]),
+            ..Default::default()
+        };
+        let creds = creds_from(config, &options)?;
+        let token = creds.get_token().await?;
+        assert_eq!(token.token, "impersonated-token");
+
+        // The federated token must be able to call the IAM Credentials API.
+        let requests = state.sts_requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 1, "{requests:?}");
+        assert_eq!(
+            requests[0].get("scope").map(String::as_str),
+            Some(DEFAULT_SCOPES[0])
+        );
+        // The user scopes apply to the impersonated token.
+        let requests = state.iam_requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 1, "{requests:?}");
+        let (_, body) = &requests[0];
+        assert_eq!(
+            body["scope"],
+            serde_json::json!(["https://www.googleapis.com/auth/devstorage.read_only"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn creds_from_invalid() {
+        let json = serde_json::json!({
+            "type": "external_account",
+            "audience": AUDIENCE,
+            "subject_token_type": JWT_TOKEN_TYPE,
+            "credential_source": { "environment_id": "aws1" },
+        });
+        assert!(creds_from(json, &CredentialOptions::default()).is_err());
+
+        let json = serde_json::json!({
+            "type": "external_account",
+            "subject_token_type": JWT_TOKEN_TYPE,
+            "credential_source": { "file": "/var/run/token" },
+        });
+        assert!(creds_from(json, &CredentialOptions::default()).is_err());
+    }
+}
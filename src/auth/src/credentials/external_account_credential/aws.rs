@@ -0,0 +1,739 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Subject tokens for workloads running on AWS.
+//!
+//! The subject token is a serialized AWS `GetCallerIdentity` request, signed
+//! with [AWS Signature Version 4]. STS verifies the identity of the caller by
+//! sending this request to AWS.
+//!
+//! The AWS region and security credentials are read from the `AWS_*`
+//! environment variables if set. Otherwise they are read from the EC2
+//! instance metadata service, using the URLs in the credential configuration.
+//!
+//! [AWS Signature Version 4]: https://docs.aws.amazon.com/IAM/latest/UserGuide/reference_sigv.html
+
+use super::SourceContext;
+use crate::credentials::util::http_client;
+use crate::errors::{is_retryable, CredentialError};
+use crate::Result;
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::{Client, Method, RequestBuilder, Url};
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+
+// The only version of the AWS `environment_id` we support.
+const SUPPORTED_ENVIRONMENT: &str = "aws1";
+
+const SIGNING_ALGORITHM: &str = "AWS4-HMAC-SHA256";
+const STS_SERVICE: &str = "sts";
+
+const IMDSV2_TOKEN_HEADER: &str = "x-aws-ec2-metadata-token";
+const IMDSV2_TTL_HEADER: &str = "x-aws-ec2-metadata-token-ttl-seconds";
+const IMDSV2_TTL_SECONDS: &str = "300";
+
+const TARGET_RESOURCE_HEADER: &str = "x-goog-cloud-target-resource";
+
+// The characters escaped by SigV4 and in the subject token: everything except
+// the RFC 3986 unreserved characters.
+const ESCAPED: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// The AWS flavor of the `credential_source` field in the configuration file.
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct AwsConfig {
+    pub(super) environment_id: String,
+    pub(super) region_url: Option<String>,
+    pub(super) url: Option<String>,
+    pub(super) regional_cred_verification_url: Option<String>,
+    pub(super) imdsv2_session_token_url: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct AwsSource {
+    region_url: Option<String>,
+    credentials_url: Option<String>,
+    regional_cred_verification_url: String,
+    imdsv2_session_token_url: Option<String>,
+    audience: String,
+}
+
+/// AWS security credentials.
+#[derive(Clone, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AwsSecurityCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    #[serde(rename = "Token")]
+    session_token: Option<String>,
+}
+
+impl std::fmt::Debug for AwsSecurityCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AwsSecurityCredentials")
+            .field("access_key_id", &self.access_key_id)
+            .field("secret_access_key", &"[censored]")
+            .field(
+                "session_token",
+                &self.session_token.as_ref().map(|_| "[censored]"),
+            )
+            .finish()
+    }
+}
+
+impl AwsSource {
+    pub(super) fn new(config: AwsConfig, context: SourceContext) -> Result<Self> {
+        if config.environment_id != SUPPORTED_ENVIRONMENT {
+            return Err(CredentialError::non_retryable(format!(
+                "unsupported AWS environment_id `{}`, expected `{SUPPORTED_ENVIRONMENT}`",
+                config.environment_id
+            )));
+        }
+        let regional_cred_verification_url =
+            config.regional_cred_verification_url.ok_or_else(|| {
+                CredentialError::non_retryable(
+                    "the AWS `credential_source` requires a `regional_cred_verification_url`",
+                )
+            })?;
+        Ok(Self {
+            region_url: config.region_url,
+            credentials_url: config.url,
+            regional_cred_verification_url,
+            imdsv2_session_token_url: config.imdsv2_session_token_url,
+            audience: context.audience,
+        })
+    }
+
+    pub(super) async fn subject_token(&self) -> Result<String> {
+        let client = http_client::client();
+        let env_region = region_from_env();
+        let env_credentials = credentials_from_env();
+        // The session token is only needed if we need to query the metadata
+        // service.
+        let session_token = match (
+            &env_region,
+            &env_credentials,
+            &self.imdsv2_session_token_url,
+        ) {
+            (Some(_), Some(_), _) | (_, _, None) => None,
+            (_, _, Some(url)) => Some(imdsv2_session_token(&client, url).await?),
+        };
+        let region = match env_region {
+            Some(r) => r,
+            None => self.region(&client, session_token.as_deref()).await?,
+        };
+        let credentials = match env_credentials {
+            Some(c) => c,
+            None => self.credentials(&client, session_token.as_deref()).await?,
+        };
+        let url = self
+            .regional_cred_verification_url
+            .replace("{region}", &region);
+        let request = SignedRequest::new(
+            Method::POST,
+            &url,
+            &region,
+            STS_SERVICE,
+            &credentials,
+            vec![(TARGET_RESOURCE_HEADER.to_string(), self.audience.clone())],
+            OffsetDateTime::now_utc(),
+        )?;
+        Ok(request.subject_token())
+    }
+
+    async fn region(&self, client: &Client, session_token: Option<&str>) -> Result<String> {
+        let url = self.region_url.as_deref().ok_or_else(|| {
+            CredentialError::non_retryable(
+                "cannot determine the AWS region: the `AWS_REGION` environment variable is not set and the `credential_source` has no `region_url`",
+            )
+        })?;
+        let zone = metadata_get(client, url, session_token).await?;
+        // The metadata service returns the availability zone, e.g.
+        // `us-east-1b`. The region is the zone without the trailing letter.
+        let zone = zone.trim();
+        let mut chars = zone.chars();
+        chars.next_back();
+        let region = chars.as_str();
+        if region.is_empty() {
+            return Err(CredentialError::non_retryable(format!(
+                "invalid AWS availability zone `{zone}`"
+            )));
+        }
+        Ok(region.to_string())
+    }
+
+    async fn credentials(
+        &self,
+        client: &Client,
+        session_token: Option<&str>,
+    ) -> Result<AwsSecurityCredentials> {
+        let url = self.credentials_url.as_deref().ok_or_else(|| {
+            CredentialError::non_retryable(
+                "cannot determine the AWS security credentials: the `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` environment variables are not set and the `credential_source` has no `url`",
+            )
+        })?;
+        let role = metadata_get(client, url, session_token).await?;
+        let role = role.trim();
+        let url = format!("{}/{role}", url.trim_end_matches('/'));
+        let body = metadata_get(client, &url, session_token).await?;
+        serde_json::from_str::<AwsSecurityCredentials>(&body)
+            .map_err(CredentialError::non_retryable)
+    }
+}
+
+fn region_from_env() -> Option<String> {
+    std::env::var("AWS_REGION")
+        .or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+        .ok()
+        .filter(|r| !r.is_empty())
+}
+
+fn credentials_from_env() -> Option<AwsSecurityCredentials> {
+    let access_key_id = std::env::var("AWS_ACCESS_KEY_ID").ok()?;
+    let secret_access_key = std::env::var("AWS_SECRET_ACCESS_KEY").ok()?;
+    Some(AwsSecurityCredentials {
+        access_key_id,
+        secret_access_key,
+        session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+    })
+}
+
+async fn imdsv2_session_token(client: &Client, url: &str) -> Result<String> {
+    let builder = client
+        .put(url)
+        .header(IMDSV2_TTL_HEADER, IMDSV2_TTL_SECONDS);
+    send(builder, "Failed to fetch the IMDSv2 session token.").await
+}
+
+async fn metadata_get(client: &Client, url: &str, session_token: Option<&str>) -> Result<String> {
+    let mut builder = client.get(url);
+    if let Some(token) = session_token {
+        builder = builder.header(IMDSV2_TOKEN_HEADER, token);
+    }
+    send(builder, "Failed to fetch AWS metadata.").await
+}
+
+async fn send(builder: RequestBuilder, message: &str) -> Result<String> {
+    let resp = builder.send().await.map_err(CredentialError::retryable)?;
+    let status = resp.status();
+    let body = resp
+        .text()
+        .await
+        .map_err(|e| CredentialError::new(is_retryable(status), e.into()))?;
+    if !status.is_success() {
+        return Err(CredentialError::new(
+            is_retryable(status),
+            Box::from(format!("{message} {body}")),
+        ));
+    }
+    Ok(body)
+}
+
+/// A request signed with AWS Signature Version 4.
+#[derive(Clone, Debug, PartialEq)]
+struct SignedRequest {
+    url: String,
+    method: Method,
+    /// The request headers, including `Authorization`, sorted by name.
+    headers: Vec<(String, String)>,
+}
+
+impl SignedRequest {
+    fn new(
+        method: Method,
+        url: &str,
+        region: &str,
+        service: &str,
+        credentials: &AwsSecurityCredentials,
+        extra_headers: Vec<(String, String)>,
+        now: OffsetDateTime,
+    ) -> Result<Self> {
+        let parsed = Url::parse(url).map_err(CredentialError::non_retryable)?;
+        let host = match (parsed.host_str(), parsed.port()) {
+            (Some(h), Some(p)) => format!("{h}:{p}"),
+            (Some(h), None) => h.to_string(),
+            (None, _) => {
+                return Err(CredentialError::non_retryable(format!(
+                    "missing host in AWS request URL {url}"
+                )))
+            }
+        };
+        let amz_date = format!(
+            "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+            now.year(),
+            u8::from(now.month()),
+            now.day(),
+            now.hour(),
+            now.minute(),
+            now.second()
+        );
+        let date_stamp = &amz_date[..8];
+
+        let mut headers = vec![
+            ("host".to_string(), host),
+            ("x-amz-date".to_string(), amz_date.clone()),
+        ];
+        if let Some(token) = &credentials.session_token {
+            headers.push(("x-amz-security-token".to_string(), token.clone()));
+        }
+        headers.extend(
+            extra_headers
+                .into_iter()
+                .map(|(k, v)| (k.to_ascii_lowercase(), v)),
+        );
+        headers.sort();
+
+        let canonical_uri = match parsed.path() {
+            "" => "/",
+            p => p,
+        };
+        let mut query: Vec<(String, String)> = parsed
+            .query_pairs()
+            .map(|(k, v)| (escape(&k), escape(&v)))
+            .collect();
+        query.sort();
+        let canonical_query = query
+            .iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect::<Vec<_>>()
+            .join("&");
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(k, v)| format!("{k}:{}\n", v.trim()))
+            .collect();
+        let signed_headers = headers
+            .iter()
+            .map(|(k, _)| k.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+        // The `GetCallerIdentity` requests have no body.
+        let payload_hash = hex(&Sha256::digest(b""));
+        let canonical_request = format!(
+            "{method}\n{canonical_uri}\n{canonical_query}\n{canonical_headers}\n{signed_headers}\n{payload_hash}"
+        );
+
+        let scope = format!("{date_stamp}/{region}/{service}/aws4_request");
+        let string_to_sign = format!(
+            "{SIGNING_ALGORITHM}\n{amz_date}\n{scope}\n{}",
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let key = hmac_sha256(
+            format!("AWS4{}", credentials.secret_access_key).as_bytes(),
+            date_stamp.as_bytes(),
+        );
+        let key = hmac_sha256(&key, region.as_bytes());
+        let key = hmac_sha256(&key, service.as_bytes());
+        let key = hmac_sha256(&key, b"aws4_request");
+        let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "{SIGNING_ALGORITHM} Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+            credentials.access_key_id
+        );
+        headers.push(("Authorization".to_string(), authorization));
+        headers.sort();
+
+        Ok(Self {
+            url: url.to_string(),
+            method,
+            headers,
+        })
+    }
+
+    /// Serializes the request in the format expected by STS.
+    fn subject_token(&self) -> String {
+        let headers: Vec<_> = self
+            .headers
+            .iter()
+            .map(|(k, v)| serde_json::json!({ "key": k, "value": v }))
+            .collect();
+        let request = serde_json::json!({
+            "url": self.url,
+            "method": self.method.as_str(),
+            "headers": headers,
+        });
+        escape(&request.to_string())
+    }
+}
+
+fn escape(s: &str) -> String {
+    utf8_percent_encode(s, ESCAPED).to_string()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::extract::Path;
+    use http::{HeaderMap, StatusCode};
+    use scoped_env::ScopedEnv;
+    use std::sync::{Arc, Mutex};
+    use tokio::task::JoinHandle;
+
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    const VERIFICATION_URL: &str =
+        "https://sts.{region}.amazonaws.com?Action=GetCallerIdentity&Version=2011-06-15";
+
+    fn example_credentials(session_token: Option<&str>) -> AwsSecurityCredentials {
+        AwsSecurityCredentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: session_token.map(str::to_string),
+        }
+    }
+
+    fn example_time() -> OffsetDateTime {
+        // 2015-08-30T12:36:00Z, the timestamp used in the AWS test suite.
+        OffsetDateTime::from_unix_timestamp(1440938160).unwrap()
+    }
+
+    fn header<'a>(request: &'a SignedRequest, name: &str) -> Option<&'a str> {
+        request
+            .headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    // The `get-vanilla` case from the AWS Signature Version 4 test suite.
+    #[test]
+    fn sigv4_get_vanilla() -> TestResult {
+        let request = SignedRequest::new(
+            Method::GET,
+            "https://example.amazonaws.com/",
+            "us-east-1",
+            "service",
+            &example_credentials(None),
+            Vec::new(),
+            example_time(),
+        )?;
+        assert_eq!(
+            header(&request, "Authorization"),
+            Some("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=host;x-amz-date, Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31")
+        );
+        assert_eq!(header(&request, "x-amz-date"), Some("20150830T123600Z"));
+        assert_eq!(header(&request, "host"), Some("example.amazonaws.com"));
+        Ok(())
+    }
+
+    #[test]
+    fn sigv4_get_caller_identity() -> TestResult {
+        let request = SignedRequest::new(
+            Method::POST,
+            "https://sts.us-east-2.amazonaws.com?Action=GetCallerIdentity&Version=2011-06-15",
+            "us-east-2",
+            STS_SERVICE,
+            &example_credentials(Some("test-session-token")),
+            vec![(
+                "X-Goog-Cloud-Target-Resource".to_string(),
+                "test-audience".to_string(),
+            )],
+            example_time(),
+        )?;
+        let names: Vec<_> = request.headers.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "Authorization",
+                "host",
+                "x-amz-date",
+                "x-amz-security-token",
+                "x-goog-cloud-target-resource"
+            ]
+        );
+        let authorization = header(&request, "Authorization").unwrap();
+        assert!(
+            authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-2/sts/aws4_request, SignedHeaders=host;x-amz-date;x-amz-security-token;x-goog-cloud-target-resource, Signature="),
+            "{authorization}"
+        );
+        assert_eq!(
+            header(&request, "x-amz-security-token"),
+            Some("test-session-token")
+        );
+
+        let token = request.subject_token();
+        let decoded = percent_encoding::percent_decode_str(&token).decode_utf8()?;
+        let json = serde_json::from_str::<serde_json::Value>(&decoded)?;
+        assert_eq!(json["url"], request.url);
+        assert_eq!(json["method"], "POST");
+        assert_eq!(json["headers"][0]["key"], "Authorization");
+        assert_eq!(json["headers"][0]["value"], authorization);
+        assert_eq!(json["headers"][4]["value"], "test-audience");
+        Ok(())
+    }
+
+    #[test]
+    fn new_source_validation() {
+        let config = AwsConfig {
+            environment_id: "aws2".to_string(),
+            region_url: None,
+            url: None,
+            regional_cred_verification_url: Some(VERIFICATION_URL.to_string()),
+            imdsv2_session_token_url: None,
+        };
+        let e = AwsSource::new(config.clone(), SourceContext::default())
+            .err()
+            .unwrap();
+        assert!(e.to_string().contains("aws2"), "{e}");
+
+        let config = AwsConfig {
+            environment_id: "aws1".to_string(),
+            regional_cred_verification_url: None,
+            ..config
+        };
+        let e = AwsSource::new(config, SourceContext::default())
+            .err()
+            .unwrap();
+        assert!(
+            e.to_string().contains("regional_cred_verification_url"),
+            "{e}"
+        );
+    }
+
+    #[test]
+    fn debug_credentials() {
+        let fmt = format!("{:?}", example_credentials(Some("test-session-token")));
+        assert!(fmt.contains("AKIDEXAMPLE"), "{fmt}");
+        assert!(!fmt.contains("wJalrXUtnFEMI"), "{fmt}");
+        assert!(!fmt.contains("test-session-token"), "{fmt}");
+    }
+
+    #[derive(Clone, Debug, Default)]
+    struct MetadataState {
+        // The IMDSv2 session token header in each metadata request.
+        session_tokens: Arc<Mutex<Vec<Option<String>>>>,
+    }
+
+    // Starts a fake EC2 metadata service. Returns an (endpoint, server) pair.
+    async fn start(state: MetadataState) -> (String, JoinHandle<()>) {
+        let record = |state: &MetadataState, headers: &HeaderMap| {
+            state.session_tokens.lock().unwrap().push(
+                headers
+                    .get(IMDSV2_TOKEN_HEADER)
+                    .map(|v| v.to_str().unwrap().to_string()),
+            );
+        };
+        let token = |headers: HeaderMap| async move {
+            assert_eq!(
+                headers.get(IMDSV2_TTL_HEADER).map(|v| v.to_str().unwrap()),
+                Some(IMDSV2_TTL_SECONDS)
+            );
+            "test-imds-token"
+        };
+        let s1 = state.clone();
+        let zone = move |headers: HeaderMap| async move {
+            record(&s1, &headers);
+            "us-east-2b"
+        };
+        let s2 = state.clone();
+        let role = move |headers: HeaderMap| async move {
+            record(&s2, &headers);
+            "test-role"
+        };
+        let s3 = state.clone();
+        let creds = move |headers: HeaderMap, Path(role): Path<String>| async move {
+            record(&s3, &headers);
+            if role != "test-role" {
+                return (StatusCode::NOT_FOUND, String::new());
+            }
+            let body = serde_json::json!({
+                "Code": "Success",
+                "AccessKeyId": "test-access-key-id",
+                "SecretAccessKey": "test-secret-access-key",
+                "Token": "test-security-token",
+            });
+            (StatusCode::OK, body.to_string())
+        };
+        let app = axum::Router::new()
+            .route("/latest/api/token", axum::routing::put(token))
+            .route(
+                "/latest/meta-data/placement/availability-zone",
+                axum::routing::get(zone),
+            )
+            .route(
+                "/latest/meta-data/iam/security-credentials",
+                axum::routing::get(role),
+            )
+            .route(
+                "/latest/meta-data/iam/security-credentials/{role}",
+                axum::routing::get(creds),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        (format!("http://{}:{}", addr.ip(), addr.port()), server)
+    }
+
+    fn test_source(endpoint: &str, imdsv2: bool) -> Result<AwsSource> {
+        let config = AwsConfig {
+            environment_id: "aws1".to_string(),
+            region_url: Some(format!(
+                "{endpoint}/latest/meta-data/placement/availability-zone"
+            )),
+            url: Some(format!(
+                "{endpoint}/latest/meta-data/iam/security-credentials"
+            )),
+            regional_cred_verification_url: Some(VERIFICATION_URL.to_string()),
+            imdsv2_session_token_url: imdsv2.then(|| format!("{endpoint}/latest/api/token")),
+        };
+        let context = SourceContext {
+            audience: "test-audience".to_string(),
+            ..Default::default()
+        };
+        AwsSource::new(config, context)
+    }
+
+    fn decode(token: &str) -> serde_json::Value {
+        let decoded = percent_encoding::percent_decode_str(token)
+            .decode_utf8()
+            .unwrap();
+        serde_json::from_str(&decoded).unwrap()
+    }
+
+    fn header_value(json: &serde_json::Value, key: &str) -> Option<String> {
+        json["headers"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|h| h["key"] == key)
+            .map(|h| h["value"].as_str().unwrap().to_string())
+    }
+
+    fn remove_aws_env() -> Vec<ScopedEnv<&'static str>> {
+        [
+            "AWS_REGION",
+            "AWS_DEFAULT_REGION",
+            "AWS_ACCESS_KEY_ID",
+            "AWS_SECRET_ACCESS_KEY",
+            "AWS_SESSION_TOKEN",
+        ]
+        .into_iter()
+        .map(ScopedEnv::remove)
+        .collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[serial_test::serial]
+    async fn subject_token_from_metadata() -> TestResult {
+        let _env = remove_aws_env();
+        let state = MetadataState::default();
+        let (endpoint, _server) = start(state.clone()).await;
+        let source = test_source(&endpoint, true)?;
+
+        let json = decode(&source.subject_token().await?);
+        assert_eq!(
+            json["url"],
+            "https://sts.us-east-2.amazonaws.com?Action=GetCallerIdentity&Version=2011-06-15"
+        );
+        assert_eq!(json["method"], "POST");
+        assert_eq!(
+            header_value(&json, "host").as_deref(),
+            Some("sts.us-east-2.amazonaws.com")
+        );
+        assert_eq!(
+            header_value(&json, "x-amz-security-token").as_deref(),
+            Some("test-security-token")
+        );
+        assert_eq!(
+            header_value(&json, "x-goog-cloud-target-resource").as_deref(),
+            Some("test-audience")
+        );
+        let authorization = header_value(&json, "Authorization").unwrap();
+        assert!(
+            authorization.contains("Credential=test-access-key-id/"),
+            "{authorization}"
+        );
+        assert!(authorization.contains("/us-east-2/sts/"), "{authorization}");
+
+        let tokens = state.session_tokens.lock().unwrap().clone();
+        assert_eq!(tokens, vec![Some("test-imds-token".to_string()); 3]);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[serial_test::serial]
+    async fn subject_token_from_metadata_without_imdsv2() -> TestResult {
+        let _env = remove_aws_env();
+        let state = MetadataState::default();
+        let (endpoint, _server) = start(state.clone()).await;
+        let source = test_source(&endpoint, false)?;
+        let json = decode(&source.subject_token().await?);
+        assert_eq!(
+            header_value(&json, "x-amz-security-token").as_deref(),
+            Some("test-security-token")
+        );
+        let tokens = state.session_tokens.lock().unwrap().clone();
+        assert_eq!(tokens, vec![None; 3]);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[serial_test::serial]
+    async fn subject_token_from_env() -> TestResult {
+        let _env = remove_aws_env();
+        let _e1 = ScopedEnv::set("AWS_REGION", "eu-west-1");
+        let _e2 = ScopedEnv::set("AWS_ACCESS_KEY_ID", "env-access-key-id");
+        let _e3 = ScopedEnv::set("AWS_SECRET_ACCESS_KEY", "env-secret-access-key");
+        let state = MetadataState::default();
+        let (endpoint, _server) = start(state.clone()).await;
+        let source = test_source(&endpoint, true)?;
+
+        let json = decode(&source.subject_token().await?);
+        assert_eq!(
+            header_value(&json, "host").as_deref(),
+            Some("sts.eu-west-1.amazonaws.com")
+        );
+        assert_eq!(header_value(&json, "x-amz-security-token"), None);
+        let authorization = header_value(&json, "Authorization").unwrap();
+        assert!(
+            authorization.contains("Credential=env-access-key-id/"),
+            "{authorization}"
+        );
+        // The metadata service is not used.
+        assert!(state.session_tokens.lock().unwrap().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[serial_test::serial]
+    async fn metadata_errors() -> TestResult {
+        let _env = remove_aws_env();
+        let (endpoint, _server) = start(MetadataState::default()).await;
+        let mut source = test_source(&endpoint, false)?;
+        source.credentials_url = Some(format!("{endpoint}/missing"));
+        let e = source.subject_token().await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+
+        source.region_url = None;
+        let e = source.subject_token().await.err().unwrap();
+        assert!(e.to_string().contains("AWS_REGION"), "{e}");
+        Ok(())
+    }
+}
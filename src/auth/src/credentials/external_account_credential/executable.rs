@@ -0,0 +1,496 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Subject tokens returned by a local executable.
+//!
+//! The executable must print a JSON response to stdout, using the format
+//! described in the [executable-sourced credentials] documentation. Running
+//! executables must be explicitly allowed by setting the
+//! `GOOGLE_EXTERNAL_ACCOUNT_ALLOW_EXECUTABLES` environment variable to `1`.
+//!
+//! [executable-sourced credentials]: https://cloud.google.com/iam/docs/workload-identity-federation-with-other-providers#use_executable-sourced_credentials_with_oidc_and_saml
+
+use super::SourceContext;
+use crate::errors::CredentialError;
+use crate::Result;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+const ALLOW_EXECUTABLES_ENV: &str = "GOOGLE_EXTERNAL_ACCOUNT_ALLOW_EXECUTABLES";
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const MIN_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_TIMEOUT: Duration = Duration::from_secs(120);
+
+// The newest version of the executable response format we understand.
+const SUPPORTED_VERSION: u32 = 1;
+
+const SAML_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:saml2";
+
+/// The `credential_source.executable` field in the configuration file.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+pub(super) struct ExecutableConfig {
+    command: String,
+    timeout_millis: Option<u64>,
+    output_file: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct ExecutableSource {
+    program: String,
+    args: Vec<String>,
+    timeout: Duration,
+    output_file: Option<String>,
+    context: SourceContext,
+}
+
+impl ExecutableSource {
+    pub(super) fn new(config: ExecutableConfig, context: SourceContext) -> Result<Self> {
+        let mut words = config.command.split_whitespace().map(str::to_string);
+        let program = words.next().ok_or_else(|| {
+            CredentialError::non_retryable("the executable `command` must not be empty")
+        })?;
+        let timeout = config
+            .timeout_millis
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_TIMEOUT);
+        if !(MIN_TIMEOUT..=MAX_TIMEOUT).contains(&timeout) {
+            return Err(CredentialError::non_retryable(format!(
+                "the executable `timeout_millis` must be between {} and {}",
+                MIN_TIMEOUT.as_millis(),
+                MAX_TIMEOUT.as_millis()
+            )));
+        }
+        Ok(Self {
+            program,
+            args: words.collect(),
+            timeout,
+            output_file: config.output_file,
+            context,
+        })
+    }
+
+    pub(super) async fn subject_token(&self) -> Result<String> {
+        if std::env::var(ALLOW_EXECUTABLES_ENV).as_deref() != Ok("1") {
+            return Err(CredentialError::non_retryable(format!(
+                "executables need to be explicitly allowed (set {ALLOW_EXECUTABLES_ENV} to '1') to run"
+            )));
+        }
+        if let Some(token) = self.cached_subject_token().await? {
+            return Ok(token);
+        }
+        let stdout = self.run().await?;
+        let response = self.parse(&stdout)?;
+        let token = response.subject_token(OffsetDateTime::now_utc())?;
+        if let Some(path) = &self.output_file {
+            tokio::fs::write(path, &stdout).await.map_err(|e| {
+                CredentialError::non_retryable(format!(
+                    "cannot write the executable output file {path}: {e}"
+                ))
+            })?;
+        }
+        Ok(token)
+    }
+
+    // Returns the subject token in the output file, if it is present and has
+    // not expired.
+    async fn cached_subject_token(&self) -> Result<Option<String>> {
+        let Some(path) = &self.output_file else {
+            return Ok(None);
+        };
+        let Ok(contents) = tokio::fs::read_to_string(path).await else {
+            // A missing or unreadable file simply means there is no cached
+            // response.
+            return Ok(None);
+        };
+        if contents.trim().is_empty() {
+            return Ok(None);
+        }
+        let response = self.parse(&contents)?;
+        match response.subject_token(OffsetDateTime::now_utc()) {
+            Ok(token) => Ok(Some(token)),
+            // Expired (or failed) responses are replaced by running the
+            // executable again.
+            Err(_) => Ok(None),
+        }
+    }
+
+    async fn run(&self) -> Result<String> {
+        let mut command = tokio::process::Command::new(&self.program);
+        command
+            .args(&self.args)
+            .env("GOOGLE_EXTERNAL_ACCOUNT_AUDIENCE", &self.context.audience)
+            .env(
+                "GOOGLE_EXTERNAL_ACCOUNT_TOKEN_TYPE",
+                &self.context.subject_token_type,
+            )
+            // Interactive mode is not supported.
+            .env("GOOGLE_EXTERNAL_ACCOUNT_INTERACTIVE", "0")
+            .stdin(std::process::Stdio::null())
+            .kill_on_drop(true);
+        if let Some(email) = &self.context.impersonated_email {
+            command.env("GOOGLE_EXTERNAL_ACCOUNT_IMPERSONATED_EMAIL", email);
+        }
+        if let Some(path) = &self.output_file {
+            command.env("GOOGLE_EXTERNAL_ACCOUNT_OUTPUT_FILE", path);
+        }
+        let output = tokio::time::timeout(self.timeout, command.output())
+            .await
+            .map_err(|_| {
+                CredentialError::retryable(format!(
+                    "the executable {} timed out after {}ms",
+                    self.program,
+                    self.timeout.as_millis()
+                ))
+            })?
+            .map_err(|e| {
+                CredentialError::non_retryable(format!(
+                    "cannot run the executable {}: {e}",
+                    self.program
+                ))
+            })?;
+        if !output.status.success() {
+            return Err(CredentialError::non_retryable(format!(
+                "the executable {} failed with {}",
+                self.program, output.status
+            )));
+        }
+        String::from_utf8(output.stdout).map_err(CredentialError::non_retryable)
+    }
+
+    fn parse(&self, contents: &str) -> Result<ExecutableResponse> {
+        let response = serde_json::from_str::<ExecutableResponse>(contents).map_err(|e| {
+            CredentialError::non_retryable(format!("malformed executable response: {e}"))
+        })?;
+        if response.version > SUPPORTED_VERSION {
+            return Err(CredentialError::non_retryable(format!(
+                "unsupported executable response version {}, expected {SUPPORTED_VERSION} or lower",
+                response.version
+            )));
+        }
+        if response.success && response.expiration_time.is_none() && self.output_file.is_some() {
+            return Err(CredentialError::non_retryable(
+                "the executable response must include the `expiration_time` when an `output_file` is configured",
+            ));
+        }
+        Ok(response)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+struct ExecutableResponse {
+    version: u32,
+    success: bool,
+    token_type: Option<String>,
+    id_token: Option<String>,
+    saml_response: Option<String>,
+    /// The expiration time, in seconds since the Unix epoch.
+    expiration_time: Option<i64>,
+    code: Option<String>,
+    message: Option<String>,
+}
+
+impl ExecutableResponse {
+    fn subject_token(self, now: OffsetDateTime) -> Result<String> {
+        if !self.success {
+            return Err(CredentialError::non_retryable(format!(
+                "the executable reported an error: code={}, message={}",
+                self.code.unwrap_or_default(),
+                self.message.unwrap_or_default()
+            )));
+        }
+        if self
+            .expiration_time
+            .is_some_and(|t| t <= now.unix_timestamp())
+        {
+            return Err(CredentialError::non_retryable(
+                "the executable response has expired",
+            ));
+        }
+        let token = match self.token_type.as_deref() {
+            Some(SAML_TOKEN_TYPE) => self.saml_response,
+            Some(_) => self.id_token,
+            None => None,
+        };
+        token.filter(|t| !t.is_empty()).ok_or_else(|| {
+            CredentialError::non_retryable("the executable response is missing the subject token")
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use scoped_env::ScopedEnv;
+
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    const JWT_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:jwt";
+
+    fn config(command: &str) -> ExecutableConfig {
+        ExecutableConfig {
+            command: command.to_string(),
+            timeout_millis: None,
+            output_file: None,
+        }
+    }
+
+    fn context() -> SourceContext {
+        SourceContext {
+            audience: "test-audience".to_string(),
+            subject_token_type: JWT_TOKEN_TYPE.to_string(),
+            impersonated_email: Some("sa@p.iam.gserviceaccount.com".to_string()),
+        }
+    }
+
+    fn success_response(expiration_time: Option<i64>) -> ExecutableResponse {
+        ExecutableResponse {
+            version: 1,
+            success: true,
+            token_type: Some(JWT_TOKEN_TYPE.to_string()),
+            id_token: Some("test-id-token".to_string()),
+            saml_response: None,
+            expiration_time,
+            code: None,
+            message: None,
+        }
+    }
+
+    #[test]
+    fn new_source() -> TestResult {
+        let source = ExecutableSource::new(config("/bin/broker --flag  value"), context())?;
+        assert_eq!(source.program, "/bin/broker");
+        assert_eq!(source.args, vec!["--flag", "value"]);
+        assert_eq!(source.timeout, DEFAULT_TIMEOUT);
+
+        assert!(ExecutableSource::new(config("  "), context()).is_err());
+        for timeout_millis in [4999, 120001] {
+            let config = ExecutableConfig {
+                timeout_millis: Some(timeout_millis),
+                ..config("/bin/broker")
+            };
+            let e = ExecutableSource::new(config, context()).err().unwrap();
+            assert!(e.to_string().contains("timeout_millis"), "{e}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn response_subject_token() -> TestResult {
+        let now = OffsetDateTime::now_utc();
+        let future = now.unix_timestamp() + 3600;
+        assert_eq!(
+            success_response(Some(future)).subject_token(now)?,
+            "test-id-token"
+        );
+        assert_eq!(success_response(None).subject_token(now)?, "test-id-token");
+
+        let expired = success_response(Some(now.unix_timestamp() - 1));
+        let e = expired.subject_token(now).err().unwrap();
+        assert!(e.to_string().contains("expired"), "{e}");
+
+        let saml = ExecutableResponse {
+            token_type: Some(SAML_TOKEN_TYPE.to_string()),
+            id_token: None,
+            saml_response: Some("test-saml".to_string()),
+            ..success_response(None)
+        };
+        assert_eq!(saml.subject_token(now)?, "test-saml");
+
+        let failure = ExecutableResponse {
+            version: 1,
+            success: false,
+            token_type: None,
+            id_token: None,
+            saml_response: None,
+            expiration_time: None,
+            code: Some("401".to_string()),
+            message: Some("Caller not authorized.".to_string()),
+        };
+        let e = failure.subject_token(now).err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("Caller not authorized."), "{e}");
+        Ok(())
+    }
+
+    #[test]
+    fn parse_response() -> TestResult {
+        let source = ExecutableSource::new(config("/bin/broker"), context())?;
+        let response = source.parse(
+            r#"{"version": 1, "success": true, "token_type": "urn:ietf:params:oauth:token-type:jwt", "id_token": "test-id-token"}"#,
+        )?;
+        assert_eq!(response, success_response(None));
+
+        let e = source
+            .parse(r#"{"version": 2, "success": true}"#)
+            .err()
+            .unwrap();
+        assert!(e.to_string().contains("version"), "{e}");
+        assert!(source.parse("not json").is_err());
+
+        let source = ExecutableSource::new(
+            ExecutableConfig {
+                output_file: Some("/tmp/output.json".to_string()),
+                ..config("/bin/broker")
+            },
+            context(),
+        )?;
+        let e = source
+            .parse(r#"{"version": 1, "success": true, "token_type": "urn:ietf:params:oauth:token-type:jwt", "id_token": "test-id-token"}"#)
+            .err()
+            .unwrap();
+        assert!(e.to_string().contains("expiration_time"), "{e}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn executables_not_allowed() -> TestResult {
+        let _e = ScopedEnv::remove(ALLOW_EXECUTABLES_ENV);
+        let source = ExecutableSource::new(config("/bin/true"), context())?;
+        let e = source.subject_token().await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(e.to_string().contains(ALLOW_EXECUTABLES_ENV), "{e}");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    mod unix {
+        use super::*;
+        use std::os::unix::fs::PermissionsExt;
+        use std::path::{Path, PathBuf};
+
+        // Writes a fake executable into `dir` and returns its path.
+        fn fake_script(dir: &Path, body: &str) -> PathBuf {
+            let path = dir.join("fake-broker.sh");
+            std::fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            path
+        }
+
+        #[tokio::test]
+        #[serial_test::serial]
+        async fn run_executable() -> TestResult {
+            let _e = ScopedEnv::set(ALLOW_EXECUTABLES_ENV, "1");
+            let dir = tempfile::tempdir()?;
+            let env_file = dir.path().join("env.txt");
+            // The fake script records its arguments and environment, and
+            // returns a token that expires in one hour.
+            let script = fake_script(
+                dir.path(),
+                &format!(
+                    r#"echo "$1 $GOOGLE_EXTERNAL_ACCOUNT_AUDIENCE $GOOGLE_EXTERNAL_ACCOUNT_TOKEN_TYPE $GOOGLE_EXTERNAL_ACCOUNT_INTERACTIVE $GOOGLE_EXTERNAL_ACCOUNT_IMPERSONATED_EMAIL" >> {}
+EXP=$(( $(date +%s) + 3600 ))
+echo "{{\"version\": 1, \"success\": true, \"token_type\": \"{JWT_TOKEN_TYPE}\", \"id_token\": \"script-token\", \"expiration_time\": $EXP}}""#,
+                    env_file.display()
+                ),
+            );
+            let source = ExecutableSource::new(
+                config(&format!("{} test-arg", script.display())),
+                context(),
+            )?;
+            assert_eq!(source.subject_token().await?, "script-token");
+            let env = std::fs::read_to_string(&env_file)?;
+            assert_eq!(
+                env.trim(),
+                format!("test-arg test-audience {JWT_TOKEN_TYPE} 0 sa@p.iam.gserviceaccount.com")
+            );
+            Ok(())
+        }
+
+        #[tokio::test]
+        #[serial_test::serial]
+        async fn output_file_cache() -> TestResult {
+            let _e = ScopedEnv::set(ALLOW_EXECUTABLES_ENV, "1");
+            let dir = tempfile::tempdir()?;
+            let counter = dir.path().join("counter.txt");
+            let output_file = dir.path().join("output.json");
+            let script = fake_script(
+                dir.path(),
+                &format!(
+                    r#"echo run >> {}
+test "$GOOGLE_EXTERNAL_ACCOUNT_OUTPUT_FILE" = "{}" || exit 1
+EXP=$(( $(date +%s) + 3600 ))
+echo "{{\"version\": 1, \"success\": true, \"token_type\": \"{JWT_TOKEN_TYPE}\", \"id_token\": \"script-token\", \"expiration_time\": $EXP}}""#,
+                    counter.display(),
+                    output_file.display()
+                ),
+            );
+            let source = ExecutableSource::new(
+                ExecutableConfig {
+                    output_file: Some(output_file.to_str().unwrap().to_string()),
+                    ..config(script.to_str().unwrap())
+                },
+                context(),
+            )?;
+            assert_eq!(source.subject_token().await?, "script-token");
+            assert_eq!(source.subject_token().await?, "script-token");
+            // The second call uses the cached response.
+            assert_eq!(std::fs::read_to_string(&counter)?.lines().count(), 1);
+
+            // Expired responses in the output file are ignored.
+            let expired = format!(
+                r#"{{"version": 1, "success": true, "token_type": "{JWT_TOKEN_TYPE}", "id_token": "old-token", "expiration_time": 1}}"#
+            );
+            std::fs::write(&output_file, expired)?;
+            assert_eq!(source.subject_token().await?, "script-token");
+            assert_eq!(std::fs::read_to_string(&counter)?.lines().count(), 2);
+            Ok(())
+        }
+
+        #[tokio::test]
+        #[serial_test::serial]
+        async fn executable_errors() -> TestResult {
+            let _e = ScopedEnv::set(ALLOW_EXECUTABLES_ENV, "1");
+            let dir = tempfile::tempdir()?;
+
+            let script = fake_script(dir.path(), "exit 3");
+            let source = ExecutableSource::new(config(script.to_str().unwrap()), context())?;
+            let e = source.subject_token().await.err().unwrap();
+            assert!(!e.is_retryable(), "{e}");
+
+            let script = fake_script(
+                dir.path(),
+                r#"echo '{"version": 1, "success": false, "code": "401", "message": "Permission denied."}'"#,
+            );
+            let source = ExecutableSource::new(config(script.to_str().unwrap()), context())?;
+            let e = source.subject_token().await.err().unwrap();
+            assert!(e.to_string().contains("Permission denied."), "{e}");
+
+            let source = ExecutableSource::new(
+                config(dir.path().join("does-not-exist").to_str().unwrap()),
+                context(),
+            )?;
+            let e = source.subject_token().await.err().unwrap();
+            assert!(!e.is_retryable(), "{e}");
+            Ok(())
+        }
+
+        #[tokio::test]
+        #[serial_test::serial]
+        async fn executable_timeout() -> TestResult {
+            let _e = ScopedEnv::set(ALLOW_EXECUTABLES_ENV, "1");
+            let dir = tempfile::tempdir()?;
+            let script = fake_script(dir.path(), "sleep 60");
+            let mut source = ExecutableSource::new(config(script.to_str().unwrap()), context())?;
+            // Use a shorter timeout than allowed in the configuration to keep
+            // the test fast.
+            source.timeout = Duration::from_millis(100);
+            let e = source.subject_token().await.err().unwrap();
+            assert!(e.is_retryable(), "{e}");
+            assert!(e.to_string().contains("timed out"), "{e}");
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,371 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [ID token] credentials.
+//!
+//! Some services, such as [Cloud Run], [Cloud Functions], and applications
+//! behind [Identity-Aware Proxy], authenticate requests using OpenID Connect
+//! ID tokens instead of access tokens. An ID token asserts the identity of the
+//! caller to a specific *audience*, typically the URL of the service.
+//!
+//! ID tokens can be created by:
+//! - The [Metadata Service], for workloads running on Google Cloud.
+//! - A [service account key].
+//! - [Impersonating] a service account with the IAM Credentials API.
+//!
+//! [ID token]: https://cloud.google.com/docs/authentication/token-types#identity-tokens
+//! [Cloud Run]: https://cloud.google.com/run/docs/authenticating/service-to-service
+//! [Cloud Functions]: https://cloud.google.com/functions/docs/securing/authenticating
+//! [Identity-Aware Proxy]: https://cloud.google.com/iap/docs/authentication-howto
+//! [Metadata Service]: https://cloud.google.com/compute/docs/instances/verifying-instance-identity
+//! [service account key]: https://cloud.google.com/iam/docs/keys-create-delete
+//! [Impersonating]: https://cloud.google.com/iam/docs/create-short-lived-credentials-direct#sa-credentials-oidc
+
+use crate::credentials::util::{build_headers, IAM_CREDENTIALS_ENDPOINT};
+use crate::credentials::{
+    impersonated_credential, load_adc, mds_credential, service_account_credential, AdcContents,
+    Credential, Result,
+};
+use crate::errors::CredentialError;
+use crate::retry::RetryTokenProvider;
+use crate::token::{Token, TokenProvider};
+use crate::token_cache::TokenCache;
+use http::header::{HeaderName, HeaderValue};
+use std::sync::Arc;
+use time::OffsetDateTime;
+
+/// Credentials returning OpenID Connect ID tokens.
+///
+/// The tokens are cached, and refreshed shortly before they expire. The
+/// [Token::expires_at] field contains the expiration time decoded from the ID
+/// token.
+#[derive(Clone, Debug)]
+pub struct IdTokenCredential {
+    inner: Arc<dyn TokenProvider>,
+}
+
+impl IdTokenCredential {
+    fn new<T: TokenProvider + 'static>(provider: T) -> Self {
+        Self {
+            inner: Arc::new(TokenCache::new(RetryTokenProvider::with_defaults(provider))),
+        }
+    }
+
+    /// Returns an ID token, refreshing it if needed.
+    pub async fn get_token(&self) -> Result<Token> {
+        self.inner.get_token().await
+    }
+
+    /// Returns the headers to authenticate a request with the ID token.
+    pub async fn get_headers(&self) -> Result<Vec<(HeaderName, HeaderValue)>> {
+        let token = self.get_token().await?;
+        build_headers(&token, None)
+    }
+}
+
+/// Create ID token credentials for `target_audience`.
+///
+/// Uses the [Application Default Credentials] to create the ID tokens:
+/// - Service account keys sign the ID token requests.
+/// - Impersonated service account credentials call the IAM Credentials
+///   `generateIdToken` API.
+/// - Without an ADC file, the ID tokens are fetched from the Metadata Service.
+///
+/// User credentials cannot create ID tokens for arbitrary audiences, and
+/// return an error.
+///
+/// # Example
+/// ```
+/// # use gcp_sdk_auth::credentials::id_token_credential::create_id_token_credential;
+/// # use gcp_sdk_auth::errors::CredentialError;
+/// # tokio_test::block_on(async {
+/// let credential = create_id_token_credential("https://my-service.a.run.app").await?;
+/// # Ok::<(), CredentialError>(())
+/// # });
+/// ```
+///
+/// [Application Default Credentials]: https://cloud.google.com/docs/authentication/application-default-credentials
+pub async fn create_id_token_credential<T: Into<String>>(
+    target_audience: T,
+) -> Result<IdTokenCredential> {
+    Builder::new(target_audience).build().await
+}
+
+/// A builder for [IdTokenCredential]s.
+///
+/// Use one of the `build*()` functions to select how the ID tokens are
+/// created.
+#[derive(Clone, Debug)]
+pub struct Builder {
+    target_audience: String,
+    include_email: bool,
+    delegates: Vec<String>,
+    iam_endpoint: String,
+}
+
+impl Builder {
+    /// Creates a builder for ID tokens with the given audience.
+    ///
+    /// The audience is typically the URL of the receiving service.
+    pub fn new<T: Into<String>>(target_audience: T) -> Self {
+        Self {
+            target_audience: target_audience.into(),
+            include_email: false,
+            delegates: Vec::new(),
+            iam_endpoint: IAM_CREDENTIALS_ENDPOINT.to_string(),
+        }
+    }
+
+    /// Includes the `email` and `email_verified` claims in the ID token.
+    ///
+    /// Service account keys always include these claims.
+    pub fn with_include_email(mut self, v: bool) -> Self {
+        self.include_email = v;
+        self
+    }
+
+    /// Sets the delegation chain used with impersonated credentials.
+    ///
+    /// See [impersonated_credential::Builder::with_delegates].
+    pub fn with_delegates<I, S>(mut self, v: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.delegates = v.into_iter().map(|s| s.into()).collect();
+        self
+    }
+
+    /// Overrides the IAM Credentials API endpoint used with impersonated
+    /// credentials.
+    ///
+    /// The default is `https://iamcredentials.googleapis.com`.
+    pub fn with_iam_endpoint<T: Into<String>>(mut self, v: T) -> Self {
+        self.iam_endpoint = v.into();
+        self
+    }
+
+    /// Creates ID tokens using the [Application Default Credentials].
+    ///
+    /// See [create_id_token_credential] for details.
+    ///
+    /// [Application Default Credentials]: https://cloud.google.com/docs/authentication/application-default-credentials
+    pub async fn build(self) -> Result<IdTokenCredential> {
+        let contents = match load_adc().await? {
+            AdcContents::Contents(contents) => contents,
+            AdcContents::FallbackToMds => return Ok(self.build_metadata()),
+        };
+        let js: serde_json::Value =
+            serde_json::from_str(&contents).map_err(CredentialError::non_retryable)?;
+        let cred_type = js.get("type").and_then(|t| t.as_str()).ok_or_else(|| {
+            CredentialError::non_retryable(
+                "Failed to parse Application Default Credentials (ADC). No `type` field found.",
+            )
+        })?;
+        match cred_type {
+            "service_account" => self.build_service_account(js),
+            "impersonated_service_account" => {
+                let provider = impersonated_credential::id_token_provider_from(
+                    js,
+                    self.target_audience,
+                    self.include_email,
+                )?;
+                Ok(IdTokenCredential::new(provider))
+            }
+            _ => Err(CredentialError::non_retryable(format!(
+                "ID tokens are not supported for {cred_type} credentials"
+            ))),
+        }
+    }
+
+    /// Creates ID tokens using the [Metadata Service].
+    ///
+    /// [Metadata Service]: https://cloud.google.com/compute/docs/instances/verifying-instance-identity
+    pub fn build_metadata(self) -> IdTokenCredential {
+        IdTokenCredential::new(mds_credential::id_token_provider(
+            self.target_audience,
+            self.include_email,
+        ))
+    }
+
+    /// Creates ID tokens using a [service account key].
+    ///
+    /// [service account key]: https://cloud.google.com/iam/docs/keys-create-delete
+    pub fn build_service_account(
+        self,
+        service_account_key: serde_json::Value,
+    ) -> Result<IdTokenCredential> {
+        let provider = service_account_credential::id_token_provider(
+            service_account_key,
+            self.target_audience,
+        )?;
+        Ok(IdTokenCredential::new(provider))
+    }
+
+    /// Creates ID tokens by impersonating `target_principal` with the `source`
+    /// credentials.
+    ///
+    /// The source credentials need the `Service Account OpenID Connect Identity
+    /// Token Creator` role on the target principal.
+    pub fn build_impersonated<T: Into<String>>(
+        self,
+        source: Credential,
+        target_principal: T,
+    ) -> IdTokenCredential {
+        IdTokenCredential::new(impersonated_credential::id_token_provider(
+            source,
+            &target_principal.into(),
+            self.delegates,
+            &self.iam_endpoint,
+            self.target_audience,
+            self.include_email,
+        ))
+    }
+}
+
+/// Creates a [Token] from an ID token, decoding its expiration time.
+pub(crate) fn token_from_jwt(id_token: String) -> Result<Token> {
+    let claims = decode_claims(&id_token)?;
+    let expires_at =
+        OffsetDateTime::from_unix_timestamp(claims.exp).map_err(CredentialError::non_retryable)?;
+    Ok(Token {
+        token: id_token.trim().to_string(),
+        token_type: "Bearer".to_string(),
+        expires_at: Some(expires_at),
+        metadata: None,
+    })
+}
+
+/// Returns the `email` claim of an ID token, if the token has one.
+///
+/// The token is not verified, use this only for informational purposes.
+pub(crate) fn id_token_email(id_token: &str) -> Option<String> {
+    decode_claims(id_token).ok()?.email
+}
+
+fn decode_claims(id_token: &str) -> Result<IdTokenClaims> {
+    use base64::prelude::{Engine as _, BASE64_URL_SAFE_NO_PAD};
+    let invalid = || CredentialError::non_retryable("the ID token is not a valid JWT");
+    let payload = id_token.trim().split('.').nth(1).ok_or_else(invalid)?;
+    let payload = BASE64_URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .map_err(|_| invalid())?;
+    serde_json::from_slice::<IdTokenClaims>(&payload).map_err(CredentialError::non_retryable)
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct IdTokenClaims {
+    exp: i64,
+    #[serde(default)]
+    email: Option<String>,
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use http::header::AUTHORIZATION;
+    use std::time::Duration;
+
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    /// Creates an unsigned JWT with the given expiration time and audience.
+    pub(crate) fn fake_id_token(exp: OffsetDateTime, aud: &str) -> String {
+        use base64::prelude::{Engine as _, BASE64_URL_SAFE_NO_PAD};
+        let header = BASE64_URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","typ":"JWT"}"#);
+        let claims = serde_json::json!({
+            "aud": aud,
+            "exp": exp.unix_timestamp(),
+            "iat": (exp - Duration::from_secs(3600)).unix_timestamp(),
+            "iss": "https://accounts.google.com",
+        });
+        let claims = BASE64_URL_SAFE_NO_PAD.encode(claims.to_string());
+        format!("{header}.{claims}.c2lnbmF0dXJl")
+    }
+
+    fn expiration() -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(OffsetDateTime::now_utc().unix_timestamp() + 3600)
+            .unwrap()
+    }
+
+    #[test]
+    fn decode_token() -> TestResult {
+        let exp = expiration();
+        let jwt = fake_id_token(exp, "https://example.com");
+        let token = token_from_jwt(format!("{jwt}\n"))?;
+        assert_eq!(token.token, jwt);
+        assert_eq!(token.token_type, "Bearer");
+        assert_eq!(token.expires_at, Some(exp));
+        Ok(())
+    }
+
+    #[test]
+    fn decode_token_errors() {
+        for input in ["", "abc", "a.!!!.c", "a.e30.c"] {
+            let e = token_from_jwt(input.to_string()).err().unwrap();
+            assert!(!e.is_retryable(), "{input} {e}");
+        }
+    }
+
+    #[test]
+    fn decode_email() {
+        use base64::prelude::{Engine as _, BASE64_URL_SAFE_NO_PAD};
+        let claims = BASE64_URL_SAFE_NO_PAD.encode(r#"{"exp":0,"email":"user@example.com"}"#);
+        let jwt = format!("e30.{claims}.c2lnbmF0dXJl");
+        assert_eq!(id_token_email(&jwt).as_deref(), Some("user@example.com"));
+
+        let jwt = fake_id_token(expiration(), "https://example.com");
+        assert_eq!(id_token_email(&jwt), None);
+        assert_eq!(id_token_email("not-a-jwt"), None);
+    }
+
+    #[derive(Debug)]
+    struct FakeProvider(String);
+
+    #[async_trait::async_trait]
+    impl TokenProvider for FakeProvider {
+        async fn get_token(&self) -> Result<Token> {
+            token_from_jwt(self.0.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn get_headers() -> TestResult {
+        let jwt = fake_id_token(expiration(), "https://example.com");
+        let creds = IdTokenCredential::new(FakeProvider(jwt.clone()));
+        let headers = creds.get_headers().await?;
+        assert_eq!(headers.len(), 1, "{headers:?}");
+        assert_eq!(headers[0].0, AUTHORIZATION);
+        assert_eq!(headers[0].1.to_str()?, format!("Bearer {jwt}"));
+        assert!(headers[0].1.is_sensitive());
+        Ok(())
+    }
+
+    #[test]
+    fn builder_defaults() {
+        let builder = Builder::new("https://example.com");
+        assert_eq!(builder.target_audience, "https://example.com");
+        assert!(!builder.include_email);
+        assert!(builder.delegates.is_empty());
+        assert_eq!(builder.iam_endpoint, IAM_CREDENTIALS_ENDPOINT);
+    }
+
+    #[test]
+    fn build_service_account_invalid_key() {
+        let e = Builder::new("https://example.com")
+            .build_service_account(serde_json::json!({"type": "service_account"}))
+            .err()
+            .unwrap();
+        assert!(!e.is_retryable(), "{e}");
+    }
+}
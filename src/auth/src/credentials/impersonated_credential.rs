@@ -0,0 +1,1113 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Impersonated service account] credentials.
+//!
+//! A principal (the *source*) with the `Service Account Token Creator` role on
+//! a service account (the *target*) can create short-lived access tokens for
+//! the target service account. These credentials use the source credentials
+//! to call the IAM Credentials [generateAccessToken] API.
+//!
+//! The source credentials are typically the credentials of a developer, which
+//! lets the developer run applications with the same permissions as the
+//! service account used in production.
+//!
+//! [Impersonated service account]: https://cloud.google.com/iam/docs/service-account-impersonation
+//! [generateAccessToken]: https://cloud.google.com/iam/docs/reference/credentials/rest/v1/projects.serviceAccounts/generateAccessToken
+
+use crate::credentials::dynamic::CredentialTrait;
+use crate::credentials::id_token_credential;
+use crate::credentials::signer::{sign_blob_url, IamSigner, Signer};
+use crate::credentials::util::http_client;
+use crate::credentials::util::{
+    build_headers, DEFAULT_SCOPES, DEFAULT_UNIVERSE_DOMAIN, IAM_CREDENTIALS_ENDPOINT,
+};
+use crate::credentials::{Credential, CredentialOptions, Result};
+use crate::errors::{is_retryable, CredentialError};
+use crate::retry::{ExponentialBackoff, RetryPolicy, RetryTokenProvider};
+use crate::token::{metadata_keys, Token, TokenProvider};
+use crate::token_cache::TokenCache;
+use http::header::{HeaderName, HeaderValue};
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+const DEFAULT_LIFETIME: Duration = Duration::from_secs(3600);
+
+const SERVICE_ACCOUNTS_PREFIX: &str = "projects/-/serviceAccounts/";
+
+const SOURCE_TYPE: &str = "impersonated_service_account";
+
+pub(crate) fn creds_from(js: serde_json::Value, options: &CredentialOptions) -> Result<Credential> {
+    let config = serde_json::from_value::<ImpersonatedServiceAccount>(js)
+        .map_err(CredentialError::non_retryable)?;
+    let source =
+        crate::credentials::creds_from(config.source_credentials, &source_options(options))?;
+    let url = match &options.token_endpoint {
+        None => config.service_account_impersonation_url,
+        Some(endpoint) => with_endpoint(&config.service_account_impersonation_url, endpoint)?,
+    };
+    let delegates = config.delegates.unwrap_or_default();
+    let signer = signer_from_url(&source, &url, &delegates);
+    let token_provider = ImpersonatedTokenProvider {
+        source,
+        url,
+        delegates,
+        scopes: options
+            .scopes
+            .clone()
+            .unwrap_or_else(|| DEFAULT_SCOPES.map(str::to_string).to_vec()),
+        lifetime: DEFAULT_LIFETIME,
+        source_type: SOURCE_TYPE,
+    };
+    Ok(Credential {
+        inner: Arc::new(ImpersonatedServiceAccountCredential {
+            token_provider: TokenCache::new(options.retrying(token_provider)),
+            quota_project_id: options.quota_project_id.clone().or(config.quota_project_id),
+            universe_domain: options.universe_domain.clone(),
+            signer,
+        }),
+    })
+}
+
+// The options for the source credentials.
+//
+// The source credentials share the retry and backoff policies. The other
+// options apply to the impersonated credentials only: the token endpoint is
+// the IAM Credentials API endpoint, the scopes and quota project are those of
+// the impersonated tokens, and the source tokens need the default scopes to
+// call the IAM Credentials API.
+fn source_options(options: &CredentialOptions) -> CredentialOptions {
+    CredentialOptions {
+        retry_policy: options.retry_policy.clone(),
+        backoff_policy: options.backoff_policy.clone(),
+        ..Default::default()
+    }
+}
+
+// Creates a signer from a `generateAccessToken` URL, e.g.
+// `https://iamcredentials.googleapis.com/v1/projects/-/serviceAccounts/{email}:generateAccessToken`.
+fn signer_from_url(source: &Credential, url: &str, delegates: &[String]) -> Option<Signer> {
+    let prefix = url.strip_suffix(":generateAccessToken")?;
+    let (_, client_email) = prefix.rsplit_once('/')?;
+    Some(Signer::new(IamSigner::new(
+        source.clone(),
+        format!("{prefix}:signBlob"),
+        client_email.to_string(),
+        delegates.to_vec(),
+    )))
+}
+
+// Replaces the endpoint in an impersonation URL, e.g.
+// `https://iamcredentials.googleapis.com/v1/projects/-/serviceAccounts/...`.
+fn with_endpoint(url: &str, endpoint: &str) -> Result<String> {
+    let (_, path) = url.split_once("/v1/").ok_or_else(|| {
+        CredentialError::non_retryable(format!(
+            "unexpected `service_account_impersonation_url`, expected a URL containing `/v1/`, got {url}"
+        ))
+    })?;
+    Ok(format!("{}/v1/{path}", endpoint.trim_end_matches('/')))
+}
+
+/// A builder for impersonated service account [Credential]s.
+///
+/// # Example
+/// ```
+/// # use gcp_sdk_auth::credentials::create_access_token_credential;
+/// # use gcp_sdk_auth::credentials::impersonated_credential::Builder;
+/// # use gcp_sdk_auth::errors::CredentialError;
+/// # tokio_test::block_on(async {
+/// let source = create_access_token_credential().await?;
+/// let credential = Builder::new(source, "my-sa@my-project.iam.gserviceaccount.com")
+///     .with_lifetime(std::time::Duration::from_secs(600))
+///     .build()?;
+/// # Ok::<(), CredentialError>(())
+/// # });
+/// ```
+#[derive(Clone, Debug)]
+pub struct Builder {
+    source: Credential,
+    target_principal: String,
+    delegates: Vec<String>,
+    scopes: Vec<String>,
+    lifetime: Duration,
+    endpoint: String,
+    quota_project_id: Option<String>,
+    retry_policy: RetryPolicy,
+    backoff_policy: ExponentialBackoff,
+}
+
+impl Builder {
+    /// Creates a builder to impersonate `target_principal` using the `source`
+    /// credentials.
+    ///
+    /// The target principal is the email address of the service account.
+    pub fn new<T: Into<String>>(source: Credential, target_principal: T) -> Self {
+        Self {
+            source,
+            target_principal: target_principal.into(),
+            delegates: Vec::new(),
+            scopes: DEFAULT_SCOPES.map(str::to_string).to_vec(),
+            lifetime: DEFAULT_LIFETIME,
+            endpoint: IAM_CREDENTIALS_ENDPOINT.to_string(),
+            quota_project_id: None,
+            retry_policy: RetryPolicy::default(),
+            backoff_policy: ExponentialBackoff::default(),
+        }
+    }
+
+    /// Sets the [delegation chain].
+    ///
+    /// Each service account in the chain must have the `Service Account Token
+    /// Creator` role on the next service account in the chain. The last
+    /// service account in the chain must have this role on the target
+    /// principal. The source credentials must have the role on the first
+    /// service account in the chain.
+    ///
+    /// [delegation chain]: https://cloud.google.com/iam/docs/create-short-lived-credentials-delegated
+    pub fn with_delegates<I, S>(mut self, v: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.delegates = v.into_iter().map(|s| s.into()).collect();
+        self
+    }
+
+    /// Sets the OAuth 2.0 scopes of the access tokens.
+    ///
+    /// The default is the `https://www.googleapis.com/auth/cloud-platform`
+    /// scope.
+    pub fn with_scopes<I, S>(mut self, v: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.scopes = v.into_iter().map(|s| s.into()).collect();
+        self
+    }
+
+    /// Sets the lifetime of the access tokens.
+    ///
+    /// The default is one hour. Longer lifetimes require an [organization
+    /// policy].
+    ///
+    /// [organization policy]: https://cloud.google.com/iam/docs/create-short-lived-credentials-direct#extend-oauth-ttl
+    pub fn with_lifetime(mut self, v: Duration) -> Self {
+        self.lifetime = v;
+        self
+    }
+
+    /// Overrides the IAM Credentials API endpoint.
+    ///
+    /// The default is `https://iamcredentials.googleapis.com`.
+    pub fn with_endpoint<T: Into<String>>(mut self, v: T) -> Self {
+        self.endpoint = v.into();
+        self
+    }
+
+    /// Sets the [quota project] for requests using these credentials.
+    ///
+    /// [quota project]: https://cloud.google.com/docs/quotas/quota-project
+    pub fn with_quota_project_id<T: Into<String>>(mut self, v: T) -> Self {
+        self.quota_project_id = Some(v.into());
+        self
+    }
+
+    /// Sets the retry policy for the requests that fetch access tokens.
+    ///
+    /// This applies to the `generateAccessToken` requests. The source
+    /// credentials use the policy they were created with. See [RetryPolicy]
+    /// for the defaults.
+    pub fn with_retry_policy(mut self, v: RetryPolicy) -> Self {
+        self.retry_policy = v;
+        self
+    }
+
+    /// Sets the backoff policy between attempts to fetch access tokens.
+    pub fn with_backoff_policy(mut self, v: ExponentialBackoff) -> Self {
+        self.backoff_policy = v;
+        self
+    }
+
+    /// Returns a [Credential] with the configured options.
+    pub fn build(self) -> Result<Credential> {
+        if self.lifetime.is_zero() {
+            return Err(CredentialError::non_retryable(
+                "the lifetime of impersonated credentials must be positive",
+            ));
+        }
+        let url = format!(
+            "{}/v1/{}:generateAccessToken",
+            self.endpoint.trim_end_matches('/'),
+            service_account_name(&self.target_principal)
+        );
+        let client_email = self
+            .target_principal
+            .trim_start_matches(SERVICE_ACCOUNTS_PREFIX)
+            .to_string();
+        let signer = Signer::new(IamSigner::new(
+            self.source.clone(),
+            sign_blob_url(&self.endpoint, &client_email),
+            client_email,
+            self.delegates.clone(),
+        ));
+        let token_provider = ImpersonatedTokenProvider {
+            source: self.source,
+            url,
+            delegates: self.delegates,
+            scopes: self.scopes,
+            lifetime: self.lifetime,
+            source_type: SOURCE_TYPE,
+        };
+        Ok(Credential {
+            inner: Arc::new(ImpersonatedServiceAccountCredential {
+                token_provider: TokenCache::new(RetryTokenProvider::new(
+                    token_provider,
+                    self.retry_policy,
+                    self.backoff_policy,
+                )),
+                quota_project_id: self.quota_project_id,
+                universe_domain: None,
+                signer: Some(signer),
+            }),
+        })
+    }
+}
+
+// Returns the IAM resource name for a service account email, unless it is
+// already a resource name.
+pub(crate) fn service_account_name(email: &str) -> String {
+    if email.starts_with(SERVICE_ACCOUNTS_PREFIX) {
+        return email.to_string();
+    }
+    format!("{SERVICE_ACCOUNTS_PREFIX}{email}")
+}
+
+// Extracts the service account email from an impersonation URL, e.g.
+// `.../projects/-/serviceAccounts/{email}:generateAccessToken`.
+pub(crate) fn impersonated_email(url: &str) -> Option<String> {
+    let (_, name) = url.rsplit_once("/serviceAccounts/")?;
+    let (email, _) = name.split_once(':')?;
+    Some(email.to_string())
+}
+
+/// The representation of impersonated service account credentials in ADC
+/// files, as created by `gcloud auth application-default login
+/// --impersonate-service-account`.
+#[derive(Debug, serde::Deserialize)]
+struct ImpersonatedServiceAccount {
+    service_account_impersonation_url: String,
+    source_credentials: serde_json::Value,
+    delegates: Option<Vec<String>>,
+    quota_project_id: Option<String>,
+}
+
+#[derive(Debug)]
+pub(crate) struct ImpersonatedTokenProvider {
+    source: Credential,
+    url: String,
+    delegates: Vec<String>,
+    scopes: Vec<String>,
+    lifetime: Duration,
+    // Recorded in the token metadata, see `metadata_keys::SOURCE_TYPE`.
+    source_type: &'static str,
+}
+
+impl ImpersonatedTokenProvider {
+    /// Creates a provider calling the `generateAccessToken` API at `url`.
+    ///
+    /// Other credential types use this provider to impersonate a service
+    /// account, using their own tokens as the source credentials. The tokens
+    /// report `source_type` as their [metadata_keys::SOURCE_TYPE].
+    pub(crate) fn new(
+        source: Credential,
+        url: String,
+        scopes: Vec<String>,
+        lifetime: Option<Duration>,
+        source_type: &'static str,
+    ) -> Self {
+        Self {
+            source,
+            url,
+            delegates: Vec::new(),
+            scopes,
+            lifetime: lifetime.unwrap_or(DEFAULT_LIFETIME),
+            source_type,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl TokenProvider for ImpersonatedTokenProvider {
+    async fn get_token(&self) -> Result<Token> {
+        let request = GenerateAccessTokenRequest {
+            delegates: self
+                .delegates
+                .iter()
+                .map(|d| service_account_name(d))
+                .collect(),
+            scope: self.scopes.clone(),
+            lifetime: format!("{}s", self.lifetime.as_secs()),
+        };
+        let response: GenerateAccessTokenResponse =
+            post_iam(&self.source, &self.url, &request).await?;
+        let token = Token {
+            token: response.access_token,
+            token_type: "Bearer".to_string(),
+            expires_at: Some(response.expire_time),
+            metadata: None,
+        };
+        Ok(token
+            .with_metadata(metadata_keys::SCOPES, Some(self.scopes.join(" ")))
+            .with_metadata(metadata_keys::SOURCE_TYPE, Some(self.source_type))
+            .with_metadata(
+                metadata_keys::PRINCIPAL_EMAIL,
+                impersonated_email(&self.url),
+            ))
+    }
+}
+
+/// Creates a provider for ID tokens using impersonated service account
+/// credentials in ADC format.
+pub(crate) fn id_token_provider_from(
+    js: serde_json::Value,
+    audience: String,
+    include_email: bool,
+) -> Result<ImpersonatedIdTokenProvider> {
+    let config = serde_json::from_value::<ImpersonatedServiceAccount>(js)
+        .map_err(CredentialError::non_retryable)?;
+    let source =
+        crate::credentials::creds_from(config.source_credentials, &CredentialOptions::default())?;
+    let url = config
+        .service_account_impersonation_url
+        .strip_suffix(":generateAccessToken")
+        .map(|prefix| format!("{prefix}:generateIdToken"))
+        .ok_or_else(|| {
+            CredentialError::non_retryable(format!(
+                "unexpected `service_account_impersonation_url`, expected a URL ending in `:generateAccessToken`, got {}",
+                config.service_account_impersonation_url
+            ))
+        })?;
+    Ok(ImpersonatedIdTokenProvider {
+        source,
+        url,
+        delegates: config.delegates.unwrap_or_default(),
+        audience,
+        include_email,
+    })
+}
+
+/// Creates a provider for ID tokens impersonating `target_principal`.
+pub(crate) fn id_token_provider(
+    source: Credential,
+    target_principal: &str,
+    delegates: Vec<String>,
+    endpoint: &str,
+    audience: String,
+    include_email: bool,
+) -> ImpersonatedIdTokenProvider {
+    let url = format!(
+        "{}/v1/{}:generateIdToken",
+        endpoint.trim_end_matches('/'),
+        service_account_name(target_principal)
+    );
+    ImpersonatedIdTokenProvider {
+        source,
+        url,
+        delegates,
+        audience,
+        include_email,
+    }
+}
+
+/// Calls the IAM Credentials [generateIdToken] API.
+///
+/// [generateIdToken]: https://cloud.google.com/iam/docs/reference/credentials/rest/v1/projects.serviceAccounts/generateIdToken
+#[derive(Debug)]
+pub(crate) struct ImpersonatedIdTokenProvider {
+    source: Credential,
+    url: String,
+    delegates: Vec<String>,
+    audience: String,
+    include_email: bool,
+}
+
+#[async_trait::async_trait]
+impl TokenProvider for ImpersonatedIdTokenProvider {
+    async fn get_token(&self) -> Result<Token> {
+        let request = GenerateIdTokenRequest {
+            delegates: self
+                .delegates
+                .iter()
+                .map(|d| service_account_name(d))
+                .collect(),
+            audience: self.audience.clone(),
+            include_email: self.include_email,
+        };
+        let response: GenerateIdTokenResponse = post_iam(&self.source, &self.url, &request).await?;
+        id_token_credential::token_from_jwt(response.token)
+    }
+}
+
+// Sends a request to the IAM Credentials API, authenticated with the source
+// credentials.
+pub(crate) async fn post_iam<Req, Resp>(
+    source: &Credential,
+    url: &str,
+    request: &Req,
+) -> Result<Resp>
+where
+    Req: serde::Serialize,
+    Resp: serde::de::DeserializeOwned,
+{
+    let client = http_client::client();
+    let mut builder = client.post(url).json(request);
+    for (name, value) in source.get_headers().await? {
+        builder = builder.header(name, value);
+    }
+    let resp = builder.send().await.map_err(CredentialError::retryable)?;
+
+    // Process the response
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| CredentialError::new(is_retryable(status), e.into()))?;
+        return Err(CredentialError::new(
+            is_retryable(status),
+            Box::from(format!("Failed to call the IAM Credentials API. {body}")),
+        ));
+    }
+    resp.json::<Resp>().await.map_err(|e| {
+        let retryable = !e.is_decode();
+        CredentialError::new(retryable, e.into())
+    })
+}
+
+#[derive(Debug)]
+struct ImpersonatedServiceAccountCredential<T>
+where
+    T: TokenProvider,
+{
+    token_provider: T,
+    quota_project_id: Option<String>,
+    universe_domain: Option<String>,
+    signer: Option<Signer>,
+}
+
+#[async_trait::async_trait]
+impl<T> CredentialTrait for ImpersonatedServiceAccountCredential<T>
+where
+    T: TokenProvider,
+{
+    async fn get_token(&self) -> Result<Token> {
+        self.token_provider.get_token().await
+    }
+
+    async fn get_headers(&self) -> Result<Vec<(HeaderName, HeaderValue)>> {
+        let token = self.get_token().await?;
+        build_headers(&token, self.quota_project_id.as_deref())
+    }
+
+    async fn get_universe_domain(&self) -> Option<String> {
+        Some(
+            self.universe_domain
+                .clone()
+                .unwrap_or_else(|| DEFAULT_UNIVERSE_DOMAIN.to_string()),
+        )
+    }
+
+    fn signer(&self) -> Option<Signer> {
+        self.signer.clone()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+struct GenerateAccessTokenRequest {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    delegates: Vec<String>,
+    scope: Vec<String>,
+    lifetime: String,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateAccessTokenResponse {
+    access_token: String,
+    #[serde(with = "time::serde::rfc3339")]
+    expire_time: OffsetDateTime,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateIdTokenRequest {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    delegates: Vec<String>,
+    audience: String,
+    include_email: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+struct GenerateIdTokenResponse {
+    token: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::credentials::QUOTA_PROJECT_KEY;
+    use crate::token::test::MockTokenProvider;
+    use axum::extract::Json;
+    use http::header::AUTHORIZATION;
+    use http::{HeaderMap, StatusCode};
+    use std::error::Error;
+    use tokio::task::JoinHandle;
+
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    const TARGET: &str = "test-sa@test-project.iam.gserviceaccount.com";
+
+    #[derive(Debug)]
+    struct FakeSource;
+
+    #[async_trait::async_trait]
+    impl CredentialTrait for FakeSource {
+        async fn get_token(&self) -> Result<Token> {
+            Ok(Token {
+                token: "source-token".to_string(),
+                token_type: "Bearer".to_string(),
+                expires_at: None,
+                metadata: None,
+            })
+        }
+
+        async fn get_headers(&self) -> Result<Vec<(HeaderName, HeaderValue)>> {
+            Ok(vec![(
+                AUTHORIZATION,
+                HeaderValue::from_static("Bearer source-token"),
+            )])
+        }
+    }
+
+    fn fake_source() -> Credential {
+        Credential {
+            inner: Arc::new(FakeSource),
+        }
+    }
+
+    #[test]
+    fn service_account_names() {
+        assert_eq!(
+            service_account_name(TARGET),
+            format!("projects/-/serviceAccounts/{TARGET}")
+        );
+        assert_eq!(
+            service_account_name(&format!("projects/-/serviceAccounts/{TARGET}")),
+            format!("projects/-/serviceAccounts/{TARGET}")
+        );
+    }
+
+    #[test]
+    fn request_serde() -> TestResult {
+        let request = GenerateAccessTokenRequest {
+            delegates: vec!["projects/-/serviceAccounts/d1".to_string()],
+            scope: vec!["scope1".to_string(), "scope2".to_string()],
+            lifetime: "600s".to_string(),
+        };
+        let json = serde_json::to_value(&request)?;
+        let expected = serde_json::json!({
+            "delegates": ["projects/-/serviceAccounts/d1"],
+            "scope": ["scope1", "scope2"],
+            "lifetime": "600s",
+        });
+        assert_eq!(json, expected);
+
+        let request = GenerateAccessTokenRequest {
+            delegates: Vec::new(),
+            ..request
+        };
+        let json = serde_json::to_value(&request)?;
+        assert_eq!(json.get("delegates"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn response_serde() -> TestResult {
+        let json = serde_json::json!({
+            "accessToken": "test-access-token",
+            "expireTime": "2025-01-02T03:04:05Z",
+        });
+        let response = serde_json::from_value::<GenerateAccessTokenResponse>(json)?;
+        assert_eq!(response.access_token, "test-access-token");
+        assert_eq!(
+            response.expire_time,
+            OffsetDateTime::parse(
+                "2025-01-02T03:04:05Z",
+                &time::format_description::well_known::Rfc3339
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn builder_zero_lifetime_is_error() {
+        let e = Builder::new(fake_source(), TARGET)
+            .with_lifetime(Duration::ZERO)
+            .build()
+            .err()
+            .unwrap();
+        assert!(e.to_string().contains("lifetime"), "{e}");
+    }
+
+    #[tokio::test]
+    async fn get_headers_with_quota_project() -> TestResult {
+        let mut mock = MockTokenProvider::new();
+        mock.expect_get_token().times(1).return_once(|| {
+            Ok(Token {
+                token: "test-token".to_string(),
+                token_type: "Bearer".to_string(),
+                expires_at: None,
+                metadata: None,
+            })
+        });
+        let creds = ImpersonatedServiceAccountCredential {
+            token_provider: mock,
+            quota_project_id: Some("test-project".to_string()),
+            universe_domain: None,
+            signer: None,
+        };
+        let headers = creds.get_headers().await?;
+        assert_eq!(
+            headers,
+            vec![
+                (AUTHORIZATION, HeaderValue::from_static("Bearer test-token")),
+                (
+                    HeaderName::from_static(QUOTA_PROJECT_KEY),
+                    HeaderValue::from_static("test-project")
+                ),
+            ]
+        );
+        assert!(headers[0].1.is_sensitive());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_token_source_error() {
+        let mut mock = MockTokenProvider::new();
+        mock.expect_get_token()
+            .times(1)
+            .return_once(|| Err(CredentialError::retryable("source failed")));
+        let source = Credential {
+            inner: Arc::new(ImpersonatedServiceAccountCredential {
+                token_provider: mock,
+                quota_project_id: None,
+                universe_domain: None,
+                signer: None,
+            }),
+        };
+        let creds = Builder::new(source, TARGET)
+            .with_endpoint("http://127.0.0.1:1")
+            .with_retry_policy(RetryPolicy::never())
+            .build()
+            .unwrap();
+        let e = creds.get_token().await.err().unwrap();
+        assert!(e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("source failed"), "{e}");
+    }
+
+    // Starts a server running locally. Returns an (endpoint, server) pair.
+    async fn start(response_code: StatusCode, response_body: String) -> (String, JoinHandle<()>) {
+        let handler = move |headers: HeaderMap, Json(request): Json<GenerateAccessTokenRequest>| async move {
+            assert_eq!(
+                headers.get(AUTHORIZATION).map(|v| v.to_str().unwrap()),
+                Some("Bearer source-token")
+            );
+            assert_eq!(
+                request.delegates,
+                vec![format!("projects/-/serviceAccounts/test-delegate")]
+            );
+            assert_eq!(request.scope, vec!["scope1".to_string()]);
+            assert_eq!(request.lifetime, "600s");
+            (response_code, response_body)
+        };
+        let path = format!("/v1/projects/-/serviceAccounts/{TARGET}:generateAccessToken");
+        let app = axum::Router::new().route(&path, axum::routing::post(handler));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        (format!("http://{}:{}", addr.ip(), addr.port()), server)
+    }
+
+    fn test_credential(endpoint: &str) -> Result<Credential> {
+        Builder::new(fake_source(), TARGET)
+            .with_delegates(["test-delegate"])
+            .with_scopes(["scope1"])
+            .with_lifetime(Duration::from_secs(600))
+            .with_endpoint(endpoint)
+            .build()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn token_provider_success() -> TestResult {
+        let expire_time = OffsetDateTime::now_utc() + Duration::from_secs(600);
+        let response = GenerateAccessTokenResponse {
+            access_token: "test-access-token".to_string(),
+            expire_time: expire_time.replace_nanosecond(0)?,
+        };
+        let (endpoint, _server) = start(StatusCode::OK, serde_json::to_string(&response)?).await;
+
+        let creds = test_credential(&endpoint)?;
+        let token = creds.get_token().await?;
+        assert_eq!(token.token, "test-access-token");
+        assert_eq!(token.token_type, "Bearer");
+        assert_eq!(token.expires_at, Some(response.expire_time));
+        assert_eq!(token.scopes(), Some(vec!["scope1"]));
+        assert_eq!(token.source_type(), Some("impersonated_service_account"));
+        assert_eq!(token.principal_email(), Some(TARGET));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn token_provider_retryable_error() -> TestResult {
+        let (endpoint, _server) =
+            start(StatusCode::SERVICE_UNAVAILABLE, "try again".to_string()).await;
+        let creds = test_credential(&endpoint)?;
+        let e = creds.get_token().await.err().unwrap();
+        assert!(e.is_retryable(), "{e}");
+        assert!(e.source().unwrap().to_string().contains("try again"), "{e}");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn token_provider_nonretryable_error() -> TestResult {
+        let (endpoint, _server) = start(StatusCode::FORBIDDEN, "epic fail".to_string()).await;
+        let creds = test_credential(&endpoint)?;
+        let e = creds.get_token().await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(e.source().unwrap().to_string().contains("epic fail"), "{e}");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn token_provider_malformed_response_is_nonretryable() -> TestResult {
+        let (endpoint, _server) = start(StatusCode::OK, "bad json".to_string()).await;
+        let creds = test_credential(&endpoint)?;
+        let e = creds.get_token().await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        Ok(())
+    }
+
+    #[test]
+    fn creds_from_adc() -> TestResult {
+        let json = serde_json::json!({
+            "type": "impersonated_service_account",
+            "service_account_impersonation_url": format!("https://iamcredentials.googleapis.com/v1/projects/-/serviceAccounts/{TARGET}:generateAccessToken"),
+            "delegates": ["test-delegate"],
+            "quota_project_id": "test-project",
+            "source_credentials": {
+                "type": "authorized_user",
+                "client_id": "test-client-id",
+                "client_secret": "test-client-secret",
+                "refresh_token": "test-refresh-token",
+            }
+        });
+        let creds = creds_from(json, &CredentialOptions::default())?;
+        let fmt = format!("{creds:?}");
+        assert!(
+            fmt.contains("ImpersonatedServiceAccountCredential"),
+            "{fmt}"
+        );
+        assert!(fmt.contains("UserCredential"), "{fmt}");
+        assert!(fmt.contains("test-delegate"), "{fmt}");
+        assert!(fmt.contains("test-project"), "{fmt}");
+        assert!(!fmt.contains("test-refresh-token"), "{fmt}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn creds_from_adc_with_options() -> TestResult {
+        let json = serde_json::json!({
+            "type": "impersonated_service_account",
+            "service_account_impersonation_url": format!("https://iamcredentials.googleapis.com/v1/projects/-/serviceAccounts/{TARGET}:generateAccessToken"),
+            "quota_project_id": "test-project",
+            "source_credentials": {
+                "type": "authorized_user",
+                "client_id": "test-client-id",
+                "client_secret": "test-client-secret",
+                "refresh_token": "test-refresh-token",
+            }
+        });
+        let options = CredentialOptions {
+            scopes: Some(vec!["scope1".to_string()]),
+            quota_project_id: Some("override-project".to_string()),
+            universe_domain: Some("test-universe-domain".to_string()),
+            token_endpoint: Some("https://iam.example.com/".to_string()),
+            ..Default::default()
+        };
+        let creds = creds_from(json, &options)?;
+        let fmt = format!("{creds:?}");
+        assert!(
+            fmt.contains(&format!(
+                "https://iam.example.com/v1/projects/-/serviceAccounts/{TARGET}:generateAccessToken"
+            )),
+            "{fmt}"
+        );
+        assert!(fmt.contains("scope1"), "{fmt}");
+        assert!(fmt.contains("override-project"), "{fmt}");
+        assert!(!fmt.contains("\"test-project\""), "{fmt}");
+        assert_eq!(
+            creds.get_universe_domain().await.as_deref(),
+            Some("test-universe-domain")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn source_options_policies() {
+        let options = CredentialOptions {
+            scopes: Some(vec!["scope1".to_string()]),
+            quota_project_id: Some("test-project".to_string()),
+            universe_domain: Some("test-universe-domain".to_string()),
+            token_endpoint: Some("https://iam.example.com/".to_string()),
+            retry_policy: RetryPolicy::new().with_maximum_attempts(7),
+            backoff_policy: ExponentialBackoff::new().with_initial_delay(Duration::from_millis(7)),
+            ..Default::default()
+        };
+        let got = source_options(&options);
+        let want = CredentialOptions {
+            retry_policy: options.retry_policy.clone(),
+            backoff_policy: options.backoff_policy.clone(),
+            ..Default::default()
+        };
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn creds_from_adc_source_policies() -> TestResult {
+        let json = serde_json::json!({
+            "type": "impersonated_service_account",
+            "service_account_impersonation_url": format!("https://iamcredentials.googleapis.com/v1/projects/-/serviceAccounts/{TARGET}:generateAccessToken"),
+            "source_credentials": {
+                "type": "authorized_user",
+                "client_id": "test-client-id",
+                "client_secret": "test-client-secret",
+                "refresh_token": "test-refresh-token",
+            }
+        });
+        let options = CredentialOptions {
+            retry_policy: RetryPolicy::new().with_maximum_attempts(7),
+            ..Default::default()
+        };
+        let creds = creds_from(json, &options)?;
+        // Both the impersonated and the source credentials use the policy.
+        let fmt = format!("{creds:?}");
+        assert!(fmt.contains("maximum_attempts: 7"), "{fmt}");
+        assert!(!fmt.contains("maximum_attempts: 5"), "{fmt}");
+        Ok(())
+    }
+
+    #[test]
+    fn with_endpoint_bad_url() {
+        let e = with_endpoint("https://example.com/unexpected", "https://iam.example.com")
+            .err()
+            .unwrap();
+        assert!(e.to_string().contains("/v1/"), "{e}");
+    }
+
+    #[test]
+    fn creds_from_adc_bad_source() {
+        let json = serde_json::json!({
+            "type": "impersonated_service_account",
+            "service_account_impersonation_url": "https://iamcredentials.googleapis.com/v1/projects/-/serviceAccounts/test:generateAccessToken",
+            "source_credentials": {
+                "type": "unknown",
+            }
+        });
+        let e = creds_from(json, &CredentialOptions::default())
+            .err()
+            .unwrap();
+        assert!(e.to_string().contains("Unimplemented"), "{e}");
+    }
+
+    #[test]
+    fn creds_from_adc_missing_fields() {
+        let json = serde_json::json!({
+            "type": "impersonated_service_account",
+            "source_credentials": {
+                "type": "authorized_user",
+                "client_id": "test-client-id",
+                "client_secret": "test-client-secret",
+                "refresh_token": "test-refresh-token",
+            }
+        });
+        assert!(creds_from(json, &CredentialOptions::default()).is_err());
+    }
+
+    // Starts a server returning ID tokens. Returns an (endpoint, server) pair.
+    async fn start_id_token(
+        response_code: StatusCode,
+        response_body: String,
+    ) -> (String, JoinHandle<()>) {
+        let handler = move |headers: HeaderMap, Json(request): Json<GenerateIdTokenRequest>| async move {
+            assert_eq!(
+                headers.get(AUTHORIZATION).map(|v| v.to_str().unwrap()),
+                Some("Bearer source-token")
+            );
+            let want = GenerateIdTokenRequest {
+                delegates: vec!["projects/-/serviceAccounts/test-delegate".to_string()],
+                audience: "https://example.com".to_string(),
+                include_email: true,
+            };
+            assert_eq!(request, want);
+            (response_code, response_body)
+        };
+        let path = format!("/v1/projects/-/serviceAccounts/{TARGET}:generateIdToken");
+        let app = axum::Router::new().route(&path, axum::routing::post(handler));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        (format!("http://{}:{}", addr.ip(), addr.port()), server)
+    }
+
+    fn test_id_token_provider(endpoint: &str) -> ImpersonatedIdTokenProvider {
+        id_token_provider(
+            fake_source(),
+            TARGET,
+            vec!["test-delegate".to_string()],
+            endpoint,
+            "https://example.com".to_string(),
+            true,
+        )
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn id_token_provider_success() -> TestResult {
+        let exp = OffsetDateTime::now_utc().replace_nanosecond(0)? + Duration::from_secs(600);
+        let id_token = id_token_credential::test::fake_id_token(exp, "https://example.com");
+        let response = GenerateIdTokenResponse {
+            token: id_token.clone(),
+        };
+        let (endpoint, _server) =
+            start_id_token(StatusCode::OK, serde_json::to_string(&response)?).await;
+
+        let token = test_id_token_provider(&endpoint).get_token().await?;
+        assert_eq!(token.token, id_token);
+        assert_eq!(token.expires_at, Some(exp));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn id_token_provider_errors() -> TestResult {
+        let (endpoint, _server) =
+            start_id_token(StatusCode::SERVICE_UNAVAILABLE, "try again".to_string()).await;
+        let e = test_id_token_provider(&endpoint)
+            .get_token()
+            .await
+            .err()
+            .unwrap();
+        assert!(e.is_retryable(), "{e}");
+
+        let (endpoint, _server) =
+            start_id_token(StatusCode::FORBIDDEN, "epic fail".to_string()).await;
+        let e = test_id_token_provider(&endpoint)
+            .get_token()
+            .await
+            .err()
+            .unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(e.source().unwrap().to_string().contains("epic fail"), "{e}");
+        Ok(())
+    }
+
+    #[test]
+    fn id_token_provider_from_adc() -> TestResult {
+        let json = serde_json::json!({
+            "type": "impersonated_service_account",
+            "service_account_impersonation_url": format!("https://iamcredentials.googleapis.com/v1/projects/-/serviceAccounts/{TARGET}:generateAccessToken"),
+            "delegates": ["test-delegate"],
+            "source_credentials": {
+                "type": "authorized_user",
+                "client_id": "test-client-id",
+                "client_secret": "test-client-secret",
+                "refresh_token": "test-refresh-token",
+            }
+        });
+        let provider = id_token_provider_from(json, "https://example.com".to_string(), false)?;
+        assert_eq!(
+            provider.url,
+            format!(
+                "https://iamcredentials.googleapis.com/v1/projects/-/serviceAccounts/{TARGET}:generateIdToken"
+            )
+        );
+        assert_eq!(provider.delegates, vec!["test-delegate".to_string()]);
+        assert!(!provider.include_email);
+
+        let json = serde_json::json!({
+            "type": "impersonated_service_account",
+            "service_account_impersonation_url": "https://example.com/unexpected",
+            "source_credentials": {
+                "type": "authorized_user",
+                "client_id": "test-client-id",
+                "client_secret": "test-client-secret",
+                "refresh_token": "test-refresh-token",
+            }
+        });
+        let e = id_token_provider_from(json, "https://example.com".to_string(), false)
+            .err()
+            .unwrap();
+        assert!(e.to_string().contains(":generateAccessToken"), "{e}");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn signer_from_builder() -> TestResult {
+        let (endpoint, _server) =
+            crate::credentials::signer::test::start_iam("test-sa@test.iam.gserviceaccount.com")
+                .await;
+        let creds = Builder::new(
+            crate::credentials::testing::test_credentials(),
+            "test-sa@test.iam.gserviceaccount.com",
+        )
+        .with_endpoint(endpoint)
+        .build()?;
+        let signer = creds.signer()?;
+        assert_eq!(
+            signer.client_email().await?,
+            "test-sa@test.iam.gserviceaccount.com"
+        );
+        assert_eq!(signer.sign_blob(b"abc").await?, b"cba");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn signer_from_adc_url() -> TestResult {
+        let source = crate::credentials::testing::test_credentials();
+        let url = "https://iamcredentials.googleapis.com/v1/projects/-/serviceAccounts/test-sa@test.iam.gserviceaccount.com:generateAccessToken";
+        let signer = signer_from_url(&source, url, &[]).unwrap();
+        assert_eq!(
+            signer.client_email().await?,
+            "test-sa@test.iam.gserviceaccount.com"
+        );
+        assert!(signer_from_url(&source, "https://example.com/invalid", &[]).is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,792 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! User login for [installed applications].
+//!
+//! Command-line tools and other applications installed on a user's machine
+//! can obtain user credentials without `gcloud`. The application runs the
+//! OAuth 2.0 authorization code flow, with [PKCE], using a *loopback*
+//! redirect: the user opens the authorization URL in a browser, signs in, and
+//! the browser is redirected to a listener on `127.0.0.1`. The application
+//! then exchanges the authorization code for a refresh token.
+//!
+//! The result has the same format as the `authorized_user` files created by
+//! `gcloud auth application-default login`. Applications can use it directly,
+//! save it to a file, or save it as the Application Default Credentials.
+//!
+//! [installed applications]: https://developers.google.com/identity/protocols/oauth2/native-app
+//! [PKCE]: https://www.rfc-editor.org/rfc/rfc7636
+
+use crate::credentials::user_credential::{AuthorizedUser, OAUTH2_ENDPOINT};
+use crate::credentials::util::http_client;
+use crate::credentials::util::DEFAULT_SCOPES;
+use crate::credentials::{adc_well_known_path, Credential, Result};
+use crate::errors::{is_retryable, CredentialError};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+const AUTHORIZATION_ENDPOINT: &str = "https://accounts.google.com/o/oauth2/auth";
+
+const SUCCESS_PAGE: &str =
+    "<html><body>Authentication complete. You can close this window.</body></html>";
+
+/// A builder for the installed application login flow.
+///
+/// The OAuth client id and secret identify the application. Create them in
+/// the Google Cloud console, as a *Desktop app* OAuth client.
+///
+/// # Example
+/// ```no_run
+/// # use gcp_sdk_auth::credentials::installed_app::Builder;
+/// # use gcp_sdk_auth::errors::CredentialError;
+/// # tokio_test::block_on(async {
+/// let pending = Builder::new("my-client-id", "my-client-secret")
+///     .start()
+///     .await?;
+/// println!("Open this URL in your browser: {}", pending.url());
+/// let login = pending.complete().await?;
+/// let path = login.save_adc().await?;
+/// println!("Credentials saved to {}", path.display());
+/// # Ok::<(), CredentialError>(())
+/// # });
+/// ```
+#[derive(Clone)]
+pub struct Builder {
+    client_id: String,
+    client_secret: String,
+    scopes: Vec<String>,
+    quota_project_id: Option<String>,
+    authorization_endpoint: String,
+    token_endpoint: String,
+}
+
+impl Builder {
+    /// Creates a builder for the OAuth client with `client_id` and
+    /// `client_secret`.
+    pub fn new<T: Into<String>, S: Into<String>>(client_id: T, client_secret: S) -> Self {
+        Self {
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+            scopes: DEFAULT_SCOPES.map(str::to_string).to_vec(),
+            quota_project_id: None,
+            authorization_endpoint: AUTHORIZATION_ENDPOINT.to_string(),
+            token_endpoint: OAUTH2_ENDPOINT.to_string(),
+        }
+    }
+
+    /// Sets the OAuth 2.0 scopes requested from the user.
+    ///
+    /// The default is the `https://www.googleapis.com/auth/cloud-platform`
+    /// scope.
+    pub fn with_scopes<I, S>(mut self, v: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.scopes = v.into_iter().map(|s| s.into()).collect();
+        self
+    }
+
+    /// Sets the [quota project] saved with the credentials.
+    ///
+    /// [quota project]: https://cloud.google.com/docs/quotas/quota-project
+    pub fn with_quota_project_id<T: Into<String>>(mut self, v: T) -> Self {
+        self.quota_project_id = Some(v.into());
+        self
+    }
+
+    /// Sets the URL of the authorization endpoint.
+    ///
+    /// The default is `https://accounts.google.com/o/oauth2/auth`.
+    pub fn with_authorization_endpoint<T: Into<String>>(mut self, v: T) -> Self {
+        self.authorization_endpoint = v.into();
+        self
+    }
+
+    /// Sets the URL of the token endpoint.
+    ///
+    /// The default is `https://oauth2.googleapis.com/token`.
+    pub fn with_token_endpoint<T: Into<String>>(mut self, v: T) -> Self {
+        self.token_endpoint = v.into();
+        self
+    }
+
+    /// Starts the login flow.
+    ///
+    /// Starts the loopback listener, and returns the pending login with the
+    /// URL the user must open in a browser.
+    pub async fn start(self) -> Result<PendingLogin> {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .map_err(CredentialError::non_retryable)?;
+        let addr = listener
+            .local_addr()
+            .map_err(CredentialError::non_retryable)?;
+        let redirect_uri = format!("http://{addr}");
+        let code_verifier = random_string(32);
+        let state = random_string(16);
+        let mut url =
+            Url::parse(&self.authorization_endpoint).map_err(CredentialError::non_retryable)?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &redirect_uri)
+            .append_pair("scope", &self.scopes.join(" "))
+            .append_pair("state", &state)
+            .append_pair("code_challenge", &code_challenge(&code_verifier))
+            .append_pair("code_challenge_method", "S256")
+            .append_pair("access_type", "offline")
+            .append_pair("prompt", "consent");
+        Ok(PendingLogin {
+            builder: self,
+            listener,
+            url: url.into(),
+            redirect_uri,
+            code_verifier,
+            state,
+        })
+    }
+}
+
+impl std::fmt::Debug for Builder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Builder")
+            .field("client_id", &self.client_id)
+            .field("client_secret", &"[censored]")
+            .field("scopes", &self.scopes)
+            .field("quota_project_id", &self.quota_project_id)
+            .field("authorization_endpoint", &self.authorization_endpoint)
+            .field("token_endpoint", &self.token_endpoint)
+            .finish()
+    }
+}
+
+/// A login waiting for the user to authorize the application.
+pub struct PendingLogin {
+    builder: Builder,
+    listener: TcpListener,
+    url: String,
+    redirect_uri: String,
+    code_verifier: String,
+    state: String,
+}
+
+impl std::fmt::Debug for PendingLogin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingLogin")
+            .field("builder", &self.builder)
+            .field("url", &self.url)
+            .field("redirect_uri", &self.redirect_uri)
+            .field("code_verifier", &"[censored]")
+            .finish()
+    }
+}
+
+impl PendingLogin {
+    /// The URL the user must open in a browser to authorize the application.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Waits for the browser redirect, and exchanges the authorization code.
+    ///
+    /// This waits until the user completes, or denies, the authorization.
+    /// Wrap the call with [tokio::time::timeout] to limit the wait.
+    pub async fn complete(self) -> Result<Login> {
+        let redirect = wait_for_redirect(&self.listener, &self.state).await?;
+        let param = |name: &str| query_param(&redirect, name);
+        if let Some(error) = param("error") {
+            return Err(CredentialError::non_retryable(format!(
+                "the authorization was not granted: {error}"
+            )));
+        }
+        let code = param("code").ok_or_else(|| {
+            CredentialError::non_retryable("the authorization response has no `code`")
+        })?;
+        let builder = self.builder;
+        let refresh_token =
+            exchange_code(&builder, &code, &self.code_verifier, &self.redirect_uri).await?;
+        Ok(Login {
+            authorized_user: AuthorizedUser::new(
+                builder.client_id,
+                builder.client_secret,
+                refresh_token,
+                builder.quota_project_id,
+            ),
+        })
+    }
+}
+
+/// The user credentials obtained by a successful login.
+pub struct Login {
+    authorized_user: AuthorizedUser,
+}
+
+impl std::fmt::Debug for Login {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Login")
+            .field("authorized_user", &"[censored]")
+            .finish()
+    }
+}
+
+impl Login {
+    /// Returns the credentials as an `authorized_user` JSON object.
+    ///
+    /// The object contains the refresh token, store it securely.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(&self.authorized_user)
+            .expect("authorized user serialization cannot fail")
+    }
+
+    /// Returns a [Credential] using the refresh token.
+    pub fn credential(&self) -> Result<Credential> {
+        Credential::from_json(self.to_json())
+    }
+
+    /// Saves the credentials to `path`.
+    ///
+    /// On Unix, the file is only readable by the current user.
+    pub async fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(CredentialError::non_retryable)?;
+        }
+        let contents = serde_json::to_vec_pretty(&self.authorized_user)
+            .map_err(CredentialError::non_retryable)?;
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options
+            .open(path)
+            .await
+            .map_err(CredentialError::non_retryable)?;
+        file.write_all(&contents)
+            .await
+            .map_err(CredentialError::non_retryable)?;
+        file.flush().await.map_err(CredentialError::non_retryable)
+    }
+
+    /// Saves the credentials as the Application Default Credentials.
+    ///
+    /// Writes the credentials to the well-known ADC path, e.g.
+    /// `$HOME/.config/gcloud/application_default_credentials.json`, and
+    /// returns the path.
+    pub async fn save_adc(&self) -> Result<PathBuf> {
+        let path = adc_well_known_path().ok_or_else(|| {
+            CredentialError::non_retryable(
+                "cannot find the Application Default Credentials path, the home directory is not set",
+            )
+        })?;
+        self.save(&path).await?;
+        Ok(PathBuf::from(path))
+    }
+}
+
+fn random_string(len: usize) -> String {
+    let mut bytes = vec![0_u8; len];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+fn query_param(url: &Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v.into_owned())
+}
+
+// Accepts connections until the browser sends the authorization response.
+//
+// Browsers may open connections speculatively, and leave them idle, so the
+// connections are served concurrently. Browsers may also send other
+// requests, e.g. for `/favicon.ico`, these are rejected. Requests where the
+// `state` parameter does not match the authorization request are rejected
+// too, they are not a response to this login attempt.
+async fn wait_for_redirect(listener: &TcpListener, state: &str) -> Result<Url> {
+    // Any pending connections are aborted when this set is dropped.
+    let mut connections = tokio::task::JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = accepted.map_err(CredentialError::non_retryable)?;
+                connections.spawn(serve_connection(stream, state.to_string()));
+            }
+            Some(served) = connections.join_next() => {
+                if let Ok(Some(url)) = served {
+                    return Ok(url);
+                }
+            }
+        }
+    }
+}
+
+// Serves a single request, returning the URL if it is the authorization
+// response.
+async fn serve_connection(mut stream: TcpStream, state: String) -> Option<Url> {
+    let mut reader = BufReader::new(&mut stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await.ok()?;
+    // Consume the request headers, ignoring their values.
+    let mut line = String::new();
+    while reader.read_line(&mut line).await.is_ok_and(|n| n > 2) {
+        line.clear();
+    }
+    let redirect = request_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|target| Url::parse(&format!("http://127.0.0.1{target}")).ok())
+        .filter(|url| {
+            url.path() == "/" && query_param(url, "state").as_deref() == Some(state.as_str())
+        });
+    let response = match &redirect {
+        Some(_) => format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: text/html\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{SUCCESS_PAGE}",
+            SUCCESS_PAGE.len()
+        ),
+        None => "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+            .to_string(),
+    };
+    // The browser may have closed the connection, that is not an error.
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+    redirect
+}
+
+#[derive(Debug, serde::Serialize)]
+struct CodeExchangeForm<'a> {
+    grant_type: &'a str,
+    code: &'a str,
+    code_verifier: &'a str,
+    client_id: &'a str,
+    client_secret: &'a str,
+    redirect_uri: &'a str,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct CodeExchangeResponse {
+    refresh_token: Option<String>,
+}
+
+// Exchanges the authorization code, returning the refresh token.
+async fn exchange_code(
+    builder: &Builder,
+    code: &str,
+    code_verifier: &str,
+    redirect_uri: &str,
+) -> Result<String> {
+    let form = CodeExchangeForm {
+        grant_type: "authorization_code",
+        code,
+        code_verifier,
+        client_id: &builder.client_id,
+        client_secret: &builder.client_secret,
+        redirect_uri,
+    };
+    let client = http_client::client();
+    let resp = client
+        .post(&builder.token_endpoint)
+        .form(&form)
+        .send()
+        .await
+        .map_err(CredentialError::retryable)?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| CredentialError::new(is_retryable(status), e.into()))?;
+        return Err(CredentialError::new(
+            is_retryable(status),
+            Box::from(format!("Failed to exchange authorization code. {body}")),
+        ));
+    }
+    let response = resp.json::<CodeExchangeResponse>().await.map_err(|e| {
+        let retryable = !e.is_decode();
+        CredentialError::new(retryable, e.into())
+    })?;
+    response.refresh_token.ok_or_else(|| {
+        CredentialError::non_retryable(
+            "the token response has no `refresh_token`, the application must request offline access",
+        )
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::extract::{Form, State};
+    use http::StatusCode;
+    use scoped_env::ScopedEnv;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::task::JoinHandle;
+
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    // The code challenge sent in the authorization request.
+    type Challenge = Arc<Mutex<Option<String>>>;
+
+    // Starts a fake token endpoint, returning `response` if the request is
+    // valid.
+    async fn start_token_endpoint(
+        challenge: Challenge,
+        response: (StatusCode, String),
+    ) -> (String, JoinHandle<()>) {
+        let handler = move |State(challenge): State<Challenge>,
+                            Form(form): Form<HashMap<String, String>>| {
+            let response = response.clone();
+            async move {
+                let get = |k: &str| form.get(k).map(String::as_str);
+                assert_eq!(get("grant_type"), Some("authorization_code"));
+                assert_eq!(get("code"), Some("test-code"));
+                assert_eq!(get("client_id"), Some("test-client-id"));
+                assert_eq!(get("client_secret"), Some("test-client-secret"));
+                assert!(
+                    get("redirect_uri").is_some_and(|u| u.starts_with("http://127.0.0.1:")),
+                    "{form:?}"
+                );
+                let verifier = get("code_verifier").unwrap();
+                let want = challenge.lock().unwrap().clone();
+                assert_eq!(Some(code_challenge(verifier)), want);
+                response
+            }
+        };
+        let app = axum::Router::new()
+            .route("/token", axum::routing::post(handler))
+            .with_state(challenge);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (format!("http://{addr}/token"), server)
+    }
+
+    fn token_response() -> (StatusCode, String) {
+        let body = serde_json::json!({
+            "access_token": "test-access-token",
+            "expires_in": 3600,
+            "refresh_token": "test-refresh-token",
+            "scope": "https://www.googleapis.com/auth/cloud-platform",
+            "token_type": "Bearer",
+        });
+        (StatusCode::OK, body.to_string())
+    }
+
+    fn query(url: &str) -> HashMap<String, String> {
+        Url::parse(url)
+            .unwrap()
+            .query_pairs()
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect()
+    }
+
+    // Plays the role of the browser: follows the redirect to the loopback
+    // listener with the given parameters.
+    async fn redirect(params: &HashMap<String, String>, extra: &[(&str, &str)]) -> StatusCode {
+        let mut url = Url::parse(&params["redirect_uri"]).unwrap();
+        url.query_pairs_mut().extend_pairs(extra);
+        reqwest::get(url).await.unwrap().status()
+    }
+
+    async fn run(
+        response: (StatusCode, String),
+        browser: impl FnOnce(HashMap<String, String>) -> Vec<(String, String)>,
+    ) -> Result<Login> {
+        let challenge = Challenge::default();
+        let (endpoint, _server) = start_token_endpoint(challenge.clone(), response).await;
+        let pending = Builder::new("test-client-id", "test-client-secret")
+            .with_authorization_endpoint("https://accounts.example.com/auth")
+            .with_token_endpoint(endpoint)
+            .with_quota_project_id("test-project")
+            .start()
+            .await?;
+        let params = query(pending.url());
+        *challenge.lock().unwrap() = params.get("code_challenge").cloned();
+        let extra = browser(params.clone());
+        let browser = tokio::spawn(async move {
+            // Browsers request other resources, these must be ignored.
+            let mut favicon = Url::parse(&params["redirect_uri"]).unwrap();
+            favicon.set_path("/favicon.ico");
+            assert_eq!(
+                reqwest::get(favicon).await.unwrap().status(),
+                StatusCode::NOT_FOUND
+            );
+            let extra = extra
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect::<Vec<_>>();
+            assert_eq!(redirect(&params, &extra).await, StatusCode::OK);
+        });
+        let login = pending.complete().await;
+        browser.await.unwrap();
+        login
+    }
+
+    fn grant(params: HashMap<String, String>) -> Vec<(String, String)> {
+        vec![
+            ("state".to_string(), params["state"].clone()),
+            ("code".to_string(), "test-code".to_string()),
+        ]
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn authorization_url() -> TestResult {
+        let pending = Builder::new("test-client-id", "test-client-secret")
+            .with_scopes(["scope1", "scope2"])
+            .start()
+            .await?;
+        assert!(
+            pending.url().starts_with(AUTHORIZATION_ENDPOINT),
+            "{}",
+            pending.url()
+        );
+        let params = query(pending.url());
+        let get = |k: &str| params.get(k).map(String::as_str);
+        assert_eq!(get("response_type"), Some("code"));
+        assert_eq!(get("client_id"), Some("test-client-id"));
+        assert_eq!(get("scope"), Some("scope1 scope2"));
+        assert_eq!(get("code_challenge_method"), Some("S256"));
+        assert_eq!(get("access_type"), Some("offline"));
+        let redirect_uri = get("redirect_uri").unwrap();
+        assert!(
+            redirect_uri.starts_with("http://127.0.0.1:"),
+            "{redirect_uri}"
+        );
+        assert_eq!(get("code_challenge").map(str::len), Some(43));
+        assert!(get("state").is_some_and(|s| !s.is_empty()), "{params:?}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn debug_censors_secrets() -> TestResult {
+        let pending = Builder::new("test-client-id", "test-client-secret")
+            .start()
+            .await?;
+        let fmt = format!("{pending:?}");
+        assert!(fmt.contains("test-client-id"), "{fmt}");
+        assert!(!fmt.contains("test-client-secret"), "{fmt}");
+        assert!(!fmt.contains(&pending.code_verifier), "{fmt}");
+
+        let login = Login {
+            authorized_user: AuthorizedUser::new(
+                "test-client-id".to_string(),
+                "test-client-secret".to_string(),
+                "test-refresh-token".to_string(),
+                None,
+            ),
+        };
+        let fmt = format!("{login:?}");
+        assert!(!fmt.contains("test-client-secret"), "{fmt}");
+        assert!(!fmt.contains("test-refresh-token"), "{fmt}");
+        Ok(())
+    }
+
+    #[test]
+    fn pkce_challenge() {
+        // From RFC 7636, Appendix B.
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        assert_eq!(
+            code_challenge(verifier),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+        assert_eq!(random_string(32).len(), 43);
+        assert_ne!(random_string(32), random_string(32));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn login_success() -> TestResult {
+        let login = run(token_response(), grant).await?;
+        assert_eq!(
+            login.to_json(),
+            serde_json::json!({
+                "type": "authorized_user",
+                "client_id": "test-client-id",
+                "client_secret": "test-client-secret",
+                "refresh_token": "test-refresh-token",
+                "quota_project_id": "test-project",
+            })
+        );
+        let _ = login.credential()?;
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("nested/credentials.json");
+        login.save(&path).await?;
+        let contents = std::fs::read(&path)?;
+        let saved = serde_json::from_slice::<serde_json::Value>(&contents)?;
+        assert_eq!(saved, login.to_json());
+        // The saved file has the format parsed by the user credentials.
+        let parsed = serde_json::from_value::<AuthorizedUser>(saved)?;
+        assert_eq!(parsed, login.authorized_user);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path)?.permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[serial_test::serial]
+    async fn login_save_adc() -> TestResult {
+        let dir = tempfile::tempdir()?;
+        let _e = ScopedEnv::set("HOME", dir.path().to_str().unwrap());
+        let login = run(token_response(), grant).await?;
+        let path = login.save_adc().await?;
+        assert_eq!(
+            path,
+            dir.path()
+                .join(".config/gcloud/application_default_credentials.json")
+        );
+        let saved = serde_json::from_slice::<serde_json::Value>(&std::fs::read(&path)?)?;
+        assert_eq!(saved, login.to_json());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn login_denied() -> TestResult {
+        let e = run(token_response(), |params| {
+            vec![
+                ("state".to_string(), params["state"].clone()),
+                ("error".to_string(), "access_denied".to_string()),
+            ]
+        })
+        .await
+        .err()
+        .unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("access_denied"), "{e}");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn login_ignores_state_mismatch() -> TestResult {
+        let challenge = Challenge::default();
+        let (endpoint, _server) = start_token_endpoint(challenge.clone(), token_response()).await;
+        let pending = Builder::new("test-client-id", "test-client-secret")
+            .with_token_endpoint(endpoint)
+            .start()
+            .await?;
+        let params = query(pending.url());
+        *challenge.lock().unwrap() = params.get("code_challenge").cloned();
+        let browser = tokio::spawn(async move {
+            // Requests with a different `state` do not end the login, even
+            // if they report an error.
+            for (k, v) in [("code", "forged-code"), ("error", "access_denied")] {
+                let status = redirect(&params, &[("state", "forged-state"), (k, v)]).await;
+                assert_eq!(status, StatusCode::NOT_FOUND);
+            }
+            let status = redirect(&params, &[("code", "test-code")]).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            let status = redirect(
+                &params,
+                &[("state", params["state"].as_str()), ("code", "test-code")],
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+        });
+        let login = pending.complete().await?;
+        browser.await?;
+        assert_eq!(login.to_json()["refresh_token"], "test-refresh-token");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn login_with_idle_connections() -> TestResult {
+        let challenge = Challenge::default();
+        let (endpoint, _server) = start_token_endpoint(challenge.clone(), token_response()).await;
+        let pending = Builder::new("test-client-id", "test-client-secret")
+            .with_token_endpoint(endpoint)
+            .start()
+            .await?;
+        let params = query(pending.url());
+        *challenge.lock().unwrap() = params.get("code_challenge").cloned();
+        let browser = tokio::spawn(async move {
+            // Browsers may preconnect, and never send a request on these
+            // connections.
+            let url = Url::parse(&params["redirect_uri"]).unwrap();
+            let addr = format!("{}:{}", url.host_str().unwrap(), url.port().unwrap());
+            let _idle = TcpStream::connect(&addr).await.unwrap();
+            let mut partial = TcpStream::connect(&addr).await.unwrap();
+            partial.write_all(b"GET /?state=").await.unwrap();
+            let status = redirect(
+                &params,
+                &[("state", params["state"].as_str()), ("code", "test-code")],
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+        });
+        let login =
+            tokio::time::timeout(std::time::Duration::from_secs(10), pending.complete()).await??;
+        browser.await?;
+        assert_eq!(login.to_json()["refresh_token"], "test-refresh-token");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn login_no_refresh_token() -> TestResult {
+        let body = serde_json::json!({
+            "access_token": "test-access-token",
+            "expires_in": 3600,
+            "token_type": "Bearer",
+        });
+        let e = run((StatusCode::OK, body.to_string()), grant)
+            .await
+            .err()
+            .unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("refresh_token"), "{e}");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn login_token_endpoint_errors() -> TestResult {
+        let e = run(
+            (StatusCode::SERVICE_UNAVAILABLE, "try again".to_string()),
+            grant,
+        )
+        .await
+        .err()
+        .unwrap();
+        assert!(e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("try again"), "{e}");
+
+        let e = run(
+            (StatusCode::BAD_REQUEST, "invalid_grant".to_string()),
+            grant,
+        )
+        .await
+        .err()
+        .unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("invalid_grant"), "{e}");
+        Ok(())
+    }
+}
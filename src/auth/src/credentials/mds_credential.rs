@@ -12,29 +12,298 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! Credentials using the [Metadata Service].
+//!
+//! Google Cloud environments such as [Google Compute Engine (GCE)][gce-link],
+//! [Google Kubernetes Engine (GKE)][gke-link], or [Cloud Run] provide a
+//! metadata service. Applications running in these environments can fetch
+//! access tokens for the service accounts attached to the VM (or node pool,
+//! or service) from the metadata service.
+//!
+//! The location of the metadata service can be changed with the
+//! `GCE_METADATA_HOST` environment variable, e.g. `localhost:8080`. This is
+//! useful for emulators and sidecars. The `GCE_METADATA_IP` environment
+//! variable is used if `GCE_METADATA_HOST` is not set.
+//!
+//! # Example
+//! ```
+//! # use gcp_sdk_auth::credentials::mds_credential::Builder;
+//! let credential = Builder::new()
+//!     .with_service_account("my-sa@my-project.iam.gserviceaccount.com")
+//!     .with_scopes(["https://www.googleapis.com/auth/pubsub"])
+//!     .build();
+//! ```
+//!
+//! [Cloud Run]: https://cloud.google.com/run
+//! [gce-link]: https://cloud.google.com/products/compute
+//! [gke-link]: https://cloud.google.com/kubernetes-engine
+//! [Metadata Service]: https://cloud.google.com/compute/docs/metadata/overview
+
 use crate::credentials::dynamic::CredentialTrait;
-use crate::credentials::{Credential, Result};
+use crate::credentials::id_token_credential;
+use crate::credentials::signer::{sign_blob, sign_blob_url, Signer, SignerTrait};
+use crate::credentials::util::http_client;
+use crate::credentials::util::{build_headers, DEFAULT_UNIVERSE_DOMAIN, IAM_CREDENTIALS_ENDPOINT};
+use crate::credentials::{Credential, CredentialOptions, Result};
 use crate::errors::{is_retryable, CredentialError};
-use crate::token::{Token, TokenProvider};
+use crate::retry::{ExponentialBackoff, RetryPolicy, RetryTokenProvider};
+use crate::token::{metadata_keys, Token, TokenProvider};
+use crate::token_cache::TokenCache;
 use async_trait::async_trait;
-use http::header::{HeaderName, HeaderValue, AUTHORIZATION};
+use http::header::{HeaderName, HeaderValue};
 use reqwest::header::HeaderMap;
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 use time::OffsetDateTime;
+use tokio::sync::OnceCell;
 
 const METADATA_FLAVOR_VALUE: &str = "Google";
 const METADATA_FLAVOR: &str = "metadata-flavor";
 const METADATA_ROOT: &str = "http://metadata.google.internal/computeMetadata/v1";
+const GCE_METADATA_HOST_ENV_VAR: &str = "GCE_METADATA_HOST";
+const GCE_METADATA_IP_ENV_VAR: &str = "GCE_METADATA_IP";
+const DEFAULT_SERVICE_ACCOUNT: &str = "default";
+const PROJECT_ID_PATH: &str = "project/project-id";
+const UNIVERSE_DOMAIN_PATH: &str = "universe/universe-domain";
+// Off Google Cloud the metadata service may not respond at all. Application
+// Default Credentials use a short timeout to detect this case. It must be long
+// enough for a metadata service that is slow to respond, e.g. while a node
+// starts.
+pub(crate) const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Returns the root URL of the metadata service.
+fn metadata_root() -> String {
+    std::env::var(GCE_METADATA_HOST_ENV_VAR)
+        .or_else(|_| std::env::var(GCE_METADATA_IP_ENV_VAR))
+        .map(|host| format!("http://{host}/computeMetadata/v1"))
+        .unwrap_or_else(|_| METADATA_ROOT.to_string())
+}
+
+/// The result of [probe].
+#[derive(Debug)]
+pub(crate) enum Probed {
+    /// The metadata service responded, these are its credentials.
+    Available(Credential),
+    /// The metadata service cannot be reached, e.g. outside Google Cloud.
+    Unreachable(String),
+}
 
-pub(crate) fn new() -> Credential {
-    let token_provider = MDSAccessTokenProvider {
-        endpoint: METADATA_ROOT.to_string(),
+/// Probes the metadata service, and creates its credentials for Application
+/// Default Credentials.
+///
+/// The metadata service is unreachable if the connection fails, e.g. because
+/// its host name does not resolve. A metadata service that does not respond
+/// within `timeout`, or responds with an error, is an error.
+pub(crate) async fn probe(options: &CredentialOptions, timeout: Duration) -> Result<Probed> {
+    let endpoint = options.token_endpoint.clone().unwrap_or_else(metadata_root);
+    let client = http_client::client();
+    let info = MDSAccessTokenProvider::get_service_account_info(&client, endpoint.clone(), None);
+    let info = match tokio::time::timeout(timeout, info).await {
+        Ok(Ok(info)) => info,
+        Ok(Err(e)) => {
+            if let Some(e) = connect_error(&e) {
+                return Ok(Probed::Unreachable(format!(
+                    "cannot connect to the metadata service at {endpoint}: {e}"
+                )));
+            }
+            return Err(CredentialError::new(
+                e.is_retryable(),
+                Box::from(format!(
+                    "the metadata service at {endpoint} is not usable: {e}"
+                )),
+            ));
+        }
+        Err(_) => {
+            return Err(CredentialError::retryable(format!(
+                "the metadata service at {endpoint} did not respond within {timeout:?}"
+            )))
+        }
     };
-    Credential {
-        inner: Arc::new(MDSCredential { token_provider }),
+    let credential = Builder {
+        endpoint: Some(endpoint),
+        service_account: None,
+        scopes: options.scopes.clone(),
+        quota_project_id: options.quota_project_id.clone(),
+        universe_domain: options.universe_domain.clone(),
+        probe_timeout: Some(timeout),
+        service_account_info: Some(info),
+        retry_policy: options.retry_policy.clone(),
+        backoff_policy: options.backoff_policy.clone(),
+    }
+    .build();
+    Ok(Probed::Available(credential))
+}
+
+// Returns the connection error that caused `e`, if any.
+fn connect_error(e: &CredentialError) -> Option<&reqwest::Error> {
+    std::error::Error::source(e)
+        .and_then(|s| s.downcast_ref::<reqwest::Error>())
+        .filter(|e| e.is_connect())
+}
+
+/// A builder for metadata service [Credential]s.
+///
+/// By default, the credentials use the `default` service account, with the
+/// scopes of the VM (or node pool). The universe domain is fetched from the
+/// metadata service, unless it is set in the builder. The project of the VM
+/// is fetched from the metadata service, and returned by
+/// [Credential::get_project_id].
+///
+/// The credentials only send a quota project if one is configured. The
+/// project of the VM is not used as the quota project, the principal may not
+/// have permission to use it.
+#[derive(Clone, Debug, Default)]
+pub struct Builder {
+    endpoint: Option<String>,
+    service_account: Option<String>,
+    scopes: Option<Vec<String>>,
+    quota_project_id: Option<String>,
+    universe_domain: Option<String>,
+    probe_timeout: Option<Duration>,
+    // The service account info, if already fetched by [probe].
+    service_account_info: Option<ServiceAccountInfo>,
+    retry_policy: RetryPolicy,
+    backoff_policy: ExponentialBackoff,
+}
+
+impl Builder {
+    /// Creates a builder with the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the root URL of the metadata service.
+    ///
+    /// The default is `http://metadata.google.internal/computeMetadata/v1`,
+    /// or the host in the `GCE_METADATA_HOST` (or `GCE_METADATA_IP`)
+    /// environment variable.
+    pub fn with_endpoint<T: Into<String>>(mut self, v: T) -> Self {
+        self.endpoint = Some(v.into());
+        self
+    }
+
+    /// Selects the service account, by email, used to fetch access tokens.
+    ///
+    /// The service account must be attached to the VM (or node pool). The
+    /// default is the `default` service account.
+    pub fn with_service_account<T: Into<String>>(mut self, v: T) -> Self {
+        self.service_account = Some(v.into());
+        self
+    }
+
+    /// Sets the [OAuth 2.0 scopes] of the access tokens.
+    ///
+    /// The default is the scopes of the VM (or node pool).
+    ///
+    /// [OAuth 2.0 scopes]: https://developers.google.com/identity/protocols/oauth2/scopes
+    pub fn with_scopes<I, S>(mut self, v: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.scopes = Some(v.into_iter().map(|s| s.into()).collect());
+        self
+    }
+
+    /// Sets the [quota project] for requests using these credentials.
+    ///
+    /// [quota project]: https://cloud.google.com/docs/quotas/quota-project
+    pub fn with_quota_project_id<T: Into<String>>(mut self, v: T) -> Self {
+        self.quota_project_id = Some(v.into());
+        self
+    }
+
+    /// Sets the universe domain of the credentials.
+    pub fn with_universe_domain<T: Into<String>>(mut self, v: T) -> Self {
+        self.universe_domain = Some(v.into());
+        self
+    }
+
+    /// Verifies the metadata service is available before the first access
+    /// token request, waiting at most `v` for a response.
+    ///
+    /// By default, the credentials do not verify the metadata service.
+    /// Application Default Credentials wait up to 3 seconds. Failures to
+    /// connect, timeouts, and server errors are retried with the
+    /// [retry policy][Builder::with_retry_policy]. The credentials fail
+    /// without retrying if the service account is not attached to the VM (or
+    /// node pool).
+    pub fn with_probe_timeout(mut self, v: Duration) -> Self {
+        self.probe_timeout = Some(v);
+        self
+    }
+
+    /// Sets the retry policy for the requests that fetch access tokens.
+    ///
+    /// The metadata service may be briefly unavailable, e.g. while a node
+    /// starts. See [RetryPolicy] for the defaults.
+    pub fn with_retry_policy(mut self, v: RetryPolicy) -> Self {
+        self.retry_policy = v;
+        self
+    }
+
+    /// Sets the backoff policy between attempts to fetch access tokens.
+    pub fn with_backoff_policy(mut self, v: ExponentialBackoff) -> Self {
+        self.backoff_policy = v;
+        self
+    }
+
+    /// Returns a [Credential] with the configured options.
+    pub fn build(self) -> Credential {
+        let endpoint = self.endpoint.unwrap_or_else(metadata_root);
+        let service_account = self
+            .service_account
+            .unwrap_or_else(|| DEFAULT_SERVICE_ACCOUNT.to_string());
+        let token_provider = TokenCache::new(RetryTokenProvider::new(
+            MDSAccessTokenProvider {
+                endpoint: endpoint.clone(),
+                service_account: service_account.clone(),
+                scopes: self.scopes,
+                probe: self
+                    .probe_timeout
+                    .map(|timeout| Probe::new(timeout, self.service_account_info)),
+            },
+            self.retry_policy,
+            self.backoff_policy,
+        ));
+        let iam_endpoint = self
+            .universe_domain
+            .as_ref()
+            .map(|u| format!("https://iamcredentials.{u}"))
+            .unwrap_or_else(|| IAM_CREDENTIALS_ENDPOINT.to_string());
+        let signer = MDSSigner {
+            // The signer shares the token cache, but not the credentials,
+            // to avoid a reference cycle.
+            auth: Credential {
+                inner: Arc::new(MDSCredential {
+                    token_provider: token_provider.clone(),
+                    quota_project_id: None,
+                    project_id: MetadataValue::fixed(None),
+                    universe_domain: MetadataValue::fixed(None),
+                    signer: None,
+                }),
+            },
+            iam_endpoint,
+            metadata_endpoint: endpoint.clone(),
+            service_account,
+            email: OnceCell::new(),
+        };
+        let project_id = MetadataValue::fetched(endpoint.clone(), PROJECT_ID_PATH);
+        let universe_domain = match self.universe_domain {
+            Some(v) => MetadataValue::fixed(Some(v)),
+            None => MetadataValue::fetched(endpoint, UNIVERSE_DOMAIN_PATH),
+        };
+        Credential {
+            inner: Arc::new(MDSCredential {
+                token_provider,
+                quota_project_id: self.quota_project_id,
+                project_id,
+                universe_domain,
+                signer: Some(Signer::new(signer)),
+            }),
+        }
     }
 }
 
@@ -44,6 +313,10 @@ where
     T: TokenProvider,
 {
     token_provider: T,
+    quota_project_id: Option<String>,
+    project_id: MetadataValue,
+    universe_domain: MetadataValue,
+    signer: Option<Signer>,
 }
 
 #[async_trait::async_trait]
@@ -57,11 +330,139 @@ where
 
     async fn get_headers(&self) -> Result<Vec<(HeaderName, HeaderValue)>> {
         let token = self.get_token().await?;
-        let mut value = HeaderValue::from_str(&format!("{} {}", token.token_type, token.token))
-            .map_err(CredentialError::non_retryable)?;
-        value.set_sensitive(true);
-        Ok(vec![(AUTHORIZATION, value)])
+        build_headers(&token, self.quota_project_id.as_deref())
+    }
+
+    async fn get_universe_domain(&self) -> Option<String> {
+        // The metadata service in the default universe does not have a
+        // universe domain entry.
+        match self.universe_domain.get().await {
+            Ok(Some(v)) => Some(v),
+            Ok(None) => Some(DEFAULT_UNIVERSE_DOMAIN.to_string()),
+            Err(_) => None,
+        }
+    }
+
+    async fn get_project_id(&self) -> Option<String> {
+        self.project_id.get().await.ok().flatten()
+    }
+
+    fn signer(&self) -> Option<Signer> {
+        self.signer.clone()
+    }
+}
+
+/// Signs blobs with the IAM Credentials API, using the service account
+/// attached to the VM (or node pool).
+#[derive(Debug)]
+struct MDSSigner {
+    auth: Credential,
+    iam_endpoint: String,
+    metadata_endpoint: String,
+    // The email of the service account, or `default`.
+    service_account: String,
+    // The email of the service account, fetched from the metadata service
+    // if needed.
+    email: OnceCell<String>,
+}
+
+#[async_trait]
+impl SignerTrait for MDSSigner {
+    async fn client_email(&self) -> Result<String> {
+        let email = self
+            .email
+            .get_or_try_init(|| async {
+                if self.service_account != DEFAULT_SERVICE_ACCOUNT {
+                    return Ok(self.service_account.clone());
+                }
+                let info = MDSAccessTokenProvider::get_service_account_info(
+                    &http_client::client(),
+                    self.metadata_endpoint.clone(),
+                    Some(self.service_account.clone()),
+                )
+                .await?;
+                Ok::<_, CredentialError>(info.email)
+            })
+            .await?;
+        Ok(email.clone())
+    }
+
+    async fn sign(&self, content: &[u8]) -> Result<Vec<u8>> {
+        let email = self.client_email().await?;
+        let url = sign_blob_url(&self.iam_endpoint, &email);
+        sign_blob(&self.auth, &url, &[], content).await
+    }
+}
+
+/// A value either configured by the application, or fetched (once) from the
+/// metadata service.
+#[derive(Debug)]
+struct MetadataValue {
+    value: OnceCell<Option<String>>,
+    // The metadata service endpoint and the path of the value, if the value
+    // is fetched.
+    source: Option<(String, &'static str)>,
+}
+
+impl MetadataValue {
+    fn fixed(v: Option<String>) -> Self {
+        Self {
+            value: OnceCell::new_with(Some(v)),
+            source: None,
+        }
+    }
+
+    fn fetched(endpoint: String, path: &'static str) -> Self {
+        Self {
+            value: OnceCell::new(),
+            source: Some((endpoint, path)),
+        }
+    }
+
+    async fn get(&self) -> Result<Option<String>> {
+        let value = self
+            .value
+            .get_or_try_init(|| async {
+                match &self.source {
+                    None => Ok(None),
+                    Some((endpoint, path)) => fetch_value(endpoint, path).await,
+                }
+            })
+            .await?;
+        Ok(value.clone())
+    }
+}
+
+/// Fetches a value from the metadata service.
+///
+/// Returns `None` if the value does not exist.
+async fn fetch_value(endpoint: &str, path: &str) -> Result<Option<String>> {
+    let response = http_client::client()
+        .get(format!("{endpoint}/{path}"))
+        .header(
+            METADATA_FLAVOR,
+            HeaderValue::from_static(METADATA_FLAVOR_VALUE),
+        )
+        .send()
+        .await
+        .map_err(CredentialError::retryable)?;
+    let status = response.status();
+    if status == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let body = response
+        .text()
+        .await
+        .map_err(|e| CredentialError::new(is_retryable(status), e.into()))?;
+    if !status.is_success() {
+        return Err(CredentialError::new(
+            is_retryable(status),
+            Box::from(format!(
+                "Failed to fetch {path} from the metadata service. {body}"
+            )),
+        ));
     }
+    Ok(Some(body.trim().to_string()).filter(|v| !v.is_empty()))
 }
 
 #[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
@@ -82,16 +483,39 @@ struct MDSTokenResponse {
 #[derive(Debug)]
 struct MDSAccessTokenProvider {
     endpoint: String,
+    // The email of the service account, or `default`.
+    service_account: String,
+    // If set, request access tokens with these scopes instead of the scopes
+    // of the VM (or node pool).
+    scopes: Option<Vec<String>>,
+    // If set, verify the metadata service is available, with a short
+    // timeout, before the first token request. The service account info
+    // returned by the probe is used to populate the token metadata.
+    probe: Option<Probe>,
+}
+
+#[derive(Debug)]
+struct Probe {
+    timeout: Duration,
+    info: OnceCell<ServiceAccountInfo>,
+}
+
+impl Probe {
+    fn new(timeout: Duration, info: Option<ServiceAccountInfo>) -> Self {
+        Self {
+            timeout,
+            info: OnceCell::new_with(info),
+        }
+    }
 }
 
 impl MDSAccessTokenProvider {
-    #[allow(dead_code)]
     async fn get_service_account_info(
         request: &Client,
         metadata_service_endpoint: String,
         email: Option<String>,
     ) -> Result<ServiceAccountInfo> {
-        let email: String = email.unwrap_or("default".to_string());
+        let email: String = email.unwrap_or(DEFAULT_SERVICE_ACCOUNT.to_string());
         let path: String = format!(
             "{}/instance/service-accounts/{}/",
             metadata_service_endpoint, email
@@ -112,28 +536,85 @@ impl MDSAccessTokenProvider {
             .headers(headers)
             .send()
             .await
-            .map_err(CredentialError::non_retryable)?;
+            .map_err(CredentialError::retryable)?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response
+                .text()
+                .await
+                .map_err(|e| CredentialError::new(is_retryable(status), e.into()))?;
+            return Err(CredentialError::new(
+                is_retryable(status),
+                Box::from(format!(
+                    "Failed to fetch service account {email} info. {body}"
+                )),
+            ));
+        }
 
         response
             .json::<ServiceAccountInfo>()
             .await
             .map_err(CredentialError::non_retryable)
     }
+
+    // Verifies the metadata service is available, and that the service
+    // account is attached to the VM (or node pool).
+    //
+    // The metadata service may be slow, or briefly unavailable, e.g. while a
+    // node starts. Only errors that will not go away, such as an unknown
+    // service account, are not retryable.
+    async fn probe(&self, timeout: Duration) -> Result<ServiceAccountInfo> {
+        let client = http_client::client();
+        let info = Self::get_service_account_info(
+            &client,
+            self.endpoint.clone(),
+            Some(self.service_account.clone()),
+        );
+        match tokio::time::timeout(timeout, info).await {
+            Ok(Ok(info)) => Ok(info),
+            Ok(Err(e)) => Err(CredentialError::new(
+                e.is_retryable(),
+                Box::from(format!(
+                    "The metadata service at {} is not usable: {e}",
+                    self.endpoint
+                )),
+            )),
+            Err(_) => Err(CredentialError::retryable(format!(
+                "The metadata service at {} did not respond within {timeout:?}",
+                self.endpoint
+            ))),
+        }
+    }
 }
 
 #[async_trait]
 impl TokenProvider for MDSAccessTokenProvider {
     async fn get_token(&self) -> Result<Token> {
-        let client = Client::new();
-        let request = client
+        let info = match &self.probe {
+            // Only successful probes are cached, failures are quick to
+            // detect again.
+            Some(probe) => Some(
+                probe
+                    .info
+                    .get_or_try_init(|| self.probe(probe.timeout))
+                    .await?,
+            ),
+            None => None,
+        };
+        let client = http_client::client();
+        let mut request = client
             .get(format!(
-                "{}/instance/service-accounts/default/token",
-                self.endpoint
+                "{}/instance/service-accounts/{}/token",
+                self.endpoint, self.service_account
             ))
             .header(
                 METADATA_FLAVOR,
                 HeaderValue::from_static(METADATA_FLAVOR_VALUE),
             );
+        if let Some(scopes) = &self.scopes {
+            request = request.query(&[("scopes", scopes.join(","))]);
+        }
 
         let response = request.send().await.map_err(CredentialError::retryable)?;
         // Process the response
@@ -160,16 +641,84 @@ impl TokenProvider for MDSAccessTokenProvider {
                 .map(|d| OffsetDateTime::now_utc() + Duration::from_secs(d)),
             metadata: None,
         };
-        Ok(token)
+        // The metadata service does not report the scopes of the token. Use
+        // the requested scopes, or the scopes of the VM if we have them.
+        let scopes = self
+            .scopes
+            .as_ref()
+            .or_else(|| info.and_then(|i| i.scopes.as_ref()))
+            .map(|s| s.join(" "));
+        let email = info.map(|i| i.email.clone()).or_else(|| {
+            (self.service_account != DEFAULT_SERVICE_ACCOUNT).then(|| self.service_account.clone())
+        });
+        Ok(token
+            .with_metadata(metadata_keys::SCOPES, scopes)
+            .with_metadata(metadata_keys::SOURCE_TYPE, Some("compute_metadata"))
+            .with_metadata(metadata_keys::PRINCIPAL_EMAIL, email))
+    }
+}
+
+/// Creates a provider for ID tokens with the given `audience`.
+pub(crate) fn id_token_provider(audience: String, include_email: bool) -> MDSIdTokenProvider {
+    MDSIdTokenProvider {
+        endpoint: metadata_root(),
+        audience,
+        include_email,
+    }
+}
+
+/// Fetches ID tokens from the metadata service `identity` endpoint.
+#[derive(Debug)]
+pub(crate) struct MDSIdTokenProvider {
+    endpoint: String,
+    audience: String,
+    include_email: bool,
+}
+
+#[async_trait]
+impl TokenProvider for MDSIdTokenProvider {
+    async fn get_token(&self) -> Result<Token> {
+        let mut params = vec![("audience", self.audience.as_str())];
+        if self.include_email {
+            params.push(("format", "full"));
+        }
+        let client = http_client::client();
+        let request = client
+            .get(format!(
+                "{}/instance/service-accounts/default/identity",
+                self.endpoint
+            ))
+            .query(&params)
+            .header(
+                METADATA_FLAVOR,
+                HeaderValue::from_static(METADATA_FLAVOR_VALUE),
+            );
+
+        let response = request.send().await.map_err(CredentialError::retryable)?;
+        // Process the response
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| CredentialError::new(is_retryable(status), e.into()))?;
+        if !status.is_success() {
+            return Err(CredentialError::new(
+                is_retryable(status),
+                Box::from(format!("Failed to fetch token. {body}")),
+            ));
+        }
+        id_token_credential::token_from_jwt(body)
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::credentials::QUOTA_PROJECT_KEY;
     use crate::token::test::MockTokenProvider;
     use axum::response::IntoResponse;
-    use reqwest::StatusCode;
+    use http::header::AUTHORIZATION;
+    use scoped_env::ScopedEnv;
     use serde_json::Value;
     use std::error::Error;
     use tokio::task::JoinHandle;
@@ -193,6 +742,10 @@ mod test {
 
         let mdsc = MDSCredential {
             token_provider: mock,
+            quota_project_id: None,
+            project_id: MetadataValue::fixed(None),
+            universe_domain: MetadataValue::fixed(None),
+            signer: None,
         };
         let actual = mdsc.get_token().await.unwrap();
         assert_eq!(actual, expected);
@@ -207,6 +760,10 @@ mod test {
 
         let mdsc = MDSCredential {
             token_provider: mock,
+            quota_project_id: None,
+            project_id: MetadataValue::fixed(None),
+            universe_domain: MetadataValue::fixed(None),
+            signer: None,
         };
         assert!(mdsc.get_token().await.is_err());
     }
@@ -232,6 +789,10 @@ mod test {
 
         let mdsc = MDSCredential {
             token_provider: mock,
+            quota_project_id: None,
+            project_id: MetadataValue::fixed(None),
+            universe_domain: MetadataValue::fixed(None),
+            signer: None,
         };
         let headers: Vec<HV> = mdsc
             .get_headers()
@@ -264,6 +825,10 @@ mod test {
 
         let mdsc = MDSCredential {
             token_provider: mock,
+            quota_project_id: None,
+            project_id: MetadataValue::fixed(None),
+            universe_domain: MetadataValue::fixed(None),
+            signer: None,
         };
         assert!(mdsc.get_headers().await.is_err());
     }
@@ -369,8 +934,19 @@ mod test {
         let (endpoint, _server) = start(StatusCode::OK, response_body, path.to_string()).await;
         println!("endpoint = {endpoint}");
 
-        let tp = MDSAccessTokenProvider { endpoint: endpoint };
-        let mdsc = MDSCredential { token_provider: tp };
+        let tp = MDSAccessTokenProvider {
+            endpoint,
+            service_account: DEFAULT_SERVICE_ACCOUNT.to_string(),
+            scopes: None,
+            probe: None,
+        };
+        let mdsc = MDSCredential {
+            token_provider: tp,
+            quota_project_id: None,
+            project_id: MetadataValue::fixed(None),
+            universe_domain: MetadataValue::fixed(None),
+            signer: None,
+        };
         let now = OffsetDateTime::now_utc();
         let token = mdsc.get_token().await?;
         assert_eq!(token.token, "test-access-token");
@@ -378,6 +954,50 @@ mod test {
         assert!(token
             .expires_at
             .is_some_and(|d| d >= now + Duration::from_secs(3600)));
+        assert_eq!(token.source_type(), Some("compute_metadata"));
+        assert_eq!(token.scopes(), None);
+        assert_eq!(token.principal_email(), None);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn token_metadata_from_probe() -> TestResult {
+        let response = MDSTokenResponse {
+            access_token: "test-access-token".to_string(),
+            expires_in: Some(3600),
+            token_type: "Bearer".to_string(),
+        };
+        let info = ServiceAccountInfo {
+            email: "test@test.com".to_string(),
+            scopes: Some(vec!["scope1".to_string(), "scope2".to_string()]),
+            aliases: None,
+        };
+        let app = axum::Router::new()
+            .route(
+                "/instance/service-accounts/default/token",
+                axum::routing::get(|| async move { axum::Json(response) }),
+            )
+            .route(
+                "/instance/service-accounts/default/",
+                axum::routing::get(|| async move { axum::Json(info) }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let _server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let tp = MDSAccessTokenProvider {
+            endpoint: format!("http://{addr}"),
+            service_account: DEFAULT_SERVICE_ACCOUNT.to_string(),
+            scopes: None,
+            probe: Some(Probe::new(DEFAULT_PROBE_TIMEOUT, None)),
+        };
+        let token = tp.get_token().await?;
+        assert_eq!(token.source_type(), Some("compute_metadata"));
+        assert_eq!(token.scopes(), Some(vec!["scope1", "scope2"]));
+        assert_eq!(token.principal_email(), Some("test@test.com"));
 
         Ok(())
     }
@@ -394,8 +1014,19 @@ mod test {
         let (endpoint, _server) = start(StatusCode::OK, response_body, path.to_string()).await;
         println!("endpoint = {endpoint}");
 
-        let tp = MDSAccessTokenProvider { endpoint: endpoint };
-        let mdsc = MDSCredential { token_provider: tp };
+        let tp = MDSAccessTokenProvider {
+            endpoint,
+            service_account: DEFAULT_SERVICE_ACCOUNT.to_string(),
+            scopes: None,
+            probe: None,
+        };
+        let mdsc = MDSCredential {
+            token_provider: tp,
+            quota_project_id: None,
+            project_id: MetadataValue::fixed(None),
+            universe_domain: MetadataValue::fixed(None),
+            signer: None,
+        };
         let token = mdsc.get_token().await?;
         assert_eq!(token.token, "test-access-token");
         assert_eq!(token.token_type, "test-token-type");
@@ -414,8 +1045,19 @@ mod test {
         )
         .await;
 
-        let tp = MDSAccessTokenProvider { endpoint: endpoint };
-        let mdsc = MDSCredential { token_provider: tp };
+        let tp = MDSAccessTokenProvider {
+            endpoint,
+            service_account: DEFAULT_SERVICE_ACCOUNT.to_string(),
+            scopes: None,
+            probe: None,
+        };
+        let mdsc = MDSCredential {
+            token_provider: tp,
+            quota_project_id: None,
+            project_id: MetadataValue::fixed(None),
+            universe_domain: MetadataValue::fixed(None),
+            signer: None,
+        };
         let e = mdsc.get_token().await.err().unwrap();
         assert!(e.is_retryable());
         assert!(e.source().unwrap().to_string().contains("try again"));
@@ -433,8 +1075,19 @@ mod test {
         )
         .await;
 
-        let tp = MDSAccessTokenProvider { endpoint: endpoint };
-        let mdsc = MDSCredential { token_provider: tp };
+        let tp = MDSAccessTokenProvider {
+            endpoint,
+            service_account: DEFAULT_SERVICE_ACCOUNT.to_string(),
+            scopes: None,
+            probe: None,
+        };
+        let mdsc = MDSCredential {
+            token_provider: tp,
+            quota_project_id: None,
+            project_id: MetadataValue::fixed(None),
+            universe_domain: MetadataValue::fixed(None),
+            signer: None,
+        };
         let e = mdsc.get_token().await.err().unwrap();
         assert!(!e.is_retryable());
         assert!(e.source().unwrap().to_string().contains("epic fail"));
@@ -452,11 +1105,730 @@ mod test {
         )
         .await;
 
-        let tp = MDSAccessTokenProvider { endpoint: endpoint };
-        let mdsc = MDSCredential { token_provider: tp };
+        let tp = MDSAccessTokenProvider {
+            endpoint,
+            service_account: DEFAULT_SERVICE_ACCOUNT.to_string(),
+            scopes: None,
+            probe: None,
+        };
+        let mdsc = MDSCredential {
+            token_provider: tp,
+            quota_project_id: None,
+            project_id: MetadataValue::fixed(None),
+            universe_domain: MetadataValue::fixed(None),
+            signer: None,
+        };
         let e = mdsc.get_token().await.err().unwrap();
         assert!(!e.is_retryable());
 
         Ok(())
     }
+
+    // Starts a server returning ID tokens. Returns an (endpoint, server) pair.
+    async fn start_identity(
+        response_code: StatusCode,
+        response_body: String,
+        expected_format: Option<&'static str>,
+    ) -> (String, JoinHandle<()>) {
+        let handler =
+            move |axum::extract::Query(query): axum::extract::Query<HashMap<String, String>>,
+                  headers: axum::http::HeaderMap| async move {
+                assert_eq!(
+                    headers.get(METADATA_FLAVOR).map(|v| v.to_str().unwrap()),
+                    Some(METADATA_FLAVOR_VALUE)
+                );
+                assert_eq!(
+                    query.get("audience").map(String::as_str),
+                    Some("https://example.com")
+                );
+                assert_eq!(query.get("format").map(String::as_str), expected_format);
+                (response_code, response_body)
+            };
+        let app = axum::Router::new().route(
+            "/instance/service-accounts/default/identity",
+            axum::routing::get(handler),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        (format!("http://{}:{}", addr.ip(), addr.port()), server)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn id_token_provider_success() -> TestResult {
+        let exp =
+            OffsetDateTime::from_unix_timestamp(OffsetDateTime::now_utc().unix_timestamp() + 3600)?;
+        let id_token = id_token_credential::test::fake_id_token(exp, "https://example.com");
+        for (include_email, format) in [(false, None), (true, Some("full"))] {
+            let (endpoint, _server) =
+                start_identity(StatusCode::OK, id_token.clone(), format).await;
+            let provider = MDSIdTokenProvider {
+                endpoint,
+                ..id_token_provider("https://example.com".to_string(), include_email)
+            };
+            let token = provider.get_token().await?;
+            assert_eq!(token.token, id_token);
+            assert_eq!(token.expires_at, Some(exp));
+        }
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn id_token_provider_errors() -> TestResult {
+        let (endpoint, _server) = start_identity(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "try again".to_string(),
+            None,
+        )
+        .await;
+        let provider = MDSIdTokenProvider {
+            endpoint,
+            ..id_token_provider("https://example.com".to_string(), false)
+        };
+        let e = provider.get_token().await.err().unwrap();
+        assert!(e.is_retryable(), "{e}");
+
+        let (endpoint, _server) =
+            start_identity(StatusCode::OK, "not-a-jwt".to_string(), None).await;
+        let provider = MDSIdTokenProvider {
+            endpoint,
+            ..id_token_provider("https://example.com".to_string(), false)
+        };
+        let e = provider.get_token().await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn probe_with_options() -> TestResult {
+        let handler = |axum::extract::Query(query): axum::extract::Query<
+            HashMap<String, String>,
+        >| async move {
+            assert_eq!(
+                query.get("scopes").map(String::as_str),
+                Some("scope1,scope2")
+            );
+            let response = MDSTokenResponse {
+                access_token: "test-access-token".to_string(),
+                expires_in: Some(3600),
+                token_type: "Bearer".to_string(),
+            };
+            (StatusCode::OK, serde_json::to_string(&response).unwrap())
+        };
+        let info = ServiceAccountInfo {
+            email: "test@test.com".to_string(),
+            scopes: None,
+            aliases: None,
+        };
+        let app = axum::Router::new()
+            .route(
+                "/instance/service-accounts/default/token",
+                axum::routing::get(handler),
+            )
+            .route(
+                "/instance/service-accounts/default/",
+                axum::routing::get(|| async move { axum::Json(info) }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let _server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let options = CredentialOptions {
+            scopes: Some(vec!["scope1".to_string(), "scope2".to_string()]),
+            quota_project_id: Some("test-project".to_string()),
+            universe_domain: Some("test-universe-domain".to_string()),
+            token_endpoint: Some(format!("http://{addr}")),
+            ..Default::default()
+        };
+        let creds = match probe(&options, DEFAULT_PROBE_TIMEOUT).await? {
+            Probed::Available(c) => c,
+            Probed::Unreachable(reason) => panic!("unexpected unreachable: {reason}"),
+        };
+        let token = creds.get_token().await?;
+        assert_eq!(token.scopes(), Some(vec!["scope1", "scope2"]));
+        assert_eq!(token.principal_email(), Some("test@test.com"));
+        let headers = creds.get_headers().await?;
+        assert!(headers
+            .iter()
+            .any(|(k, v)| k == AUTHORIZATION && v == "Bearer test-access-token"));
+        assert!(headers
+            .iter()
+            .any(|(k, v)| k == QUOTA_PROJECT_KEY && v == "test-project"));
+        assert_eq!(
+            creds.get_universe_domain().await.as_deref(),
+            Some("test-universe-domain")
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn probe_available() -> TestResult {
+        let info_count = Arc::new(std::sync::Mutex::new(0_usize));
+        let c = info_count.clone();
+        let info = move || async move {
+            *c.lock().unwrap() += 1;
+            axum::Json(ServiceAccountInfo {
+                email: "test@test.com".to_string(),
+                scopes: Some(vec!["scope1".to_string()]),
+                aliases: None,
+            })
+        };
+        let token = || async {
+            axum::Json(MDSTokenResponse {
+                access_token: "test-access-token".to_string(),
+                expires_in: Some(3600),
+                token_type: "Bearer".to_string(),
+            })
+        };
+        let app = axum::Router::new()
+            .route(
+                "/instance/service-accounts/default/",
+                axum::routing::get(info),
+            )
+            .route(
+                "/instance/service-accounts/default/token",
+                axum::routing::get(token),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let _server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let options = CredentialOptions {
+            token_endpoint: Some(format!("http://{addr}")),
+            quota_project_id: Some("test-project".to_string()),
+            ..Default::default()
+        };
+        let creds = match probe(&options, DEFAULT_PROBE_TIMEOUT).await? {
+            Probed::Available(c) => c,
+            Probed::Unreachable(reason) => panic!("unexpected unreachable: {reason}"),
+        };
+        let fmt = format!("{creds:?}");
+        assert!(fmt.contains("probe: Some"), "{fmt}");
+        assert!(fmt.contains(&format!("{DEFAULT_PROBE_TIMEOUT:?}")), "{fmt}");
+        assert!(fmt.contains("test-project"), "{fmt}");
+        let token = creds.get_token().await?;
+        assert_eq!(token.token, "test-access-token");
+        assert_eq!(token.principal_email(), Some("test@test.com"));
+        assert_eq!(token.scopes(), Some(vec!["scope1"]));
+        // The credentials use the service account info from the probe.
+        assert_eq!(*info_count.lock().unwrap(), 1);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn probe_unreachable() -> TestResult {
+        // Nothing listens on the port once the listener is dropped.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        drop(listener);
+        let options = CredentialOptions {
+            token_endpoint: Some(format!("http://{addr}")),
+            ..Default::default()
+        };
+        match probe(&options, DEFAULT_PROBE_TIMEOUT).await? {
+            Probed::Unreachable(reason) => {
+                assert!(reason.contains("cannot connect"), "{reason}");
+                assert!(reason.contains(&addr.to_string()), "{reason}");
+            }
+            Probed::Available(c) => panic!("unexpected credentials: {c:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn probe_errors() -> TestResult {
+        let (endpoint, _server) = start(
+            StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::to_value("try again")?,
+            "/instance/service-accounts/default/".to_string(),
+        )
+        .await;
+        let options = CredentialOptions {
+            token_endpoint: Some(endpoint),
+            ..Default::default()
+        };
+        let e = probe(&options, DEFAULT_PROBE_TIMEOUT).await.err().unwrap();
+        assert!(e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("not usable"), "{e}");
+
+        // The fake service does not have any service accounts.
+        let (endpoint, _server) = start(
+            StatusCode::NOT_FOUND,
+            serde_json::to_value("not found")?,
+            "/unused".to_string(),
+        )
+        .await;
+        let options = CredentialOptions {
+            token_endpoint: Some(endpoint),
+            ..Default::default()
+        };
+        let e = probe(&options, DEFAULT_PROBE_TIMEOUT).await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("not usable"), "{e}");
+
+        // The listener accepts connections, but never responds.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let options = CredentialOptions {
+            token_endpoint: Some(format!("http://{addr}")),
+            ..Default::default()
+        };
+        let e = probe(&options, Duration::from_millis(200))
+            .await
+            .err()
+            .unwrap();
+        assert!(e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("did not respond"), "{e}");
+        Ok(())
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn metadata_root_from_env() {
+        let _e1 = ScopedEnv::remove(GCE_METADATA_HOST_ENV_VAR);
+        let _e2 = ScopedEnv::remove(GCE_METADATA_IP_ENV_VAR);
+        assert_eq!(metadata_root(), METADATA_ROOT);
+
+        let _e2 = ScopedEnv::set(GCE_METADATA_IP_ENV_VAR, "169.254.169.254");
+        assert_eq!(metadata_root(), "http://169.254.169.254/computeMetadata/v1");
+
+        let _e1 = ScopedEnv::set(GCE_METADATA_HOST_ENV_VAR, "localhost:8080");
+        assert_eq!(metadata_root(), "http://localhost:8080/computeMetadata/v1");
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn builder_uses_env() {
+        let _e1 = ScopedEnv::set(GCE_METADATA_HOST_ENV_VAR, "localhost:8080");
+        let creds = Builder::new().build();
+        let fmt = format!("{creds:?}");
+        assert!(
+            fmt.contains("http://localhost:8080/computeMetadata/v1"),
+            "{fmt}"
+        );
+        assert!(fmt.contains("probe: None"), "{fmt}");
+    }
+
+    type Counters = Arc<std::sync::Mutex<HashMap<String, usize>>>;
+
+    // Starts a fake metadata service for the `test@test.com` service account.
+    // The `project_id` and `universe_domain` entries exist only if set.
+    async fn start_metadata(
+        project_id: Option<&'static str>,
+        universe_domain: Option<&'static str>,
+    ) -> (String, Counters, JoinHandle<()>) {
+        let counters = Counters::default();
+        let count = |c: &Counters, path: &str| {
+            *c.lock().unwrap().entry(path.to_string()).or_default() += 1;
+        };
+        let value = |c: Counters, path: &'static str, v: Option<&'static str>| {
+            move || async move {
+                count(&c, path);
+                match v {
+                    Some(v) => (StatusCode::OK, v.to_string()),
+                    None => (StatusCode::NOT_FOUND, "not found".to_string()),
+                }
+            }
+        };
+        let c = counters.clone();
+        let token = move |axum::extract::Query(query): axum::extract::Query<
+            HashMap<String, String>,
+        >| async move {
+            count(&c, "token");
+            let response = MDSTokenResponse {
+                access_token: format!(
+                    "token-scopes-{}",
+                    query.get("scopes").map(String::as_str).unwrap_or("none")
+                ),
+                expires_in: Some(3600),
+                token_type: "Bearer".to_string(),
+            };
+            axum::Json(response)
+        };
+        let info = || async {
+            axum::Json(ServiceAccountInfo {
+                email: "test@test.com".to_string(),
+                scopes: None,
+                aliases: None,
+            })
+        };
+        let app = axum::Router::new()
+            .route(
+                "/instance/service-accounts/test@test.com/token",
+                axum::routing::get(token),
+            )
+            .route(
+                "/instance/service-accounts/default/",
+                axum::routing::get(info),
+            )
+            .route(
+                "/project/project-id",
+                axum::routing::get(value(counters.clone(), "project-id", project_id)),
+            )
+            .route(
+                "/universe/universe-domain",
+                axum::routing::get(value(counters.clone(), "universe-domain", universe_domain)),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (format!("http://{addr}"), counters, server)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn builder_service_account_and_metadata_values() -> TestResult {
+        let (endpoint, counters, _server) =
+            start_metadata(Some("test-project"), Some("test-universe-domain")).await;
+        let creds = Builder::new()
+            .with_endpoint(endpoint)
+            .with_service_account("test@test.com")
+            .with_scopes(["scope1", "scope2"])
+            .build();
+        for _ in 0..2 {
+            let headers = creds.get_headers().await?;
+            assert!(headers
+                .iter()
+                .any(|(k, v)| k == AUTHORIZATION && v == "Bearer token-scopes-scope1,scope2"));
+            // The project of the VM is not used as the quota project.
+            assert!(!headers.iter().any(|(k, _)| k == QUOTA_PROJECT_KEY));
+            assert_eq!(
+                creds.get_universe_domain().await.as_deref(),
+                Some("test-universe-domain")
+            );
+            assert_eq!(
+                creds.get_project_id().await.as_deref(),
+                Some("test-project")
+            );
+        }
+        // The values are fetched once.
+        let counters = counters.lock().unwrap().clone();
+        assert_eq!(
+            counters,
+            HashMap::from([
+                ("token".to_string(), 1),
+                ("project-id".to_string(), 1),
+                ("universe-domain".to_string(), 1),
+            ])
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn metadata_errors_do_not_fail_headers() -> TestResult {
+        let response = MDSTokenResponse {
+            access_token: "test-access-token".to_string(),
+            expires_in: Some(3600),
+            token_type: "Bearer".to_string(),
+        };
+        // All the metadata paths, except the token, return errors.
+        let app = axum::Router::new()
+            .route(
+                "/instance/service-accounts/default/token",
+                axum::routing::get(|| async move { axum::Json(response) }),
+            )
+            .fallback(|| async { (StatusCode::INTERNAL_SERVER_ERROR, "uh-oh") });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let _server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let creds = Builder::new()
+            .with_endpoint(format!("http://{addr}"))
+            .build();
+        let headers = creds.get_headers().await?;
+        assert_eq!(headers.len(), 1, "{headers:?}");
+        assert_eq!(headers[0].0, AUTHORIZATION);
+        assert_eq!(headers[0].1, "Bearer test-access-token");
+        assert_eq!(creds.get_universe_domain().await, None);
+        assert_eq!(creds.get_project_id().await, None);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn builder_metadata_values_not_found() -> TestResult {
+        let (endpoint, _counters, _server) = start_metadata(None, None).await;
+        let creds = Builder::new()
+            .with_endpoint(endpoint)
+            .with_service_account("test@test.com")
+            .build();
+        let headers = creds.get_headers().await?;
+        assert!(headers
+            .iter()
+            .any(|(k, v)| k == AUTHORIZATION && v == "Bearer token-scopes-none"));
+        assert!(!headers.iter().any(|(k, _)| k == QUOTA_PROJECT_KEY));
+        assert_eq!(
+            creds.get_universe_domain().await.as_deref(),
+            Some(DEFAULT_UNIVERSE_DOMAIN)
+        );
+        assert_eq!(creds.get_project_id().await, None);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn builder_overrides_metadata_values() -> TestResult {
+        let (endpoint, counters, _server) =
+            start_metadata(Some("test-project"), Some("test-universe-domain")).await;
+        let creds = Builder::new()
+            .with_endpoint(endpoint)
+            .with_service_account("test@test.com")
+            .with_quota_project_id("override-project")
+            .with_universe_domain("override-universe-domain")
+            .build();
+        let headers = creds.get_headers().await?;
+        assert!(headers
+            .iter()
+            .any(|(k, v)| k == QUOTA_PROJECT_KEY && v == "override-project"));
+        assert_eq!(
+            creds.get_universe_domain().await.as_deref(),
+            Some("override-universe-domain")
+        );
+        let counters = counters.lock().unwrap().clone();
+        assert_eq!(counters, HashMap::from([("token".to_string(), 1)]));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn probe_unknown_service_account() -> TestResult {
+        // The fake service does not have any service accounts.
+        let (endpoint, _server) = start(
+            StatusCode::NOT_FOUND,
+            serde_json::to_value("not found")?,
+            "/unused".to_string(),
+        )
+        .await;
+        let tp = MDSAccessTokenProvider {
+            endpoint,
+            service_account: DEFAULT_SERVICE_ACCOUNT.to_string(),
+            scopes: None,
+            probe: Some(Probe::new(DEFAULT_PROBE_TIMEOUT, None)),
+        };
+        let e = tp.get_token().await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("not usable"), "{e}");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn probe_timeout() -> TestResult {
+        // The listener accepts connections, but never responds.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let timeout = Duration::from_millis(200);
+        let tp = MDSAccessTokenProvider {
+            endpoint: format!("http://{addr}"),
+            service_account: DEFAULT_SERVICE_ACCOUNT.to_string(),
+            scopes: None,
+            probe: Some(Probe::new(timeout, None)),
+        };
+        let start = std::time::Instant::now();
+        let e = tp.get_token().await.err().unwrap();
+        assert!(start.elapsed() < timeout * 10, "{:?}", start.elapsed());
+        assert!(e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("did not respond"), "{e}");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn probe_server_error() -> TestResult {
+        let (endpoint, _server) = start(
+            StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::to_value("try again")?,
+            "/instance/service-accounts/default/".to_string(),
+        )
+        .await;
+        let tp = MDSAccessTokenProvider {
+            endpoint,
+            service_account: DEFAULT_SERVICE_ACCOUNT.to_string(),
+            scopes: None,
+            probe: Some(Probe::new(DEFAULT_PROBE_TIMEOUT, None)),
+        };
+        let e = tp.get_token().await.err().unwrap();
+        assert!(e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("not usable"), "{e}");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn probe_connection_refused() -> TestResult {
+        // Nothing listens on the port once the listener is dropped.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        drop(listener);
+        let tp = MDSAccessTokenProvider {
+            endpoint: format!("http://{addr}"),
+            service_account: DEFAULT_SERVICE_ACCOUNT.to_string(),
+            scopes: None,
+            probe: Some(Probe::new(DEFAULT_PROBE_TIMEOUT, None)),
+        };
+        let e = tp.get_token().await.err().unwrap();
+        assert!(e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("not usable"), "{e}");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn signer_default_service_account() -> TestResult {
+        let (endpoint, _counters, _server) = start_metadata(None, None).await;
+        let (iam_endpoint, _iam) =
+            crate::credentials::signer::test::start_iam("test@test.com").await;
+        let creds = Builder::new()
+            .with_endpoint(&endpoint)
+            .with_service_account("test@test.com")
+            .build();
+        let signer = MDSSigner {
+            auth: creds,
+            iam_endpoint,
+            metadata_endpoint: endpoint,
+            service_account: DEFAULT_SERVICE_ACCOUNT.to_string(),
+            email: OnceCell::new(),
+        };
+        assert_eq!(signer.client_email().await?, "test@test.com");
+        assert_eq!(signer.sign(b"abc").await?, b"cba");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn signer_configured_service_account() -> TestResult {
+        let creds = Builder::new()
+            .with_endpoint("http://127.0.0.1:1")
+            .with_service_account("test@test.com")
+            .build();
+        // The email is known, no need to contact the metadata service.
+        let signer = creds.signer()?;
+        assert_eq!(signer.client_email().await?, "test@test.com");
+        Ok(())
+    }
+
+    // Starts a metadata service that fails the first `failures` token
+    // requests with `503 Service Unavailable`.
+    async fn start_flaky_metadata(failures: usize) -> (String, Counters, JoinHandle<()>) {
+        let counters = Counters::default();
+        let c = counters.clone();
+        let token = move || async move {
+            let count = {
+                let mut guard = c.lock().unwrap();
+                let count = guard.entry("token".to_string()).or_default();
+                *count += 1;
+                *count
+            };
+            if count <= failures {
+                return (StatusCode::SERVICE_UNAVAILABLE, "try again".to_string());
+            }
+            let response = MDSTokenResponse {
+                access_token: "test-access-token".to_string(),
+                expires_in: Some(3600),
+                token_type: "Bearer".to_string(),
+            };
+            (StatusCode::OK, serde_json::to_string(&response).unwrap())
+        };
+        let app = axum::Router::new().route(
+            "/instance/service-accounts/test@test.com/token",
+            axum::routing::get(token),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (format!("http://{addr}"), counters, server)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn token_retries_transient_errors() -> TestResult {
+        let (endpoint, counters, _server) = start_flaky_metadata(2).await;
+        let creds = Builder::new()
+            .with_endpoint(endpoint)
+            .with_service_account("test@test.com")
+            .with_backoff_policy(
+                ExponentialBackoff::new().with_initial_delay(Duration::from_millis(1)),
+            )
+            .build();
+        let token = creds.get_token().await?;
+        assert_eq!(token.token, "test-access-token");
+        assert_eq!(counters.lock().unwrap().get("token"), Some(&3));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn token_retry_policy_exhausted() -> TestResult {
+        let (endpoint, counters, _server) = start_flaky_metadata(usize::MAX).await;
+        let creds = Builder::new()
+            .with_endpoint(endpoint)
+            .with_service_account("test@test.com")
+            .with_retry_policy(RetryPolicy::new().with_maximum_attempts(3))
+            .with_backoff_policy(
+                ExponentialBackoff::new().with_initial_delay(Duration::from_millis(1)),
+            )
+            .build();
+        let e = creds.get_token().await.err().unwrap();
+        assert!(e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("try again"), "{e}");
+        assert_eq!(counters.lock().unwrap().get("token"), Some(&3));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn probe_retries_transient_errors() -> TestResult {
+        let attempts = Arc::new(std::sync::Mutex::new(0_usize));
+        let a = attempts.clone();
+        let info = move || async move {
+            let count = {
+                let mut guard = a.lock().unwrap();
+                *guard += 1;
+                *guard
+            };
+            if count <= 2 {
+                return (StatusCode::SERVICE_UNAVAILABLE, "try again".to_string());
+            }
+            let info = ServiceAccountInfo {
+                email: "test@test.com".to_string(),
+                scopes: None,
+                aliases: None,
+            };
+            (StatusCode::OK, serde_json::to_string(&info).unwrap())
+        };
+        let token = || async {
+            axum::Json(MDSTokenResponse {
+                access_token: "test-access-token".to_string(),
+                expires_in: Some(3600),
+                token_type: "Bearer".to_string(),
+            })
+        };
+        let app = axum::Router::new()
+            .route(
+                "/instance/service-accounts/default/",
+                axum::routing::get(info),
+            )
+            .route(
+                "/instance/service-accounts/default/token",
+                axum::routing::get(token),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let _server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let creds = Builder::new()
+            .with_endpoint(format!("http://{addr}"))
+            .with_probe_timeout(DEFAULT_PROBE_TIMEOUT)
+            .with_backoff_policy(
+                ExponentialBackoff::new().with_initial_delay(Duration::from_millis(1)),
+            )
+            .build();
+        let token = creds.get_token().await?;
+        assert_eq!(token.principal_email(), Some("test@test.com"));
+        assert_eq!(*attempts.lock().unwrap(), 3);
+        Ok(())
+    }
 }
@@ -0,0 +1,260 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sign blobs with the identity of a service account.
+//!
+//! Some protocols, such as Cloud Storage [signed URLs], or custom JWTs sent to
+//! downstream systems, require a signature from a service account. A [Signer]
+//! creates these signatures without exposing the service account key to the
+//! application.
+//!
+//! Service account credentials sign locally, using the private key in the
+//! service account key file. Impersonated service account credentials, and
+//! the metadata service credentials, call the IAM Credentials [signBlob] API.
+//!
+//! # Example
+//! ```
+//! # use gcp_sdk_auth::credentials::create_access_token_credential;
+//! # use gcp_sdk_auth::errors::CredentialError;
+//! # async fn sample() -> Result<(), CredentialError> {
+//! let credential = create_access_token_credential().await?;
+//! let signer = credential.signer()?;
+//! let email = signer.client_email().await?;
+//! let signature = signer.sign_blob(b"content to sign").await?;
+//! # Ok(()) }
+//! ```
+//!
+//! [signBlob]: https://cloud.google.com/iam/docs/reference/credentials/rest/v1/projects.serviceAccounts/signBlob
+//! [signed URLs]: https://cloud.google.com/storage/docs/access-control/signed-urls
+
+pub mod storage;
+
+use crate::credentials::impersonated_credential::{post_iam, service_account_name};
+use crate::credentials::{Credential, Result};
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use std::sync::Arc;
+
+/// Signs blobs with the identity of a service account.
+///
+/// For RSA keys, and for the IAM Credentials API, the signature uses
+/// RSASSA-PKCS1-v1_5 with SHA-256. Service account keys using ECDSA (P-256)
+/// create ASN.1 DER encoded ECDSA signatures with SHA-256.
+///
+/// Use [Credential::signer] to create a signer.
+#[derive(Clone, Debug)]
+pub struct Signer {
+    inner: Arc<dyn SignerTrait>,
+}
+
+impl Signer {
+    pub(crate) fn new<T: SignerTrait + 'static>(inner: T) -> Self {
+        Self {
+            inner: Arc::new(inner),
+        }
+    }
+
+    /// Returns the email of the service account creating the signatures.
+    pub async fn client_email(&self) -> Result<String> {
+        self.inner.client_email().await
+    }
+
+    /// Signs `content`, returning the signature bytes.
+    pub async fn sign_blob(&self, content: &[u8]) -> Result<Vec<u8>> {
+        self.inner.sign(content).await
+    }
+
+    /// Returns the algorithm of the key creating the signatures.
+    pub(crate) fn key_algorithm(&self) -> Result<KeyAlgorithm> {
+        self.inner.key_algorithm()
+    }
+}
+
+/// The algorithm of the key used by a [Signer].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum KeyAlgorithm {
+    Rsa,
+    EcdsaP256,
+}
+
+/// The implementation of a [Signer].
+#[async_trait::async_trait]
+pub(crate) trait SignerTrait: Send + Sync + std::fmt::Debug {
+    async fn client_email(&self) -> Result<String>;
+
+    async fn sign(&self, content: &[u8]) -> Result<Vec<u8>>;
+
+    // The IAM Credentials API always signs with (Google-managed) RSA keys.
+    fn key_algorithm(&self) -> Result<KeyAlgorithm> {
+        Ok(KeyAlgorithm::Rsa)
+    }
+}
+
+/// Signs blobs with the IAM Credentials [signBlob] API.
+///
+/// [signBlob]: https://cloud.google.com/iam/docs/reference/credentials/rest/v1/projects.serviceAccounts/signBlob
+#[derive(Debug)]
+pub(crate) struct IamSigner {
+    // The credentials used to call the API.
+    source: Credential,
+    client_email: String,
+    url: String,
+    delegates: Vec<String>,
+}
+
+impl IamSigner {
+    /// Creates a signer for `client_email`, authenticating with `source`.
+    ///
+    /// The `url` is the `signBlob` API URL, see [sign_blob_url].
+    pub(crate) fn new(
+        source: Credential,
+        url: String,
+        client_email: String,
+        delegates: Vec<String>,
+    ) -> Self {
+        Self {
+            source,
+            client_email,
+            url,
+            delegates,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SignerTrait for IamSigner {
+    async fn client_email(&self) -> Result<String> {
+        Ok(self.client_email.clone())
+    }
+
+    async fn sign(&self, content: &[u8]) -> Result<Vec<u8>> {
+        sign_blob(&self.source, &self.url, &self.delegates, content).await
+    }
+}
+
+/// Returns the URL of the `signBlob` API for `client_email`.
+pub(crate) fn sign_blob_url(endpoint: &str, client_email: &str) -> String {
+    format!(
+        "{}/v1/{}:signBlob",
+        endpoint.trim_end_matches('/'),
+        service_account_name(client_email)
+    )
+}
+
+/// Calls the `signBlob` API at `url`, authenticating with `source`.
+pub(crate) async fn sign_blob(
+    source: &Credential,
+    url: &str,
+    delegates: &[String],
+    content: &[u8],
+) -> Result<Vec<u8>> {
+    let request = SignBlobRequest {
+        delegates: delegates.iter().map(|d| service_account_name(d)).collect(),
+        payload: BASE64_STANDARD.encode(content),
+    };
+    let response: SignBlobResponse = post_iam(source, url, &request).await?;
+    BASE64_STANDARD
+        .decode(response.signed_blob)
+        .map_err(crate::errors::CredentialError::non_retryable)
+}
+
+#[derive(Debug, serde::Serialize)]
+struct SignBlobRequest {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    delegates: Vec<String>,
+    payload: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SignBlobResponse {
+    signed_blob: String,
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::credentials::testing::test_credentials;
+    use axum::extract::Json;
+    use http::StatusCode;
+    use tokio::task::JoinHandle;
+
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    /// Starts a fake IAM Credentials service. The signature is the payload,
+    /// reversed. Returns the (endpoint, server) pair.
+    pub(crate) async fn start_iam(client_email: &'static str) -> (String, JoinHandle<()>) {
+        let handler = move |Json(request): Json<serde_json::Value>| async move {
+            let payload = request["payload"].as_str().unwrap();
+            let mut blob = BASE64_STANDARD.decode(payload).unwrap();
+            blob.reverse();
+            let response = serde_json::json!({
+                "keyId": "test-key-id",
+                "signedBlob": BASE64_STANDARD.encode(blob),
+            });
+            (StatusCode::OK, Json(response))
+        };
+        let path = format!("/v1/projects/-/serviceAccounts/{client_email}:signBlob");
+        let app = axum::Router::new().route(&path, axum::routing::post(handler));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (format!("http://{addr}"), server)
+    }
+
+    #[test]
+    fn request_serde() -> TestResult {
+        let request = SignBlobRequest {
+            delegates: Vec::new(),
+            payload: "abc=".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&request)?,
+            serde_json::json!({"payload": "abc="})
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn iam_signer_success() -> TestResult {
+        let (endpoint, _server) = start_iam("test-sa@test.iam.gserviceaccount.com").await;
+        let signer = Signer::new(IamSigner::new(
+            test_credentials(),
+            sign_blob_url(&endpoint, "test-sa@test.iam.gserviceaccount.com"),
+            "test-sa@test.iam.gserviceaccount.com".to_string(),
+            vec!["delegate@test.iam.gserviceaccount.com".to_string()],
+        ));
+        assert_eq!(
+            signer.client_email().await?,
+            "test-sa@test.iam.gserviceaccount.com"
+        );
+        assert_eq!(signer.sign_blob(b"abc").await?, b"cba");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn iam_signer_error() -> TestResult {
+        let (endpoint, _server) = start_iam("test-sa@test.iam.gserviceaccount.com").await;
+        let signer = Signer::new(IamSigner::new(
+            test_credentials(),
+            sign_blob_url(&endpoint, "other-sa@test.iam.gserviceaccount.com"),
+            "other-sa@test.iam.gserviceaccount.com".to_string(),
+            Vec::new(),
+        ));
+        let e = signer.sign_blob(b"abc").await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        Ok(())
+    }
+}
@@ -0,0 +1,882 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Cloud Storage [V4 signed URLs] and [signed POST policies].
+//!
+//! A signed URL gives time-limited access to a Cloud Storage object, to
+//! anybody holding the URL. A signed POST policy lets HTML forms upload
+//! objects directly to Cloud Storage, subject to the conditions in the
+//! policy.
+//!
+//! Both are signed with the identity of a service account, using a [Signer].
+//! Cloud Storage V4 signatures require RSA keys. Signers using service account
+//! keys with ECDSA return an error, use an RSA key, or sign with the IAM
+//! Credentials API, e.g., with impersonated service account credentials.
+//!
+//! # Example
+//! ```
+//! # use gcp_sdk_auth::credentials::create_access_token_credential;
+//! # use gcp_sdk_auth::credentials::signer::storage::SignedUrlBuilder;
+//! # use gcp_sdk_auth::errors::CredentialError;
+//! # use std::time::Duration;
+//! # async fn sample() -> Result<(), CredentialError> {
+//! let signer = create_access_token_credential().await?.signer()?;
+//! let url = SignedUrlBuilder::new("my-bucket", "path/to/my-object")
+//!     .with_method(http::Method::PUT)
+//!     .with_header("content-type", "text/plain")
+//!     .with_expiration(Duration::from_secs(15 * 60))
+//!     .sign(&signer)
+//!     .await?;
+//! # Ok(()) }
+//! ```
+//!
+//! [V4 signed URLs]: https://cloud.google.com/storage/docs/access-control/signed-urls
+//! [signed POST policies]: https://cloud.google.com/storage/docs/xml-api/post-object-forms
+
+use crate::credentials::signer::{KeyAlgorithm, Signer};
+use crate::credentials::Result;
+use crate::errors::CredentialError;
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+const DEFAULT_ENDPOINT: &str = "https://storage.googleapis.com";
+const SIGNING_ALGORITHM: &str = "GOOG4-RSA-SHA256";
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+const CONTENT_SHA256_HEADER: &str = "x-goog-content-sha256";
+const DEFAULT_EXPIRATION: Duration = Duration::from_secs(3600);
+/// The maximum expiration for V4 signatures, 7 days.
+const MAX_EXPIRATION: Duration = Duration::from_secs(7 * 24 * 3600);
+
+// Escape everything but the RFC 3986 unreserved characters.
+const ESCAPED: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// The style of the URLs used to access the bucket.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub enum UrlStyle {
+    /// The bucket is part of the path, e.g.
+    /// `https://storage.googleapis.com/my-bucket/my-object`.
+    #[default]
+    PathStyle,
+    /// The bucket is part of the hostname, e.g.
+    /// `https://my-bucket.storage.googleapis.com/my-object`.
+    VirtualHostedStyle,
+    /// The bucket is accessed using a custom hostname, e.g.
+    /// `https://cdn.example.com/my-object`.
+    ///
+    /// The hostname must be a [CNAME] for `c.storage.googleapis.com`, or a
+    /// load balancer in front of the bucket.
+    ///
+    /// [CNAME]: https://cloud.google.com/storage/docs/request-endpoints#cname
+    BucketBoundHostname(String),
+}
+
+/// Creates Cloud Storage [V4 signed URLs].
+///
+/// The signed URLs use `GET` and expire after one hour, unless configured
+/// otherwise.
+///
+/// [V4 signed URLs]: https://cloud.google.com/storage/docs/access-control/signed-urls
+#[derive(Clone, Debug)]
+pub struct SignedUrlBuilder {
+    bucket: String,
+    object: String,
+    method: http::Method,
+    expiration: Duration,
+    headers: Vec<(String, String)>,
+    query_parameters: Vec<(String, String)>,
+    url_style: UrlStyle,
+    endpoint: String,
+    // Only configured in tests, the signatures use the current time otherwise.
+    timestamp: Option<OffsetDateTime>,
+}
+
+impl SignedUrlBuilder {
+    /// Creates a builder for a signed URL to access `object` in `bucket`.
+    ///
+    /// Use an empty `object` to create signed URLs for bucket operations.
+    pub fn new<B: Into<String>, O: Into<String>>(bucket: B, object: O) -> Self {
+        Self {
+            bucket: bucket.into(),
+            object: object.into(),
+            method: http::Method::GET,
+            expiration: DEFAULT_EXPIRATION,
+            headers: Vec::new(),
+            query_parameters: Vec::new(),
+            url_style: UrlStyle::default(),
+            endpoint: DEFAULT_ENDPOINT.to_string(),
+            timestamp: None,
+        }
+    }
+
+    /// Sets the HTTP method allowed by the signed URL, e.g., `PUT` to upload
+    /// an object, or `DELETE` to delete it.
+    pub fn with_method(mut self, v: http::Method) -> Self {
+        self.method = v;
+        self
+    }
+
+    /// Sets how long the signed URL is valid, up to 7 days.
+    pub fn with_expiration(mut self, v: Duration) -> Self {
+        self.expiration = v;
+        self
+    }
+
+    /// Adds a header to the signature.
+    ///
+    /// Requests using the signed URL must include the same header, with the
+    /// same value. Use `x-goog-content-sha256` to sign the hash of the
+    /// payload.
+    pub fn with_header<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.headers.push((key.into(), value.into()));
+        self
+    }
+
+    /// Adds a query parameter to the signed URL, e.g.,
+    /// `response-content-disposition`.
+    pub fn with_query_parameter<K: Into<String>, V: Into<String>>(
+        mut self,
+        key: K,
+        value: V,
+    ) -> Self {
+        self.query_parameters.push((key.into(), value.into()));
+        self
+    }
+
+    /// Sets the style of the signed URL.
+    pub fn with_url_style(mut self, v: UrlStyle) -> Self {
+        self.url_style = v;
+        self
+    }
+
+    /// Sets the Cloud Storage endpoint.
+    ///
+    /// The default is `https://storage.googleapis.com`. The scheme of the
+    /// endpoint is also used with [UrlStyle::BucketBoundHostname].
+    pub fn with_endpoint<T: Into<String>>(mut self, v: T) -> Self {
+        self.endpoint = v.into();
+        self
+    }
+
+    /// Returns the signed URL, signed by `signer`.
+    pub async fn sign(self, signer: &Signer) -> Result<String> {
+        check_key_algorithm(signer)?;
+        let (url, string_to_sign) = self.string_to_sign(&signer.client_email().await?)?;
+        let signature = hex(&signer.sign_blob(string_to_sign.as_bytes()).await?);
+        Ok(format!("{url}&X-Goog-Signature={signature}"))
+    }
+
+    // Returns the URL without the signature, and the string to sign.
+    fn string_to_sign(&self, client_email: &str) -> Result<(String, String)> {
+        check_expiration(self.expiration)?;
+        let timestamp = Timestamp::new(self.timestamp);
+        let (url, canonical_request) = self.canonical_request(client_email, &timestamp)?;
+        let string_to_sign = format!(
+            "{SIGNING_ALGORITHM}\n{}\n{}\n{}",
+            timestamp.datetime,
+            timestamp.scope(),
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        Ok((url, string_to_sign))
+    }
+
+    // Returns the URL without the signature, and the canonical request.
+    fn canonical_request(
+        &self,
+        client_email: &str,
+        timestamp: &Timestamp,
+    ) -> Result<(String, String)> {
+        let (scheme, host) = split_endpoint(&self.endpoint)?;
+        let (host, path) = match &self.url_style {
+            UrlStyle::PathStyle if self.object.is_empty() => {
+                (host.to_string(), format!("/{}", self.bucket))
+            }
+            UrlStyle::PathStyle => (
+                host.to_string(),
+                format!("/{}/{}", self.bucket, escape_path(&self.object)),
+            ),
+            UrlStyle::VirtualHostedStyle => (
+                format!("{}.{host}", self.bucket),
+                format!("/{}", escape_path(&self.object)),
+            ),
+            UrlStyle::BucketBoundHostname(h) => {
+                (h.clone(), format!("/{}", escape_path(&self.object)))
+            }
+        };
+
+        // Header values are trimmed, and sequences of spaces or tabs are
+        // replaced with a single space. Repeated headers are joined with
+        // commas.
+        let mut headers = BTreeMap::<String, Vec<String>>::new();
+        for (k, v) in &self.headers {
+            let k = k.to_ascii_lowercase();
+            if k == "host" {
+                continue;
+            }
+            let v = v.split([' ', '\t']).filter(|s| !s.is_empty());
+            headers
+                .entry(k)
+                .or_default()
+                .push(v.collect::<Vec<_>>().join(" "));
+        }
+        headers.insert("host".to_string(), vec![host.clone()]);
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(k, v)| format!("{k}:{}\n", v.join(",")))
+            .collect();
+        let signed_headers = headers.keys().cloned().collect::<Vec<_>>().join(";");
+        let payload = headers
+            .get(CONTENT_SHA256_HEADER)
+            .map(|v| v.join(","))
+            .unwrap_or_else(|| UNSIGNED_PAYLOAD.to_string());
+
+        let mut query = vec![
+            (
+                "X-Goog-Algorithm".to_string(),
+                SIGNING_ALGORITHM.to_string(),
+            ),
+            (
+                "X-Goog-Credential".to_string(),
+                timestamp.credential(client_email),
+            ),
+            ("X-Goog-Date".to_string(), timestamp.datetime.clone()),
+            (
+                "X-Goog-Expires".to_string(),
+                self.expiration.as_secs().to_string(),
+            ),
+            ("X-Goog-SignedHeaders".to_string(), signed_headers.clone()),
+        ];
+        query.extend(self.query_parameters.iter().cloned());
+        let mut query: Vec<_> = query
+            .into_iter()
+            .map(|(k, v)| (escape(&k), escape(&v)))
+            .collect();
+        query.sort();
+        let canonical_query = query
+            .iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let canonical_request = format!(
+            "{}\n{path}\n{canonical_query}\n{canonical_headers}\n{signed_headers}\n{payload}",
+            self.method
+        );
+        Ok((
+            format!("{scheme}://{host}{path}?{canonical_query}"),
+            canonical_request,
+        ))
+    }
+}
+
+/// A condition in a [signed POST policy].
+///
+/// [signed POST policy]: https://cloud.google.com/storage/docs/authentication/signatures#policy-document
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum PolicyCondition {
+    /// The form field must start with the given prefix, e.g., the `key`
+    /// must start with `uploads/`.
+    StartsWith(String, String),
+    /// The size of the uploaded object must be in the given (inclusive)
+    /// range.
+    ContentLengthRange(u64, u64),
+}
+
+impl PolicyCondition {
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            Self::StartsWith(field, prefix) => {
+                let field = format!("${}", field.trim_start_matches('$'));
+                serde_json::json!(["starts-with", field, prefix])
+            }
+            Self::ContentLengthRange(min, max) => {
+                serde_json::json!(["content-length-range", min, max])
+            }
+        }
+    }
+}
+
+/// A signed POST policy, with the URL and the fields for an HTML form.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PostPolicy {
+    /// The URL to `POST` the form.
+    pub url: String,
+
+    /// The form fields, including the policy and its signature.
+    pub fields: BTreeMap<String, String>,
+}
+
+/// Creates Cloud Storage [signed POST policies].
+///
+/// The policies expire after one hour, unless configured otherwise.
+///
+/// [signed POST policies]: https://cloud.google.com/storage/docs/xml-api/post-object-forms
+#[derive(Clone, Debug)]
+pub struct PostPolicyBuilder {
+    bucket: String,
+    object: String,
+    expiration: Duration,
+    fields: BTreeMap<String, String>,
+    conditions: Vec<PolicyCondition>,
+    url_style: UrlStyle,
+    endpoint: String,
+    // Only configured in tests, the signatures use the current time otherwise.
+    timestamp: Option<OffsetDateTime>,
+}
+
+impl PostPolicyBuilder {
+    /// Creates a builder for a policy to upload `object` to `bucket`.
+    pub fn new<B: Into<String>, O: Into<String>>(bucket: B, object: O) -> Self {
+        Self {
+            bucket: bucket.into(),
+            object: object.into(),
+            expiration: DEFAULT_EXPIRATION,
+            fields: BTreeMap::new(),
+            conditions: Vec::new(),
+            url_style: UrlStyle::default(),
+            endpoint: DEFAULT_ENDPOINT.to_string(),
+            timestamp: None,
+        }
+    }
+
+    /// Sets how long the policy is valid, up to 7 days.
+    pub fn with_expiration(mut self, v: Duration) -> Self {
+        self.expiration = v;
+        self
+    }
+
+    /// Adds a form field, e.g., `content-type` or `success_action_status`.
+    ///
+    /// The policy requires an exact match for the field, unless the field
+    /// name starts with `x-ignore-`.
+    pub fn with_field<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.fields.insert(key.into(), value.into());
+        self
+    }
+
+    /// Adds a condition to the policy.
+    pub fn with_condition(mut self, v: PolicyCondition) -> Self {
+        self.conditions.push(v);
+        self
+    }
+
+    /// Sets the style of the form URL.
+    pub fn with_url_style(mut self, v: UrlStyle) -> Self {
+        self.url_style = v;
+        self
+    }
+
+    /// Sets the Cloud Storage endpoint.
+    ///
+    /// The default is `https://storage.googleapis.com`.
+    pub fn with_endpoint<T: Into<String>>(mut self, v: T) -> Self {
+        self.endpoint = v.into();
+        self
+    }
+
+    /// Returns the policy, signed by `signer`.
+    pub async fn sign(self, signer: &Signer) -> Result<PostPolicy> {
+        check_key_algorithm(signer)?;
+        let mut policy = self.unsigned(&signer.client_email().await?)?;
+        let signature = hex(&signer.sign_blob(policy.fields["policy"].as_bytes()).await?);
+        policy
+            .fields
+            .insert("x-goog-signature".to_string(), signature);
+        Ok(policy)
+    }
+
+    // Returns the policy, without the signature.
+    fn unsigned(&self, client_email: &str) -> Result<PostPolicy> {
+        check_expiration(self.expiration)?;
+        let timestamp = Timestamp::new(self.timestamp);
+        let expiration = timestamp.now + self.expiration;
+        let (scheme, host) = split_endpoint(&self.endpoint)?;
+        let url = match &self.url_style {
+            UrlStyle::PathStyle => format!("{scheme}://{host}/{}/", self.bucket),
+            UrlStyle::VirtualHostedStyle => format!("{scheme}://{}.{host}/", self.bucket),
+            UrlStyle::BucketBoundHostname(h) => format!("{scheme}://{h}/"),
+        };
+
+        let mut fields = self.fields.clone();
+        fields.insert("key".to_string(), self.object.clone());
+        fields.insert("x-goog-date".to_string(), timestamp.datetime.clone());
+        fields.insert(
+            "x-goog-credential".to_string(),
+            timestamp.credential(client_email),
+        );
+        fields.insert(
+            "x-goog-algorithm".to_string(),
+            SIGNING_ALGORITHM.to_string(),
+        );
+
+        let mut conditions: Vec<_> = self.conditions.iter().map(|c| c.to_json()).collect();
+        conditions.extend(
+            self.fields
+                .iter()
+                .filter(|(k, _)| !k.starts_with("x-ignore-"))
+                .map(|(k, v)| serde_json::json!({ k: v })),
+        );
+        conditions.extend([
+            serde_json::json!({ "bucket": self.bucket }),
+            serde_json::json!({ "key": self.object }),
+            serde_json::json!({ "x-goog-date": timestamp.datetime }),
+            serde_json::json!({ "x-goog-credential": timestamp.credential(client_email) }),
+            serde_json::json!({ "x-goog-algorithm": SIGNING_ALGORITHM }),
+        ]);
+        let expiration = format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            expiration.year(),
+            u8::from(expiration.month()),
+            expiration.day(),
+            expiration.hour(),
+            expiration.minute(),
+            expiration.second()
+        );
+        // Use `serde_json::Value` to keep the fields in the documented
+        // order: `conditions` before `expiration`.
+        let policy = format!(
+            r#"{{"conditions":{},"expiration":{}}}"#,
+            serde_json::Value::Array(conditions),
+            serde_json::Value::String(expiration)
+        );
+        fields.insert(
+            "policy".to_string(),
+            BASE64_STANDARD.encode(escape_non_ascii(&policy)),
+        );
+        Ok(PostPolicy { url, fields })
+    }
+}
+
+/// The timestamp of a signature, in the formats used by V4 signatures.
+struct Timestamp {
+    now: OffsetDateTime,
+    // In `YYYYMMDD'T'HHMMSS'Z'` format.
+    datetime: String,
+}
+
+impl Timestamp {
+    fn new(now: Option<OffsetDateTime>) -> Self {
+        let now = now.unwrap_or_else(OffsetDateTime::now_utc);
+        let datetime = format!(
+            "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+            now.year(),
+            u8::from(now.month()),
+            now.day(),
+            now.hour(),
+            now.minute(),
+            now.second()
+        );
+        Self { now, datetime }
+    }
+
+    fn scope(&self) -> String {
+        format!("{}/auto/storage/goog4_request", &self.datetime[..8])
+    }
+
+    fn credential(&self, client_email: &str) -> String {
+        format!("{client_email}/{}", self.scope())
+    }
+}
+
+fn check_expiration(expiration: Duration) -> Result<()> {
+    if expiration > MAX_EXPIRATION {
+        return Err(CredentialError::non_retryable(format!(
+            "the expiration for V4 signatures cannot exceed 7 days ({}s), got {}s",
+            MAX_EXPIRATION.as_secs(),
+            expiration.as_secs()
+        )));
+    }
+    Ok(())
+}
+
+// Cloud Storage only supports `GOOG4-RSA-SHA256` (and HMAC) V4 signatures.
+fn check_key_algorithm(signer: &Signer) -> Result<()> {
+    match signer.key_algorithm()? {
+        KeyAlgorithm::Rsa => Ok(()),
+        other => Err(CredentialError::non_retryable(format!(
+            "Cloud Storage V4 signatures require an RSA key, but the signer uses an {other:?} key, use an RSA service account key or sign with the IAM Credentials API"
+        ))),
+    }
+}
+
+// Returns the (scheme, host) of the endpoint.
+fn split_endpoint(endpoint: &str) -> Result<(&str, &str)> {
+    let (scheme, host) = endpoint.split_once("://").ok_or_else(|| {
+        CredentialError::non_retryable(format!(
+            "the Cloud Storage endpoint must include a scheme, got {endpoint}"
+        ))
+    })?;
+    Ok((scheme, host.trim_end_matches('/')))
+}
+
+fn escape(s: &str) -> String {
+    utf8_percent_encode(s, ESCAPED).to_string()
+}
+
+// Escapes each segment of an object name, the `/` separators are preserved.
+fn escape_path(s: &str) -> String {
+    s.split('/').map(escape).collect::<Vec<_>>().join("/")
+}
+
+// Escapes non-ASCII characters as JSON `\uXXXX` sequences, as the policy
+// documents created by other Cloud Storage clients.
+fn escape_non_ascii(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c.is_ascii() {
+            escaped.push(c);
+            continue;
+        }
+        let mut buf = [0_u16; 2];
+        for u in c.encode_utf16(&mut buf) {
+            escaped.push_str(&format!("\\u{u:04x}"));
+        }
+    }
+    escaped
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::credentials::Credential;
+    use serde_json::Value;
+    use std::str::FromStr;
+
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    // The test vectors use the format of the Cloud Storage conformance tests,
+    // with a test-only service account key. They are NOT the upstream
+    // conformance tests: the expected values were produced by this
+    // implementation, so they only detect regressions. The upstream
+    // `storage/v1/v4_signatures.json`, from
+    // https://github.com/googleapis/conformance-tests, should replace them,
+    // unmodified, with its service account key.
+    const TEST_VECTORS: &str = include_str!("v4_signatures.json");
+
+    // The test cases, by description, that use features not supported by
+    // this implementation.
+    const SKIPPED: &[&str] = &[];
+
+    const SIGNED_URL_FIELDS: &[&str] = &[
+        "description",
+        "bucket",
+        "object",
+        "method",
+        "expiration",
+        "timestamp",
+        "headers",
+        "queryParameters",
+        "scheme",
+        "urlStyle",
+        "bucketBoundHostname",
+        "expectedCanonicalRequest",
+        "expectedStringToSign",
+        "expectedUrl",
+    ];
+
+    const POST_POLICY_FIELDS: &[&str] = &[
+        "description",
+        "bucket",
+        "object",
+        "expiration",
+        "timestamp",
+        "fields",
+        "conditions",
+        "scheme",
+        "urlStyle",
+        "bucketBoundHostname",
+        "expectedDecodedPolicy",
+        "expectedFields",
+        "expectedUrl",
+    ];
+
+    const POST_POLICY_CONDITIONS: &[&str] = &["startsWith", "contentLengthRange"];
+
+    fn test_vectors() -> Value {
+        serde_json::from_str(TEST_VECTORS).unwrap()
+    }
+
+    // Returns the test cases in `group`, except the skipped ones.
+    //
+    // Test cases with fields the runner does not understand fail, instead of
+    // passing without testing the feature. They must be added to `SKIPPED`.
+    fn test_cases(group: &str, fields: &[&str]) -> Vec<Value> {
+        let cases = test_vectors()[group].as_array().unwrap().clone();
+        for skipped in SKIPPED {
+            assert!(
+                test_vectors()
+                    .as_object()
+                    .unwrap()
+                    .values()
+                    .filter_map(Value::as_array)
+                    .flatten()
+                    .any(|t| t["description"] == *skipped),
+                "skipped test case {skipped} does not exist"
+            );
+        }
+        cases
+            .into_iter()
+            .filter(|t| !SKIPPED.contains(&t["description"].as_str().unwrap()))
+            .inspect(|t| {
+                let description = t["description"].as_str().unwrap();
+                for k in t.as_object().unwrap().keys() {
+                    assert!(
+                        fields.contains(&k.as_str()),
+                        "{description}: unsupported field {k}, skip the test case"
+                    );
+                }
+            })
+            .collect()
+    }
+
+    fn test_signer() -> Signer {
+        let _ = rustls::crypto::CryptoProvider::install_default(
+            rustls::crypto::aws_lc_rs::default_provider(),
+        );
+        Credential::from_json(test_vectors()["serviceAccount"].clone())
+            .unwrap()
+            .signer()
+            .unwrap()
+    }
+
+    fn timestamp(v: &Value) -> OffsetDateTime {
+        OffsetDateTime::parse(
+            v.as_str().unwrap(),
+            &time::format_description::well_known::Rfc3339,
+        )
+        .unwrap()
+    }
+
+    fn url_style(test: &Value) -> UrlStyle {
+        match test["urlStyle"].as_str() {
+            None => UrlStyle::PathStyle,
+            Some("VIRTUAL_HOSTED_STYLE") => UrlStyle::VirtualHostedStyle,
+            Some("BUCKET_BOUND_HOSTNAME") => UrlStyle::BucketBoundHostname(
+                test["bucketBoundHostname"].as_str().unwrap().to_string(),
+            ),
+            Some(s) => panic!("unexpected url style {s}"),
+        }
+    }
+
+    fn endpoint(test: &Value) -> String {
+        let scheme = test["scheme"].as_str().unwrap_or("https");
+        format!("{scheme}://storage.googleapis.com")
+    }
+
+    fn pairs(v: &Value) -> Vec<(String, String)> {
+        v.as_array()
+            .map(|a| {
+                a.iter()
+                    .map(|p| {
+                        (
+                            p[0].as_str().unwrap().to_string(),
+                            p[1].as_str().unwrap().to_string(),
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn ecdsa_keys_are_rejected() -> TestResult {
+        use p256::pkcs8::{EncodePrivateKey, LineEnding};
+        let _ = rustls::crypto::CryptoProvider::install_default(
+            rustls::crypto::aws_lc_rs::default_provider(),
+        );
+        let key = p256::SecretKey::random(&mut rand::thread_rng()).to_pkcs8_pem(LineEnding::LF)?;
+        let signer = Credential::from_json(serde_json::json!({
+            "type": "service_account",
+            "client_email": "test-client-email",
+            "private_key_id": "test-private-key-id",
+            "private_key": key.as_str(),
+            "project_id": "test-project-id",
+        }))?
+        .signer()?;
+        assert_eq!(signer.key_algorithm()?, KeyAlgorithm::EcdsaP256);
+
+        let e = SignedUrlBuilder::new("my-bucket", "my-object")
+            .sign(&signer)
+            .await
+            .err()
+            .unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("require an RSA key"), "{e}");
+
+        let e = PostPolicyBuilder::new("my-bucket", "my-object")
+            .sign(&signer)
+            .await
+            .err()
+            .unwrap();
+        assert!(e.to_string().contains("require an RSA key"), "{e}");
+
+        assert_eq!(test_signer().key_algorithm()?, KeyAlgorithm::Rsa);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn signed_url_conformance() -> TestResult {
+        let signer = test_signer();
+        let client_email = signer.client_email().await?;
+        for test in &test_cases("signingV4Tests", SIGNED_URL_FIELDS) {
+            let description = test["description"].as_str().unwrap();
+            let mut builder = SignedUrlBuilder::new(
+                test["bucket"].as_str().unwrap(),
+                test["object"].as_str().unwrap(),
+            )
+            .with_method(http::Method::from_str(test["method"].as_str().unwrap())?)
+            .with_expiration(Duration::from_secs(test["expiration"].as_u64().unwrap()))
+            .with_url_style(url_style(test))
+            .with_endpoint(endpoint(test));
+            for (k, v) in pairs(&test["headers"]) {
+                builder = builder.with_header(k, v);
+            }
+            for (k, v) in pairs(&test["queryParameters"]) {
+                builder = builder.with_query_parameter(k, v);
+            }
+            builder.timestamp = Some(timestamp(&test["timestamp"]));
+
+            let (_, canonical_request) =
+                builder.canonical_request(&client_email, &Timestamp::new(builder.timestamp))?;
+            assert_eq!(
+                canonical_request, test["expectedCanonicalRequest"],
+                "{description}"
+            );
+            let (_, string_to_sign) = builder.string_to_sign(&client_email)?;
+            assert_eq!(
+                string_to_sign, test["expectedStringToSign"],
+                "{description}"
+            );
+            let url = builder.sign(&signer).await?;
+            assert_eq!(url, test["expectedUrl"], "{description}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn post_policy_conformance() -> TestResult {
+        let signer = test_signer();
+        for test in &test_cases("postPolicyV4Tests", POST_POLICY_FIELDS) {
+            let description = test["description"].as_str().unwrap();
+            let mut builder = PostPolicyBuilder::new(
+                test["bucket"].as_str().unwrap(),
+                test["object"].as_str().unwrap(),
+            )
+            .with_expiration(Duration::from_secs(test["expiration"].as_u64().unwrap()))
+            .with_url_style(url_style(test))
+            .with_endpoint(endpoint(test));
+            if let Some(fields) = test["fields"].as_object() {
+                for (k, v) in fields {
+                    builder = builder.with_field(k, v.as_str().unwrap());
+                }
+            }
+            let conditions = &test["conditions"];
+            for k in conditions.as_object().into_iter().flat_map(|c| c.keys()) {
+                assert!(
+                    POST_POLICY_CONDITIONS.contains(&k.as_str()),
+                    "{description}: unsupported condition {k}, skip the test case"
+                );
+            }
+            if let Some(v) = conditions["startsWith"].as_array() {
+                builder = builder.with_condition(PolicyCondition::StartsWith(
+                    v[0].as_str().unwrap().to_string(),
+                    v[1].as_str().unwrap().to_string(),
+                ));
+            }
+            if let Some(v) = conditions["contentLengthRange"].as_array() {
+                builder = builder.with_condition(PolicyCondition::ContentLengthRange(
+                    v[0].as_u64().unwrap(),
+                    v[1].as_u64().unwrap(),
+                ));
+            }
+            builder.timestamp = Some(timestamp(&test["timestamp"]));
+
+            let policy = builder.sign(&signer).await?;
+            let decoded = BASE64_STANDARD.decode(&policy.fields["policy"])?;
+            assert_eq!(
+                String::from_utf8(decoded)?,
+                test["expectedDecodedPolicy"],
+                "{description}"
+            );
+            assert_eq!(policy.url, test["expectedUrl"], "{description}");
+            let want =
+                serde_json::from_value::<BTreeMap<String, String>>(test["expectedFields"].clone())?;
+            assert_eq!(policy.fields, want, "{description}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn expiration_too_long() -> TestResult {
+        let signer = test_signer();
+        let e = SignedUrlBuilder::new("test-bucket", "test-object")
+            .with_expiration(MAX_EXPIRATION + Duration::from_secs(1))
+            .sign(&signer)
+            .await
+            .err()
+            .unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("7 days"), "{e}");
+
+        let e = PostPolicyBuilder::new("test-bucket", "test-object")
+            .with_expiration(MAX_EXPIRATION + Duration::from_secs(1))
+            .sign(&signer)
+            .await
+            .err()
+            .unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn endpoint_without_scheme() -> TestResult {
+        let signer = test_signer();
+        let e = SignedUrlBuilder::new("test-bucket", "test-object")
+            .with_endpoint("storage.googleapis.com")
+            .sign(&signer)
+            .await
+            .err()
+            .unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("scheme"), "{e}");
+        Ok(())
+    }
+
+    #[test]
+    fn non_ascii() {
+        assert_eq!(escape_non_ascii("abc"), "abc");
+        assert_eq!(escape_non_ascii("é"), "\\u00e9");
+        // Characters outside the BMP use surrogate pairs.
+        assert_eq!(escape_non_ascii("😀"), "\\ud83d\\ude00");
+    }
+
+    #[test]
+    fn path_escaping() {
+        assert_eq!(escape_path("a/b c/d~e"), "a/b%20c/d~e");
+        assert_eq!(escape_path("a+b=c"), "a%2Bb%3Dc");
+    }
+}
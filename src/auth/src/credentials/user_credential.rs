@@ -13,31 +13,40 @@
 // limitations under the License.
 
 use crate::credentials::dynamic::CredentialTrait;
-use crate::credentials::{Credential, Result, QUOTA_PROJECT_KEY};
+use crate::credentials::id_token_credential;
+use crate::credentials::util::http_client;
+use crate::credentials::util::{build_headers, DEFAULT_UNIVERSE_DOMAIN};
+use crate::credentials::{Credential, CredentialOptions, Result};
 use crate::errors::{is_retryable, CredentialError};
-use crate::token::{Token, TokenProvider};
-use http::header::{HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
-use reqwest::{Client, Method};
+use crate::token::{metadata_keys, Token, TokenProvider};
+use crate::token_cache::TokenCache;
+use http::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::Method;
 use std::sync::Arc;
 use std::time::Duration;
 use time::OffsetDateTime;
 
-const OAUTH2_ENDPOINT: &str = "https://oauth2.googleapis.com/token";
+pub(crate) const OAUTH2_ENDPOINT: &str = "https://oauth2.googleapis.com/token";
 
-pub(crate) fn creds_from(js: serde_json::Value) -> Result<Credential> {
+pub(crate) fn creds_from(js: serde_json::Value, options: &CredentialOptions) -> Result<Credential> {
     let au =
         serde_json::from_value::<AuthorizedUser>(js).map_err(CredentialError::non_retryable)?;
     let token_provider = UserTokenProvider {
         client_id: au.client_id,
         client_secret: au.client_secret,
         refresh_token: au.refresh_token,
-        endpoint: OAUTH2_ENDPOINT.to_string(),
+        endpoint: options
+            .token_endpoint
+            .clone()
+            .unwrap_or_else(|| OAUTH2_ENDPOINT.to_string()),
+        scopes: options.scopes.as_ref().map(|v| v.join(" ")),
     };
 
     Ok(Credential {
         inner: Arc::new(UserCredential {
-            token_provider,
-            quota_project_id: au.quota_project_id,
+            token_provider: TokenCache::new(options.retrying(token_provider)),
+            quota_project_id: options.quota_project_id.clone().or(au.quota_project_id),
+            universe_domain: options.universe_domain.clone(),
         }),
     })
 }
@@ -48,6 +57,8 @@ struct UserTokenProvider {
     client_secret: String,
     refresh_token: String,
     endpoint: String,
+    // If set, restricts the refreshed access tokens to these scopes.
+    scopes: Option<String>,
 }
 
 impl std::fmt::Debug for UserTokenProvider {
@@ -57,6 +68,7 @@ impl std::fmt::Debug for UserTokenProvider {
             .field("client_secret", &"[censored]")
             .field("refresh_token", &"[censored]")
             .field("endpoint", &self.endpoint)
+            .field("scopes", &self.scopes)
             .finish()
     }
 }
@@ -64,7 +76,7 @@ impl std::fmt::Debug for UserTokenProvider {
 #[async_trait::async_trait]
 impl TokenProvider for UserTokenProvider {
     async fn get_token(&self) -> Result<Token> {
-        let client = Client::new();
+        let client = http_client::client();
 
         // Make the request
         let req = Oauth2RefreshRequest {
@@ -72,6 +84,7 @@ impl TokenProvider for UserTokenProvider {
             client_id: self.client_id.clone(),
             client_secret: self.client_secret.clone(),
             refresh_token: self.refresh_token.clone(),
+            scope: self.scopes.clone(),
         };
         let header = HeaderValue::from_static("application/json");
         let builder = client
@@ -104,7 +117,17 @@ impl TokenProvider for UserTokenProvider {
                 .map(|d| OffsetDateTime::now_utc() + Duration::from_secs(d)),
             metadata: None,
         };
-        Ok(token)
+        let email = response
+            .id_token
+            .as_deref()
+            .and_then(id_token_credential::id_token_email);
+        Ok(token
+            .with_metadata(
+                metadata_keys::SCOPES,
+                response.scope.or_else(|| self.scopes.clone()),
+            )
+            .with_metadata(metadata_keys::SOURCE_TYPE, Some("authorized_user"))
+            .with_metadata(metadata_keys::PRINCIPAL_EMAIL, email))
     }
 }
 
@@ -118,6 +141,7 @@ where
 {
     token_provider: T,
     quota_project_id: Option<String>,
+    universe_domain: Option<String>,
 }
 
 #[async_trait::async_trait]
@@ -131,21 +155,19 @@ where
 
     async fn get_headers(&self) -> Result<Vec<(HeaderName, HeaderValue)>> {
         let token = self.get_token().await?;
-        let mut value = HeaderValue::from_str(&format!("{} {}", token.token_type, token.token))
-            .map_err(CredentialError::non_retryable)?;
-        value.set_sensitive(true);
-        let mut headers = vec![(AUTHORIZATION, value)];
-        if let Some(project) = &self.quota_project_id {
-            headers.push((
-                HeaderName::from_static(QUOTA_PROJECT_KEY),
-                HeaderValue::from_str(project).map_err(CredentialError::non_retryable)?,
-            ));
-        }
-        Ok(headers)
+        build_headers(&token, self.quota_project_id.as_deref())
+    }
+
+    async fn get_universe_domain(&self) -> Option<String> {
+        Some(
+            self.universe_domain
+                .clone()
+                .unwrap_or_else(|| DEFAULT_UNIVERSE_DOMAIN.to_string()),
+        )
     }
 }
 
-#[derive(Debug, PartialEq, serde::Deserialize)]
+#[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
 pub(crate) struct AuthorizedUser {
     #[serde(rename = "type")]
     cred_type: String,
@@ -156,6 +178,23 @@ pub(crate) struct AuthorizedUser {
     quota_project_id: Option<String>,
 }
 
+impl AuthorizedUser {
+    pub(crate) fn new(
+        client_id: String,
+        client_secret: String,
+        refresh_token: String,
+        quota_project_id: Option<String>,
+    ) -> Self {
+        Self {
+            cred_type: "authorized_user".to_string(),
+            client_id,
+            client_secret,
+            refresh_token,
+            quota_project_id,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
 enum RefreshGrantType {
     #[serde(rename = "refresh_token")]
@@ -168,6 +207,8 @@ struct Oauth2RefreshRequest {
     client_id: String,
     client_secret: String,
     refresh_token: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
 }
 
 #[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
@@ -180,13 +221,18 @@ struct Oauth2RefreshResponse {
     token_type: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     refresh_token: Option<String>,
+    // Returned when the refresh token includes the `openid` scope.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id_token: Option<String>,
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::credentials::QUOTA_PROJECT_KEY;
     use crate::token::test::MockTokenProvider;
     use axum::extract::Json;
+    use http::header::AUTHORIZATION;
     use http::StatusCode;
     use std::error::Error;
     use tokio::task::JoinHandle;
@@ -200,6 +246,7 @@ mod test {
             client_secret: "test-client-secret".to_string(),
             refresh_token: "test-refresh-token".to_string(),
             endpoint: OAUTH2_ENDPOINT.to_string(),
+            scopes: None,
         };
         let fmt = format!("{expected:?}");
         assert!(fmt.contains("test-client-id"), "{fmt}");
@@ -291,6 +338,7 @@ mod test {
         let uc = UserCredential {
             token_provider: mock,
             quota_project_id: None,
+            universe_domain: None,
         };
         let actual = uc.get_token().await.unwrap();
         assert_eq!(actual, expected);
@@ -306,6 +354,7 @@ mod test {
         let uc = UserCredential {
             token_provider: mock,
             quota_project_id: None,
+            universe_domain: None,
         };
         assert!(uc.get_token().await.is_err());
     }
@@ -333,6 +382,7 @@ mod test {
         let uc = UserCredential {
             token_provider: mock,
             quota_project_id: None,
+            universe_domain: None,
         };
         let headers: Vec<HV> = uc
             .get_headers()
@@ -366,6 +416,7 @@ mod test {
         let uc = UserCredential {
             token_provider: mock,
             quota_project_id: None,
+            universe_domain: None,
         };
         assert!(uc.get_headers().await.is_err());
     }
@@ -385,6 +436,7 @@ mod test {
         let uc = UserCredential {
             token_provider: mock,
             quota_project_id: Some("test-project".to_string()),
+            universe_domain: None,
         };
         let mut headers: Vec<HV> = uc
             .get_headers()
@@ -424,6 +476,7 @@ mod test {
             client_id: "test-client-id".to_string(),
             client_secret: "test-client-secret".to_string(),
             refresh_token: "test-refresh-token".to_string(),
+            scope: None,
         };
 
         let json = serde_json::to_value(&request).unwrap();
@@ -446,6 +499,7 @@ mod test {
             expires_in: Some(3600),
             token_type: "test-token-type".to_string(),
             refresh_token: Some("test-refresh-token".to_string()),
+            id_token: None,
         };
 
         let json = serde_json::to_value(&response).unwrap();
@@ -469,6 +523,7 @@ mod test {
             expires_in: None,
             token_type: "test-token-type".to_string(),
             refresh_token: None,
+            id_token: None,
         };
 
         let json = serde_json::to_value(&response).unwrap();
@@ -522,6 +577,7 @@ mod test {
             access_token: "test-access-token".to_string(),
             expires_in: Some(3600),
             refresh_token: Some("test-refresh-token".to_string()),
+            id_token: Some(fake_id_token("user@example.com")),
             scope: Some("scope1 scope2".to_string()),
             token_type: "test-token-type".to_string(),
         };
@@ -534,10 +590,12 @@ mod test {
             client_secret: "test-client-secret".to_string(),
             refresh_token: "test-refresh-token".to_string(),
             endpoint: endpoint,
+            scopes: None,
         };
         let uc = UserCredential {
             token_provider,
             quota_project_id: None,
+            universe_domain: None,
         };
         let now = OffsetDateTime::now_utc();
         let token = uc.get_token().await?;
@@ -546,16 +604,28 @@ mod test {
         assert!(token
             .expires_at
             .is_some_and(|d| d >= now + Duration::from_secs(3600)));
+        assert_eq!(token.scopes(), Some(vec!["scope1", "scope2"]));
+        assert_eq!(token.source_type(), Some("authorized_user"));
+        assert_eq!(token.principal_email(), Some("user@example.com"));
 
         Ok(())
     }
 
+    // Creates an unsigned ID token with the given email.
+    fn fake_id_token(email: &str) -> String {
+        use base64::prelude::{Engine as _, BASE64_URL_SAFE_NO_PAD};
+        let claims = serde_json::json!({"exp": 0, "email": email});
+        let claims = BASE64_URL_SAFE_NO_PAD.encode(claims.to_string());
+        format!("e30.{claims}.c2lnbmF0dXJl")
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     async fn token_provider_partial() -> TestResult {
         let response = Oauth2RefreshResponse {
             access_token: "test-access-token".to_string(),
             expires_in: None,
             refresh_token: None,
+            id_token: None,
             scope: None,
             token_type: "test-token-type".to_string(),
         };
@@ -568,15 +638,20 @@ mod test {
             client_secret: "test-client-secret".to_string(),
             refresh_token: "test-refresh-token".to_string(),
             endpoint: endpoint,
+            scopes: None,
         };
         let uc = UserCredential {
             token_provider,
             quota_project_id: None,
+            universe_domain: None,
         };
         let token = uc.get_token().await?;
         assert_eq!(token.token, "test-access-token");
         assert_eq!(token.token_type, "test-token-type");
         assert_eq!(token.expires_at, None);
+        assert_eq!(token.scopes(), None);
+        assert_eq!(token.source_type(), Some("authorized_user"));
+        assert_eq!(token.principal_email(), None);
 
         Ok(())
     }
@@ -592,10 +667,12 @@ mod test {
             client_secret: "test-client-secret".to_string(),
             refresh_token: "test-refresh-token".to_string(),
             endpoint: endpoint,
+            scopes: None,
         };
         let uc = UserCredential {
             token_provider,
             quota_project_id: None,
+            universe_domain: None,
         };
         let e = uc.get_token().await.err().unwrap();
         assert!(e.is_retryable());
@@ -614,10 +691,12 @@ mod test {
             client_secret: "test-client-secret".to_string(),
             refresh_token: "test-refresh-token".to_string(),
             endpoint: endpoint,
+            scopes: None,
         };
         let uc = UserCredential {
             token_provider,
             quota_project_id: None,
+            universe_domain: None,
         };
         let e = uc.get_token().await.err().unwrap();
         assert!(!e.is_retryable());
@@ -636,14 +715,88 @@ mod test {
             client_secret: "test-client-secret".to_string(),
             refresh_token: "test-refresh-token".to_string(),
             endpoint: endpoint,
+            scopes: None,
         };
         let uc = UserCredential {
             token_provider,
             quota_project_id: None,
+            universe_domain: None,
         };
         let e = uc.get_token().await.err().unwrap();
         assert!(!e.is_retryable());
 
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn creds_from_with_options() -> TestResult {
+        let handler = |Json(request): Json<Oauth2RefreshRequest>| async move {
+            assert_eq!(request.scope.as_deref(), Some("scope1 scope2"));
+            let response = Oauth2RefreshResponse {
+                access_token: "test-access-token".to_string(),
+                expires_in: Some(3600),
+                refresh_token: None,
+                id_token: None,
+                scope: request.scope,
+                token_type: "Bearer".to_string(),
+            };
+            (StatusCode::OK, serde_json::to_string(&response).unwrap())
+        };
+        let app = axum::Router::new().route("/token", axum::routing::post(handler));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let _server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let json = serde_json::json!({
+            "client_id": "test-client-id",
+            "client_secret": "test-client-secret",
+            "refresh_token": "test-refresh-token",
+            "type": "authorized_user",
+            "quota_project_id": "test-project",
+        });
+        let options = CredentialOptions {
+            scopes: Some(vec!["scope1".to_string(), "scope2".to_string()]),
+            quota_project_id: Some("override-project".to_string()),
+            universe_domain: Some("test-universe-domain".to_string()),
+            token_endpoint: Some(format!("http://{addr}/token")),
+            ..Default::default()
+        };
+        let creds = creds_from(json, &options)?;
+        let token = creds.get_token().await?;
+        assert_eq!(token.scopes(), Some(vec!["scope1", "scope2"]));
+        let headers = creds.get_headers().await?;
+        let quota_project = headers
+            .iter()
+            .find(|(k, _)| k == QUOTA_PROJECT_KEY)
+            .map(|(_, v)| v.to_str().unwrap().to_string());
+        assert_eq!(quota_project.as_deref(), Some("override-project"));
+        assert!(headers
+            .iter()
+            .any(|(k, v)| k == AUTHORIZATION && v == "Bearer test-access-token"));
+        assert_eq!(
+            creds.get_universe_domain().await.as_deref(),
+            Some("test-universe-domain")
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn creds_from_defaults() -> TestResult {
+        let json = serde_json::json!({
+            "client_id": "test-client-id",
+            "client_secret": "test-client-secret",
+            "refresh_token": "test-refresh-token",
+            "type": "authorized_user",
+        });
+        let creds = creds_from(json, &CredentialOptions::default())?;
+        let fmt = format!("{creds:?}");
+        assert!(fmt.contains(OAUTH2_ENDPOINT), "{fmt}");
+        assert_eq!(
+            creds.get_universe_domain().await.as_deref(),
+            Some(DEFAULT_UNIVERSE_DOMAIN)
+        );
+        Ok(())
+    }
 }
@@ -12,4 +12,88 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::credentials::{Result, QUOTA_PROJECT_KEY};
+use crate::errors::CredentialError;
+use crate::token::Token;
+use http::header::{HeaderName, HeaderValue, AUTHORIZATION};
+
+pub(crate) mod http_client;
 pub(crate) mod jws;
+pub(crate) mod sts;
+
+/// The scopes used when the application does not configure any.
+pub(crate) const DEFAULT_SCOPES: [&str; 1] = ["https://www.googleapis.com/auth/cloud-platform"];
+
+/// The universe domain used when the credentials do not configure one.
+pub(crate) const DEFAULT_UNIVERSE_DOMAIN: &str = "googleapis.com";
+
+/// The default endpoint for the IAM Credentials API.
+pub(crate) const IAM_CREDENTIALS_ENDPOINT: &str = "https://iamcredentials.googleapis.com";
+
+/// Returns the request headers for `token`.
+///
+/// The token is sent in the `Authorization` header, marked as sensitive. The
+/// quota project, if any, is sent in the `x-goog-user-project` header.
+pub(crate) fn build_headers(
+    token: &Token,
+    quota_project_id: Option<&str>,
+) -> Result<Vec<(HeaderName, HeaderValue)>> {
+    let mut value = HeaderValue::from_str(&format!("{} {}", token.token_type, token.token))
+        .map_err(CredentialError::non_retryable)?;
+    value.set_sensitive(true);
+    let mut headers = vec![(AUTHORIZATION, value)];
+    if let Some(project) = quota_project_id {
+        headers.push((
+            HeaderName::from_static(QUOTA_PROJECT_KEY),
+            HeaderValue::from_str(project).map_err(CredentialError::non_retryable)?,
+        ));
+    }
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    fn token() -> Token {
+        Token {
+            token: "test-token".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_at: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn build_headers_success() -> TestResult {
+        let headers = build_headers(&token(), None)?;
+        assert_eq!(
+            headers,
+            vec![(AUTHORIZATION, HeaderValue::from_static("Bearer test-token"))]
+        );
+        assert!(headers[0].1.is_sensitive());
+
+        let headers = build_headers(&token(), Some("test-project"))?;
+        assert_eq!(
+            headers,
+            vec![
+                (AUTHORIZATION, HeaderValue::from_static("Bearer test-token")),
+                (
+                    HeaderName::from_static(QUOTA_PROJECT_KEY),
+                    HeaderValue::from_static("test-project")
+                ),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn build_headers_invalid() {
+        let mut invalid = token();
+        invalid.token = "bad\ntoken".to_string();
+        assert!(build_headers(&invalid, None).is_err());
+        assert!(build_headers(&token(), Some("bad\nproject")).is_err());
+    }
+}
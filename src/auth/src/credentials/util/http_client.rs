@@ -0,0 +1,29 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The HTTP client shared by all token requests.
+
+use reqwest::Client;
+use std::sync::LazyLock;
+
+// `reqwest::Client` holds a connection pool. Sharing one client reuses
+// connections (and TLS sessions) across credentials and token refreshes.
+static CLIENT: LazyLock<Client> = LazyLock::new(Client::new);
+
+/// Returns the HTTP client for token requests.
+///
+/// Cloning the client is cheap, clones share the connection pool.
+pub(crate) fn client() -> Client {
+    CLIENT.clone()
+}
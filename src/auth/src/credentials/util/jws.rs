@@ -34,7 +34,7 @@ pub(crate) const DEFAULT_TOKEN_TIMEOUT: Duration = Duration::from_secs(3600);
 pub struct JwsClaims<'a> {
     pub iss: &'a str,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub scope: Option<Vec<&'a str>>,
+    pub scope: Option<String>,
     pub aud: Option<&'a str>,
     #[serde(with = "time::serde::timestamp::option")]
     pub exp: Option<OffsetDateTime>,
@@ -44,10 +44,60 @@ pub struct JwsClaims<'a> {
     pub typ: Option<&'a str>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sub: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_audience: Option<&'a str>,
 }
 
 impl JwsClaims<'_> {
+    /// Encodes the claims for a [self-signed JWT].
+    ///
+    /// Self-signed JWTs are scoped either by audience or by scopes, but not
+    /// both.
+    ///
+    /// [self-signed JWT]: https://google.aip.dev/auth/4111
     pub fn encode(&self) -> Result<String> {
+        if self.aud.is_some() && self.scope.is_some() {
+            return Err(CredentialError::non_retryable(format!(
+                "Found {:?} for audience and {:?} for scope, however expecting only 1 of them to be set.",
+                self.aud, self.scope
+            )));
+        }
+        self.encode_claims()
+    }
+
+    /// Encodes the claims for an assertion in the [JWT bearer grant].
+    ///
+    /// The audience of these assertions is always the token endpoint, and
+    /// the assertion must include the requested scopes.
+    ///
+    /// [JWT bearer grant]: https://datatracker.ietf.org/doc/html/rfc7523
+    pub fn encode_assertion(&self) -> Result<String> {
+        if self.aud.is_none() || self.scope.is_none() {
+            return Err(CredentialError::non_retryable(format!(
+                "Found {:?} for audience and {:?} for scope, however expecting both of them to be set.",
+                self.aud, self.scope
+            )));
+        }
+        self.encode_claims()
+    }
+
+    /// Encodes the claims for an assertion exchanged for an [ID token].
+    ///
+    /// The audience of these assertions is always the token endpoint. The
+    /// `target_audience` is the audience of the ID token.
+    ///
+    /// [ID token]: https://cloud.google.com/docs/authentication/token-types#identity-tokens
+    pub fn encode_id_token_assertion(&self) -> Result<String> {
+        if self.aud.is_none() || self.target_audience.is_none() || self.scope.is_some() {
+            return Err(CredentialError::non_retryable(format!(
+                "Found {:?} for audience, {:?} for target audience, and {:?} for scope, however expecting only the audience and target audience to be set.",
+                self.aud, self.target_audience, self.scope
+            )));
+        }
+        self.encode_claims()
+    }
+
+    fn encode_claims(&self) -> Result<String> {
         let now = OffsetDateTime::now_utc() - CLOCK_SKEW_FUDGE;
         let iat = self.iat.unwrap_or(now);
         let exp = self.exp.unwrap_or_else(|| now + DEFAULT_TOKEN_TIMEOUT);
@@ -58,13 +108,6 @@ impl JwsClaims<'_> {
             )));
         }
 
-        if self.aud.is_some() && self.scope.is_some() {
-            return Err(CredentialError::non_retryable(format!(
-                "Found {:?} for audience and {:?} for scope, however expecting only 1 of them to be set.",
-                self.aud, self.scope
-            )));
-        }
-
         let updated_jws_claim = JwsClaims {
             iat: Some(iat),
             exp: Some(exp),
@@ -151,7 +194,7 @@ mod tests {
             .exp(exp_custom)
             .typ("test_typ")
             .sub("test_sub")
-            .scope(vec!["scope1", "scope2"])
+            .scope("scope1 scope2")
             .build()
             .unwrap();
 
@@ -165,7 +208,7 @@ mod tests {
         let v: Value = serde_json::from_str(&decoded).unwrap();
 
         assert_eq!(v["iss"], "test_iss");
-        assert_eq!(v["scope"], serde_json::json!(["scope1", "scope2"]));
+        assert_eq!(v["scope"], "scope1 scope2");
 
         assert_eq!(v["iat"], iat_custom.unix_timestamp());
         assert_eq!(v["exp"], exp_custom.unix_timestamp());
@@ -190,7 +233,7 @@ mod tests {
     fn test_jws_claims_encode_error_set_scope_and_aud() {
         let claims = JwsClaimsBuilder::default()
             .iss("test_iss")
-            .scope(vec!["scope1", "scope2"])
+            .scope("scope1 scope2")
             .aud("test_aud")
             .build()
             .unwrap();
@@ -200,6 +243,81 @@ mod tests {
             .is_err_and(|e| e.to_string().contains(expected_error_message)));
     }
 
+    #[test]
+    fn test_jws_claims_encode_assertion() {
+        let claims = JwsClaimsBuilder::default()
+            .iss("test_iss")
+            .scope("scope1 scope2")
+            .aud("test_aud")
+            .build()
+            .unwrap();
+        let encoded = claims.encode_assertion().unwrap();
+        let decoded = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(encoded)
+            .unwrap();
+        let v: Value = serde_json::from_slice(&decoded).unwrap();
+        assert_eq!(v["iss"], "test_iss");
+        assert_eq!(v["scope"], "scope1 scope2");
+        assert_eq!(v["aud"], "test_aud");
+    }
+
+    #[test]
+    fn test_jws_claims_encode_assertion_error_missing_scope_or_aud() {
+        let expected_error_message = "expecting both of them to be set";
+        let claims = JwsClaimsBuilder::default()
+            .iss("test_iss")
+            .aud("test_aud")
+            .build()
+            .unwrap();
+        assert!(claims
+            .encode_assertion()
+            .is_err_and(|e| e.to_string().contains(expected_error_message)));
+
+        let claims = JwsClaimsBuilder::default()
+            .iss("test_iss")
+            .scope("scope1 scope2")
+            .build()
+            .unwrap();
+        assert!(claims
+            .encode_assertion()
+            .is_err_and(|e| e.to_string().contains(expected_error_message)));
+    }
+
+    #[test]
+    fn test_jws_claims_encode_id_token_assertion() {
+        let claims = JwsClaimsBuilder::default()
+            .iss("test_iss")
+            .aud("test_aud")
+            .target_audience("https://example.com")
+            .build()
+            .unwrap();
+        let encoded = claims.encode_id_token_assertion().unwrap();
+        let decoded = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(encoded)
+            .unwrap();
+        let v: Value = serde_json::from_slice(&decoded).unwrap();
+        assert_eq!(v["iss"], "test_iss");
+        assert_eq!(v["aud"], "test_aud");
+        assert_eq!(v["target_audience"], "https://example.com");
+        assert_eq!(v.get("scope"), None);
+
+        let claims = JwsClaimsBuilder::default()
+            .iss("test_iss")
+            .aud("test_aud")
+            .build()
+            .unwrap();
+        assert!(claims.encode_id_token_assertion().is_err());
+
+        let claims = JwsClaimsBuilder::default()
+            .iss("test_iss")
+            .aud("test_aud")
+            .target_audience("https://example.com")
+            .scope("scope1")
+            .build()
+            .unwrap();
+        assert!(claims.encode_id_token_assertion().is_err());
+    }
+
     #[test]
     fn test_jws_header_encode() {
         let header = JwsHeader {
@@ -0,0 +1,310 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A client for the [Security Token Service] (STS).
+//!
+//! STS implements the [RFC 8693] OAuth 2.0 token exchange, used by several
+//! credential types to exchange one token for a Google Cloud access token.
+//!
+//! [Security Token Service]: https://cloud.google.com/iam/docs/reference/sts/rest
+//! [RFC 8693]: https://www.rfc-editor.org/rfc/rfc8693
+
+use crate::credentials::util::http_client;
+use crate::errors::{is_retryable, CredentialError};
+use crate::token::Token;
+use crate::Result;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+pub(crate) const STS_ENDPOINT: &str = "https://sts.googleapis.com/v1/token";
+
+pub(crate) const TOKEN_EXCHANGE_GRANT_TYPE: &str =
+    "urn:ietf:params:oauth:grant-type:token-exchange";
+
+pub(crate) const ACCESS_TOKEN_TYPE: &str = "urn:ietf:params:oauth:token-type:access_token";
+
+pub(crate) const ACCESS_BOUNDARY_INTERMEDIARY_TOKEN_TYPE: &str =
+    "urn:ietf:params:oauth:token-type:access_boundary_intermediary_token";
+
+/// The parameters of a token exchange request.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct ExchangeTokenRequest {
+    pub(crate) audience: Option<String>,
+    pub(crate) scope: Vec<String>,
+    pub(crate) requested_token_type: String,
+    pub(crate) subject_token: String,
+    pub(crate) subject_token_type: String,
+    /// Additional options, as a JSON object serialized to a string.
+    pub(crate) options: Option<String>,
+    /// The client id and secret, sent using HTTP basic authentication.
+    pub(crate) client_auth: Option<(String, String)>,
+}
+
+/// The successful response to a token exchange request.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub(crate) struct ExchangeTokenResponse {
+    pub(crate) access_token: String,
+    pub(crate) issued_token_type: String,
+    pub(crate) token_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) expires_in: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) refresh_token: Option<String>,
+    /// The key to encrypt access boundaries, only returned with
+    /// intermediary tokens for client-side downscoping.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) access_boundary_session_key: Option<String>,
+}
+
+impl ExchangeTokenResponse {
+    pub(crate) fn into_token(self) -> Token {
+        Token {
+            token: self.access_token,
+            token_type: self.token_type,
+            expires_at: self
+                .expires_in
+                .map(|d| OffsetDateTime::now_utc() + Duration::from_secs(d)),
+            metadata: None,
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct ExchangeTokenForm<'a> {
+    grant_type: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    audience: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+    requested_token_type: &'a str,
+    subject_token: &'a str,
+    subject_token_type: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<&'a str>,
+}
+
+/// Exchanges a token using the STS endpoint at `url`.
+pub(crate) async fn exchange_token(
+    url: &str,
+    request: &ExchangeTokenRequest,
+) -> Result<ExchangeTokenResponse> {
+    let form = ExchangeTokenForm {
+        grant_type: TOKEN_EXCHANGE_GRANT_TYPE,
+        audience: request.audience.as_deref(),
+        scope: (!request.scope.is_empty()).then(|| request.scope.join(" ")),
+        requested_token_type: &request.requested_token_type,
+        subject_token: &request.subject_token,
+        subject_token_type: &request.subject_token_type,
+        options: request.options.as_deref(),
+    };
+    let client = http_client::client();
+    let mut builder = client.post(url).form(&form);
+    if let Some((id, secret)) = &request.client_auth {
+        builder = builder.basic_auth(id, Some(secret));
+    }
+    let resp = builder.send().await.map_err(CredentialError::retryable)?;
+
+    // Process the response
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| CredentialError::new(is_retryable(status), e.into()))?;
+        return Err(CredentialError::new(
+            is_retryable(status),
+            Box::from(format!("Failed to exchange token. {body}")),
+        ));
+    }
+    resp.json::<ExchangeTokenResponse>().await.map_err(|e| {
+        let retryable = !e.is_decode();
+        CredentialError::new(retryable, e.into())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::extract::Form;
+    use http::{header::AUTHORIZATION, HeaderMap, StatusCode};
+    use std::collections::HashMap;
+    use std::error::Error;
+    use tokio::task::JoinHandle;
+
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    // Starts a server running locally. Returns an (endpoint, server) pair.
+    async fn start(
+        response_code: StatusCode,
+        response_body: String,
+        expected_form: HashMap<String, String>,
+        expected_auth: Option<String>,
+    ) -> (String, JoinHandle<()>) {
+        let handler = move |headers: HeaderMap, Form(form): Form<HashMap<String, String>>| async move {
+            assert_eq!(form, expected_form);
+            assert_eq!(
+                headers
+                    .get(AUTHORIZATION)
+                    .map(|v| v.to_str().unwrap().to_string()),
+                expected_auth
+            );
+            (response_code, response_body)
+        };
+        let app = axum::Router::new().route("/v1/token", axum::routing::post(handler));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        (
+            format!("http://{}:{}/v1/token", addr.ip(), addr.port()),
+            server,
+        )
+    }
+
+    fn form(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn test_response() -> ExchangeTokenResponse {
+        ExchangeTokenResponse {
+            access_token: "test-access-token".to_string(),
+            issued_token_type: ACCESS_TOKEN_TYPE.to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in: Some(3600),
+            refresh_token: None,
+            access_boundary_session_key: None,
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn exchange_success() -> TestResult {
+        let expected_form = form(&[
+            ("grant_type", TOKEN_EXCHANGE_GRANT_TYPE),
+            ("audience", "test-audience"),
+            ("scope", "scope1 scope2"),
+            ("requested_token_type", ACCESS_TOKEN_TYPE),
+            ("subject_token", "test-subject-token"),
+            ("subject_token_type", "urn:ietf:params:oauth:token-type:jwt"),
+            ("options", r#"{"userProject":"test-project"}"#),
+        ]);
+        let (url, _server) = start(
+            StatusCode::OK,
+            serde_json::to_string(&test_response())?,
+            expected_form,
+            None,
+        )
+        .await;
+        let request = ExchangeTokenRequest {
+            audience: Some("test-audience".to_string()),
+            scope: vec!["scope1".to_string(), "scope2".to_string()],
+            requested_token_type: ACCESS_TOKEN_TYPE.to_string(),
+            subject_token: "test-subject-token".to_string(),
+            subject_token_type: "urn:ietf:params:oauth:token-type:jwt".to_string(),
+            options: Some(r#"{"userProject":"test-project"}"#.to_string()),
+            client_auth: None,
+        };
+        let response = exchange_token(&url, &request).await?;
+        assert_eq!(response, test_response());
+
+        let now = OffsetDateTime::now_utc();
+        let token = response.into_token();
+        assert_eq!(token.token, "test-access-token");
+        assert_eq!(token.token_type, "Bearer");
+        assert!(token
+            .expires_at
+            .is_some_and(|e| e > now + Duration::from_secs(3500)));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn exchange_with_client_auth() -> TestResult {
+        let expected_form = form(&[
+            ("grant_type", TOKEN_EXCHANGE_GRANT_TYPE),
+            ("requested_token_type", ACCESS_TOKEN_TYPE),
+            ("subject_token", "test-subject-token"),
+            ("subject_token_type", ACCESS_TOKEN_TYPE),
+        ]);
+        // base64("client-id:client-secret")
+        let expected_auth = Some("Basic Y2xpZW50LWlkOmNsaWVudC1zZWNyZXQ=".to_string());
+        let (url, _server) = start(
+            StatusCode::OK,
+            serde_json::to_string(&test_response())?,
+            expected_form,
+            expected_auth,
+        )
+        .await;
+        let request = ExchangeTokenRequest {
+            requested_token_type: ACCESS_TOKEN_TYPE.to_string(),
+            subject_token: "test-subject-token".to_string(),
+            subject_token_type: ACCESS_TOKEN_TYPE.to_string(),
+            client_auth: Some(("client-id".to_string(), "client-secret".to_string())),
+            ..Default::default()
+        };
+        let response = exchange_token(&url, &request).await?;
+        assert_eq!(response, test_response());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn exchange_errors() -> TestResult {
+        let request = ExchangeTokenRequest {
+            requested_token_type: ACCESS_TOKEN_TYPE.to_string(),
+            subject_token: "test-subject-token".to_string(),
+            subject_token_type: ACCESS_TOKEN_TYPE.to_string(),
+            ..Default::default()
+        };
+        let expected_form = form(&[
+            ("grant_type", TOKEN_EXCHANGE_GRANT_TYPE),
+            ("requested_token_type", ACCESS_TOKEN_TYPE),
+            ("subject_token", "test-subject-token"),
+            ("subject_token_type", ACCESS_TOKEN_TYPE),
+        ]);
+
+        let (url, _server) = start(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "try again".to_string(),
+            expected_form.clone(),
+            None,
+        )
+        .await;
+        let e = exchange_token(&url, &request).await.err().unwrap();
+        assert!(e.is_retryable(), "{e}");
+        assert!(e.source().unwrap().to_string().contains("try again"), "{e}");
+
+        let (url, _server) = start(
+            StatusCode::BAD_REQUEST,
+            "invalid_grant".to_string(),
+            expected_form.clone(),
+            None,
+        )
+        .await;
+        let e = exchange_token(&url, &request).await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(
+            e.source().unwrap().to_string().contains("invalid_grant"),
+            "{e}"
+        );
+
+        let (url, _server) =
+            start(StatusCode::OK, "bad json".to_string(), expected_form, None).await;
+        let e = exchange_token(&url, &request).await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        Ok(())
+    }
+}
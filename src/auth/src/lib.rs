@@ -47,6 +47,12 @@ pub mod credentials;
 /// [Tokens]: https://cloud.google.com/docs/authentication#token
 pub mod token;
 
+/// Retry, backoff, and timeout policies for token requests.
+pub mod retry;
+
+/// Caches the tokens returned by a [token::TokenProvider].
+pub(crate) mod token_cache;
+
 /// A `Result` alias where the `Err` case is
 /// `gcp-sdk-auth::errors::CredentialError`.
 pub(crate) type Result<T> = std::result::Result<T, crate::errors::CredentialError>;
@@ -0,0 +1,533 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::errors::CredentialError;
+use crate::token::{Token, TokenProvider};
+use crate::Result;
+use rand::Rng;
+use std::time::Duration;
+use tokio::time::Instant;
+
+const DEFAULT_MAXIMUM_ATTEMPTS: u32 = 5;
+const DEFAULT_MAXIMUM_DURATION: Duration = Duration::from_secs(60);
+const DEFAULT_ATTEMPT_TIMEOUT: Duration = Duration::from_secs(30);
+
+const DEFAULT_INITIAL_DELAY: Duration = Duration::from_millis(100);
+const DEFAULT_MAXIMUM_DELAY: Duration = Duration::from_secs(5);
+const DEFAULT_SCALING: f64 = 2.0;
+
+/// Controls how many times, and for how long, token requests are retried.
+///
+/// Only errors where [CredentialError::is_retryable] returns `true` are
+/// retried. When the policy is exhausted, the last error is returned.
+///
+/// The default policy makes at most 5 attempts, stops retrying after 60
+/// seconds, and times out each attempt after 30 seconds.
+///
+/// # Example
+/// ```
+/// # use gcp_sdk_auth::credentials::Builder;
+/// # use gcp_sdk_auth::retry::RetryPolicy;
+/// # use std::time::Duration;
+/// let builder = Builder::new().with_retry_policy(
+///     RetryPolicy::new()
+///         .with_maximum_attempts(10)
+///         .with_attempt_timeout(Duration::from_secs(5)),
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    maximum_attempts: u32,
+    maximum_duration: Option<Duration>,
+    attempt_timeout: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            maximum_attempts: DEFAULT_MAXIMUM_ATTEMPTS,
+            maximum_duration: Some(DEFAULT_MAXIMUM_DURATION),
+            attempt_timeout: Some(DEFAULT_ATTEMPT_TIMEOUT),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates the default policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a policy that makes a single attempt, without a timeout.
+    pub fn never() -> Self {
+        Self {
+            maximum_attempts: 1,
+            maximum_duration: None,
+            attempt_timeout: None,
+        }
+    }
+
+    /// Sets the maximum number of attempts, including the first one.
+    ///
+    /// Values smaller than 1 are treated as 1.
+    pub fn with_maximum_attempts(mut self, v: u32) -> Self {
+        self.maximum_attempts = v.max(1);
+        self
+    }
+
+    /// Stops retrying once this much time has elapsed since the first attempt.
+    pub fn with_maximum_duration(mut self, v: Duration) -> Self {
+        self.maximum_duration = Some(v);
+        self
+    }
+
+    /// Sets the timeout for each attempt.
+    ///
+    /// An attempt that times out is a retryable error.
+    pub fn with_attempt_timeout(mut self, v: Duration) -> Self {
+        self.attempt_timeout = Some(v);
+        self
+    }
+
+    // The timeout for the next attempt, bounded by the remaining time.
+    fn attempt_timeout(&self, loop_start: Instant) -> Option<Duration> {
+        let remaining = self
+            .maximum_duration
+            .map(|d| d.saturating_sub(loop_start.elapsed()));
+        match (self.attempt_timeout, remaining) {
+            (Some(t), Some(r)) => Some(t.min(r)),
+            (t, r) => t.or(r),
+        }
+    }
+}
+
+/// Controls the delay between attempts of a token request.
+///
+/// The delay grows exponentially with each attempt, up to a maximum. Each
+/// delay is randomized (with "full jitter"), to avoid synchronized retries
+/// from many clients.
+///
+/// The default backoff starts with 100 milliseconds, doubles on each attempt,
+/// and never exceeds 5 seconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExponentialBackoff {
+    initial_delay: Duration,
+    maximum_delay: Duration,
+    scaling: f64,
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> Self {
+        Self {
+            initial_delay: DEFAULT_INITIAL_DELAY,
+            maximum_delay: DEFAULT_MAXIMUM_DELAY,
+            scaling: DEFAULT_SCALING,
+        }
+    }
+}
+
+impl ExponentialBackoff {
+    /// Creates the default backoff.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the delay after the first attempt.
+    pub fn with_initial_delay(mut self, v: Duration) -> Self {
+        self.initial_delay = v;
+        self
+    }
+
+    /// Sets the maximum delay between attempts.
+    pub fn with_maximum_delay(mut self, v: Duration) -> Self {
+        self.maximum_delay = v;
+        self
+    }
+
+    /// Sets the factor applied to the delay after each attempt.
+    ///
+    /// Values smaller than 1.0 are treated as 1.0.
+    pub fn with_scaling(mut self, v: f64) -> Self {
+        self.scaling = v;
+        self
+    }
+
+    // The upper bound for the delay after `attempt_count` attempts.
+    fn maximum_delay_after(&self, attempt_count: u32) -> Duration {
+        let exponent = attempt_count.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay = self.initial_delay.as_secs_f64() * self.scaling.max(1.0).powi(exponent);
+        Duration::from_secs_f64(delay.min(self.maximum_delay.as_secs_f64()))
+    }
+
+    fn delay(&self, attempt_count: u32) -> Duration {
+        let delay = self.maximum_delay_after(attempt_count);
+        rand::thread_rng().gen_range(Duration::ZERO..=delay)
+    }
+}
+
+/// A [TokenProvider] retrying the requests of another provider.
+#[derive(Debug)]
+pub(crate) struct RetryTokenProvider<T> {
+    inner: T,
+    policy: RetryPolicy,
+    backoff: ExponentialBackoff,
+}
+
+impl<T> RetryTokenProvider<T>
+where
+    T: TokenProvider,
+{
+    pub(crate) fn new(inner: T, policy: RetryPolicy, backoff: ExponentialBackoff) -> Self {
+        Self {
+            inner,
+            policy,
+            backoff,
+        }
+    }
+
+    /// Retries `inner` with the default policies.
+    pub(crate) fn with_defaults(inner: T) -> Self {
+        Self::new(inner, RetryPolicy::default(), ExponentialBackoff::default())
+    }
+
+    async fn attempt(&self, timeout: Option<Duration>) -> Result<Token> {
+        let Some(timeout) = timeout else {
+            return self.inner.get_token().await;
+        };
+        match tokio::time::timeout(timeout, self.inner.get_token()).await {
+            Ok(result) => result,
+            Err(_) => Err(CredentialError::retryable(format!(
+                "the token request timed out after {timeout:?}"
+            ))),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> TokenProvider for RetryTokenProvider<T>
+where
+    T: TokenProvider,
+{
+    async fn get_token(&self) -> Result<Token> {
+        let provider = std::any::type_name::<T>();
+        let start = Instant::now();
+        tracing::debug!(provider, "token refresh started");
+        let result = self.retry_loop(provider, start).await;
+        let elapsed_ms = start.elapsed().as_millis() as u64;
+        // Never record the token itself, only what we know about it.
+        match &result {
+            Ok(token) => tracing::info!(
+                provider,
+                elapsed_ms,
+                source_type = token.source_type(),
+                expires_at = ?token.expires_at,
+                "token refresh succeeded"
+            ),
+            Err(e) => tracing::warn!(
+                provider,
+                elapsed_ms,
+                retryable = e.is_retryable(),
+                error = %e,
+                "token refresh failed"
+            ),
+        }
+        result
+    }
+}
+
+impl<T> RetryTokenProvider<T>
+where
+    T: TokenProvider,
+{
+    async fn retry_loop(&self, provider: &str, loop_start: Instant) -> Result<Token> {
+        let mut attempt_count = 0;
+        loop {
+            attempt_count += 1;
+            let error = match self.attempt(self.policy.attempt_timeout(loop_start)).await {
+                Ok(token) => return Ok(token),
+                Err(e) if !e.is_retryable() => return Err(e),
+                Err(e) => e,
+            };
+            if attempt_count >= self.policy.maximum_attempts {
+                return Err(error);
+            }
+            let delay = self.backoff.delay(attempt_count);
+            if self
+                .policy
+                .maximum_duration
+                .is_some_and(|d| loop_start.elapsed() + delay >= d)
+            {
+                return Err(error);
+            }
+            tracing::debug!(
+                provider,
+                attempt_count,
+                delay = ?delay,
+                error = %error,
+                "token refresh attempt failed, retrying"
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::token::test::MockTokenProvider;
+    use std::error::Error;
+
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    fn token() -> Token {
+        Token {
+            token: "test-token".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_at: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn backoff_bounds() {
+        let backoff = ExponentialBackoff::new()
+            .with_initial_delay(Duration::from_secs(1))
+            .with_maximum_delay(Duration::from_secs(5))
+            .with_scaling(2.0);
+        assert_eq!(backoff.maximum_delay_after(1), Duration::from_secs(1));
+        assert_eq!(backoff.maximum_delay_after(2), Duration::from_secs(2));
+        assert_eq!(backoff.maximum_delay_after(3), Duration::from_secs(4));
+        assert_eq!(backoff.maximum_delay_after(4), Duration::from_secs(5));
+        assert_eq!(
+            backoff.maximum_delay_after(u32::MAX),
+            Duration::from_secs(5)
+        );
+        for attempt in 1..10 {
+            let delay = backoff.delay(attempt);
+            assert!(delay <= backoff.maximum_delay_after(attempt), "{delay:?}");
+        }
+
+        let backoff = backoff.with_scaling(0.5);
+        assert_eq!(backoff.maximum_delay_after(3), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn policy_attempt_timeout() {
+        let start = Instant::now();
+        let policy = RetryPolicy::never();
+        assert_eq!(policy.attempt_timeout(start), None);
+        let policy = policy.with_attempt_timeout(Duration::from_secs(5));
+        assert_eq!(policy.attempt_timeout(start), Some(Duration::from_secs(5)));
+        let policy = RetryPolicy::never().with_maximum_duration(Duration::from_secs(2));
+        assert!(policy
+            .attempt_timeout(start)
+            .is_some_and(|t| t <= Duration::from_secs(2)));
+        assert_eq!(
+            RetryPolicy::never()
+                .with_maximum_attempts(0)
+                .maximum_attempts,
+            1
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_success() -> TestResult {
+        let mut mock = MockTokenProvider::new();
+        let mut seq = mockall::Sequence::new();
+        mock.expect_get_token()
+            .times(2)
+            .in_sequence(&mut seq)
+            .returning(|| Err(CredentialError::retryable("try again")));
+        mock.expect_get_token()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|| Ok(token()));
+        let provider = RetryTokenProvider::with_defaults(mock);
+        assert_eq!(provider.get_token().await?, token());
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn permanent_error() -> TestResult {
+        let mut mock = MockTokenProvider::new();
+        mock.expect_get_token()
+            .times(1)
+            .returning(|| Err(CredentialError::non_retryable("epic fail")));
+        let provider = RetryTokenProvider::with_defaults(mock);
+        let e = provider.get_token().await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(e.source().unwrap().to_string().contains("epic fail"), "{e}");
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn attempts_exhausted() -> TestResult {
+        let mut mock = MockTokenProvider::new();
+        mock.expect_get_token()
+            .times(3)
+            .returning(|| Err(CredentialError::retryable("try again")));
+        let provider = RetryTokenProvider::new(
+            mock,
+            RetryPolicy::new().with_maximum_attempts(3),
+            ExponentialBackoff::default(),
+        );
+        let e = provider.get_token().await.err().unwrap();
+        assert!(e.is_retryable(), "{e}");
+        assert!(e.source().unwrap().to_string().contains("try again"), "{e}");
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn duration_exhausted() -> TestResult {
+        let mut mock = MockTokenProvider::new();
+        mock.expect_get_token()
+            .returning(|| Err(CredentialError::retryable("try again")));
+        let provider = RetryTokenProvider::new(
+            mock,
+            RetryPolicy::new()
+                .with_maximum_attempts(u32::MAX)
+                .with_maximum_duration(Duration::from_secs(10)),
+            ExponentialBackoff::default(),
+        );
+        let start = Instant::now();
+        let e = provider.get_token().await.err().unwrap();
+        assert!(e.is_retryable(), "{e}");
+        assert!(start.elapsed() < Duration::from_secs(10));
+        Ok(())
+    }
+
+    #[derive(Debug)]
+    struct SlowProvider;
+
+    #[async_trait::async_trait]
+    impl TokenProvider for SlowProvider {
+        async fn get_token(&self) -> Result<Token> {
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+            Ok(token())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn attempt_timeout() -> TestResult {
+        let provider = RetryTokenProvider::new(
+            SlowProvider,
+            RetryPolicy::new()
+                .with_maximum_attempts(2)
+                .with_attempt_timeout(Duration::from_secs(5)),
+            ExponentialBackoff::default(),
+        );
+        let start = Instant::now();
+        let e = provider.get_token().await.err().unwrap();
+        assert!(e.is_retryable(), "{e}");
+        assert!(e.to_string().contains("timed out"), "{e}");
+        assert!(start.elapsed() >= Duration::from_secs(10));
+        assert!(start.elapsed() < Duration::from_secs(20));
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn never_retry() -> TestResult {
+        let mut mock = MockTokenProvider::new();
+        mock.expect_get_token()
+            .times(1)
+            .returning(|| Err(CredentialError::retryable("try again")));
+        let provider =
+            RetryTokenProvider::new(mock, RetryPolicy::never(), ExponentialBackoff::default());
+        let e = provider.get_token().await.err().unwrap();
+        assert!(e.is_retryable(), "{e}");
+        Ok(())
+    }
+
+    #[derive(Clone, Default)]
+    struct Capture(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Capture {
+        fn contents(&self) -> String {
+            String::from_utf8_lossy(&self.0.lock().unwrap()).to_string()
+        }
+    }
+
+    // Captures the events at `DEBUG` level and above in the current thread.
+    fn capture_events() -> (Capture, tracing::subscriber::DefaultGuard) {
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let guard = tracing::subscriber::set_default(subscriber);
+        (capture, guard)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tracing_success() -> TestResult {
+        let (capture, _guard) = capture_events();
+        let mut mock = MockTokenProvider::new();
+        let mut seq = mockall::Sequence::new();
+        mock.expect_get_token()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|| Err(CredentialError::retryable("try again")));
+        mock.expect_get_token()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|| {
+                Ok(token().with_metadata(
+                    crate::token::metadata_keys::SOURCE_TYPE,
+                    Some("service_account"),
+                ))
+            });
+        let provider = RetryTokenProvider::with_defaults(mock);
+        provider.get_token().await?;
+
+        let got = capture.contents();
+        assert!(got.contains("token refresh started"), "{got}");
+        assert!(got.contains("attempt_count=1"), "{got}");
+        assert!(got.contains("try again"), "{got}");
+        assert!(got.contains("token refresh succeeded"), "{got}");
+        assert!(got.contains("source_type=\"service_account\""), "{got}");
+        assert!(got.contains("elapsed_ms="), "{got}");
+        assert!(!got.contains("test-token"), "{got}");
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tracing_failure() -> TestResult {
+        let (capture, _guard) = capture_events();
+        let mut mock = MockTokenProvider::new();
+        mock.expect_get_token()
+            .times(1)
+            .returning(|| Err(CredentialError::non_retryable("epic fail")));
+        let provider = RetryTokenProvider::with_defaults(mock);
+        provider.get_token().await.err().unwrap();
+
+        let got = capture.contents();
+        assert!(got.contains("token refresh failed"), "{got}");
+        assert!(got.contains("retryable=false"), "{got}");
+        assert!(got.contains("epic fail"), "{got}");
+        assert!(!got.contains("token refresh succeeded"), "{got}");
+        Ok(())
+    }
+}
@@ -12,7 +12,36 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::credentials::util::http_client;
+use crate::errors::{is_retryable, CredentialError};
 use crate::Result;
+use std::collections::HashMap;
+use time::OffsetDateTime;
+
+const TOKEN_INFO_ENDPOINT: &str = "https://oauth2.googleapis.com/tokeninfo";
+
+/// The keys in [Token::metadata].
+///
+/// The credentials record what they know about the token, not all keys are
+/// present for all credential types.
+pub mod metadata_keys {
+    /// The OAuth 2.0 scopes of the token, separated by spaces.
+    ///
+    /// These are the scopes granted by the authorization server when it
+    /// reports them, otherwise the scopes requested by the credentials.
+    pub const SCOPES: &str = "scopes";
+
+    /// The type of the credentials that created the token, e.g.
+    /// `service_account`, or `authorized_user`.
+    ///
+    /// The values match the `type` field in the credential files. Tokens from
+    /// the metadata service use `compute_metadata`.
+    pub const SOURCE_TYPE: &str = "source_type";
+
+    /// The email of the principal (user or service account) the token
+    /// represents.
+    pub const PRINCIPAL_EMAIL: &str = "principal_email";
+}
 
 /// Represents an auth token.
 #[derive(Clone, Debug, PartialEq)]
@@ -39,6 +68,145 @@ pub struct Token {
     pub metadata: Option<std::collections::HashMap<String, String>>,
 }
 
+impl Token {
+    /// Returns the OAuth 2.0 scopes of the token, if known.
+    ///
+    /// See [metadata_keys::SCOPES].
+    pub fn scopes(&self) -> Option<Vec<&str>> {
+        self.metadata_value(metadata_keys::SCOPES)
+            .map(|s| s.split_whitespace().collect())
+    }
+
+    /// Returns the type of the credentials that created the token, if known.
+    ///
+    /// See [metadata_keys::SOURCE_TYPE].
+    pub fn source_type(&self) -> Option<&str> {
+        self.metadata_value(metadata_keys::SOURCE_TYPE)
+    }
+
+    /// Returns the email of the principal the token represents, if known.
+    ///
+    /// See [metadata_keys::PRINCIPAL_EMAIL].
+    pub fn principal_email(&self) -> Option<&str> {
+        self.metadata_value(metadata_keys::PRINCIPAL_EMAIL)
+    }
+
+    /// Fetches information about the token from the [tokeninfo] endpoint.
+    ///
+    /// This makes an HTTP request to Google. The information is reported by
+    /// the authorization server, and works for access tokens from all
+    /// credential types in the `googleapis.com` universe.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use gcp_sdk_auth::credentials::create_access_token_credential;
+    /// # use gcp_sdk_auth::errors::CredentialError;
+    /// # tokio_test::block_on(async {
+    /// let credential = create_access_token_credential().await?;
+    /// let info = credential.get_token().await?.introspect().await?;
+    /// println!("email={:?}, scopes={:?}", info.email, info.scopes);
+    /// # Ok::<(), CredentialError>(())
+    /// # });
+    /// ```
+    ///
+    /// [tokeninfo]: https://developers.google.com/identity/protocols/oauth2/web-server#tokeninfo
+    pub async fn introspect(&self) -> Result<TokenInfo> {
+        introspect(TOKEN_INFO_ENDPOINT, self).await
+    }
+
+    fn metadata_value(&self, key: &str) -> Option<&str> {
+        self.metadata.as_ref()?.get(key).map(String::as_str)
+    }
+
+    /// Adds `value` to the metadata, if it is known.
+    pub(crate) fn with_metadata<V: Into<String>>(mut self, key: &str, value: Option<V>) -> Self {
+        if let Some(v) = value {
+            self.metadata
+                .get_or_insert_with(HashMap::new)
+                .insert(key.to_string(), v.into());
+        }
+        self
+    }
+}
+
+/// Information about an access token, reported by the authorization server.
+///
+/// See [Token::introspect].
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct TokenInfo {
+    /// The OAuth 2.0 scopes granted to the token.
+    pub scopes: Vec<String>,
+
+    /// The email of the principal, if the token has the `email` scope, or the
+    /// principal is a service account.
+    pub email: Option<String>,
+
+    /// The OAuth client the token was issued to.
+    pub audience: Option<String>,
+
+    /// The unique ID of the principal.
+    pub subject: Option<String>,
+
+    /// The instant at which the token expires.
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+// The tokeninfo endpoint returns numbers as strings, e.g. `"exp": "1700000000"`.
+#[derive(Debug, serde::Deserialize)]
+struct TokenInfoResponse {
+    #[serde(default)]
+    scope: Option<String>,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    aud: Option<String>,
+    #[serde(default)]
+    sub: Option<String>,
+    #[serde(default)]
+    exp: Option<serde_json::Value>,
+}
+
+pub(crate) async fn introspect(endpoint: &str, token: &Token) -> Result<TokenInfo> {
+    let resp = http_client::client()
+        .post(endpoint)
+        .form(&[("access_token", token.token.as_str())])
+        .send()
+        .await
+        .map_err(CredentialError::retryable)?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| CredentialError::new(is_retryable(status), e.into()))?;
+        return Err(CredentialError::new(
+            is_retryable(status),
+            Box::from(format!("Failed to introspect token. {body}")),
+        ));
+    }
+    let response = resp.json::<TokenInfoResponse>().await.map_err(|e| {
+        let retryable = !e.is_decode();
+        CredentialError::new(retryable, e.into())
+    })?;
+    let exp = match response.exp {
+        Some(serde_json::Value::Number(n)) => n.as_i64(),
+        Some(serde_json::Value::String(s)) => s.parse::<i64>().ok(),
+        _ => None,
+    };
+    Ok(TokenInfo {
+        scopes: response
+            .scope
+            .as_deref()
+            .map(|s| s.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default(),
+        email: response.email,
+        audience: response.aud,
+        subject: response.sub,
+        expires_at: exp.and_then(|e| OffsetDateTime::from_unix_timestamp(e).ok()),
+    })
+}
+
 #[async_trait::async_trait]
 pub(crate) trait TokenProvider: std::fmt::Debug + Send + Sync {
     async fn get_token(&self) -> Result<Token>;
@@ -47,6 +215,9 @@ pub(crate) trait TokenProvider: std::fmt::Debug + Send + Sync {
 #[cfg(test)]
 pub(crate) mod test {
     use super::*;
+    use http::StatusCode;
+
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
 
     mockall::mock! {
         #[derive(Debug)]
@@ -57,4 +228,112 @@ pub(crate) mod test {
             async fn get_token(&self) -> Result<Token>;
         }
     }
+
+    fn test_token() -> Token {
+        Token {
+            token: "test-token".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_at: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn metadata_accessors() {
+        let token = test_token();
+        assert_eq!(token.scopes(), None);
+        assert_eq!(token.source_type(), None);
+        assert_eq!(token.principal_email(), None);
+
+        let token = test_token()
+            .with_metadata(metadata_keys::SCOPES, Some("scope1  scope2"))
+            .with_metadata(metadata_keys::SOURCE_TYPE, Some("service_account"))
+            .with_metadata(metadata_keys::PRINCIPAL_EMAIL, None::<String>);
+        assert_eq!(token.scopes(), Some(vec!["scope1", "scope2"]));
+        assert_eq!(token.source_type(), Some("service_account"));
+        assert_eq!(token.principal_email(), None);
+        assert_eq!(token.metadata.map(|m| m.len()), Some(2));
+    }
+
+    #[test]
+    fn with_metadata_none_is_noop() {
+        let token = test_token().with_metadata(metadata_keys::SCOPES, None::<&str>);
+        assert_eq!(token, test_token());
+    }
+
+    // Starts a tokeninfo server. Returns the endpoint and the server task.
+    async fn start(
+        code: StatusCode,
+        body: serde_json::Value,
+    ) -> (String, tokio::task::JoinHandle<()>) {
+        let handler = move |axum::Form(form): axum::Form<HashMap<String, String>>| async move {
+            assert_eq!(
+                form.get("access_token").map(String::as_str),
+                Some("test-token")
+            );
+            (code, axum::Json(body))
+        };
+        let app = axum::Router::new().route("/tokeninfo", axum::routing::post(handler));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (format!("http://{addr}/tokeninfo"), server)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn introspect_success() -> TestResult {
+        let body = serde_json::json!({
+            "azp": "test-client-id",
+            "aud": "test-client-id",
+            "sub": "123456",
+            "scope": "scope1 scope2",
+            "exp": "1700000000",
+            "expires_in": "3599",
+            "email": "test@example.com",
+            "email_verified": "true",
+        });
+        let (endpoint, _server) = start(StatusCode::OK, body).await;
+        let info = introspect(&endpoint, &test_token()).await?;
+        assert_eq!(info.scopes, vec!["scope1", "scope2"]);
+        assert_eq!(info.email.as_deref(), Some("test@example.com"));
+        assert_eq!(info.audience.as_deref(), Some("test-client-id"));
+        assert_eq!(info.subject.as_deref(), Some("123456"));
+        assert_eq!(
+            info.expires_at,
+            Some(OffsetDateTime::from_unix_timestamp(1700000000)?)
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn introspect_partial() -> TestResult {
+        let body = serde_json::json!({ "exp": 1700000000 });
+        let (endpoint, _server) = start(StatusCode::OK, body).await;
+        let info = introspect(&endpoint, &test_token()).await?;
+        assert_eq!(
+            info,
+            TokenInfo {
+                expires_at: Some(OffsetDateTime::from_unix_timestamp(1700000000)?),
+                ..Default::default()
+            }
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn introspect_errors() -> TestResult {
+        let body = serde_json::json!({ "error": "invalid_token" });
+        let (endpoint, _server) = start(StatusCode::BAD_REQUEST, body).await;
+        let e = introspect(&endpoint, &test_token()).await.err().unwrap();
+        assert!(!e.is_retryable(), "{e}");
+        assert!(format!("{e:?}").contains("invalid_token"), "{e:?}");
+
+        let body = serde_json::json!("try again");
+        let (endpoint, _server) = start(StatusCode::SERVICE_UNAVAILABLE, body).await;
+        let e = introspect(&endpoint, &test_token()).await.err().unwrap();
+        assert!(e.is_retryable(), "{e}");
+        Ok(())
+    }
 }
@@ -0,0 +1,566 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::token::{Token, TokenProvider};
+use crate::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::time::Instant;
+
+// Tokens are treated as expired slightly before their actual expiration time.
+// The token must remain valid while the request travels to the service, and
+// the clocks in this machine and the service are not perfectly synchronized.
+const EXPIRATION_SLACK: Duration = Duration::from_secs(10);
+
+// Tokens that expire within this window are refreshed in the background. The
+// cached token is still returned while the refresh is in progress, so most
+// callers never wait for a token fetch. For short-lived tokens the window is
+// capped at half the lifetime of the token, otherwise they would be stale as
+// soon as they are fetched.
+const REFRESH_WINDOW: Duration = Duration::from_secs(225);
+
+// After a background refresh fails, the next background refresh waits at least
+// this long. The delay doubles on each consecutive failure, up to
+// `BACKGROUND_RETRY_MAXIMUM_DELAY`. Without this, a failing token endpoint
+// would receive one request per call to `get_token()`.
+const BACKGROUND_RETRY_INITIAL_DELAY: Duration = Duration::from_secs(2);
+const BACKGROUND_RETRY_MAXIMUM_DELAY: Duration = Duration::from_secs(30);
+
+/// A [TokenProvider] that caches the tokens returned by another provider.
+///
+/// The cached token is reused until it is close to its expiration time. Tokens
+/// without an expiration time are reused forever.
+///
+/// Shortly before the cached token expires, the cache starts a refresh in the
+/// background and keeps returning the cached token. If the token has already
+/// expired (or there is no cached token), the caller waits for a new token.
+/// In both cases concurrent callers share a single request to the underlying
+/// provider. Failed background refreshes are retried with exponential backoff.
+#[derive(Debug)]
+pub(crate) struct TokenCache<T>
+where
+    T: TokenProvider + 'static,
+{
+    inner: Arc<Inner<T>>,
+}
+
+// Clones share the cached token, and the underlying provider.
+impl<T> Clone for TokenCache<T>
+where
+    T: TokenProvider + 'static,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner<T> {
+    provider: T,
+    // The most recent token. The lock is never held across an `.await`.
+    token: Mutex<Option<Cached>>,
+    // Serializes calls to the underlying provider.
+    refresh: tokio::sync::Mutex<()>,
+    // Set while a background refresh is scheduled or running.
+    background_refresh: AtomicBool,
+    // Consecutive background refresh failures, if any.
+    background_failures: Mutex<Option<Failures>>,
+}
+
+#[derive(Debug)]
+struct Failures {
+    count: u32,
+    // Background refreshes are not attempted before this time.
+    retry_after: Instant,
+}
+
+impl Failures {
+    fn next(previous: Option<&Failures>, now: Instant) -> Self {
+        let count = previous.map_or(1, |f| f.count.saturating_add(1));
+        let delay = BACKGROUND_RETRY_INITIAL_DELAY
+            .saturating_mul(2_u32.saturating_pow(count - 1))
+            .min(BACKGROUND_RETRY_MAXIMUM_DELAY);
+        Self {
+            count,
+            retry_after: now + delay,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Cached {
+    token: Token,
+    // When the token was fetched, used to compute its lifetime.
+    fetched_at: OffsetDateTime,
+    // Incremented on each fetch, detects refreshes completed by other tasks.
+    generation: u64,
+}
+
+#[derive(Debug, PartialEq)]
+enum CachedToken {
+    Fresh(Token),
+    Stale(Token),
+    Expired,
+}
+
+impl<T> TokenCache<T>
+where
+    T: TokenProvider + 'static,
+{
+    pub(crate) fn new(provider: T) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                provider,
+                token: Mutex::new(None),
+                refresh: tokio::sync::Mutex::new(()),
+                background_refresh: AtomicBool::new(false),
+                background_failures: Mutex::new(None),
+            }),
+        }
+    }
+
+    fn refresh_in_background(&self) {
+        if self.inner.background_backoff() {
+            // A recent background refresh failed, wait before trying again.
+            return;
+        }
+        if self.inner.background_refresh.swap(true, Ordering::AcqRel) {
+            // Another background refresh is already in progress.
+            return;
+        }
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            // Without a runtime we cannot refresh in the background. The token
+            // will be refreshed once it expires.
+            self.inner
+                .background_refresh
+                .store(false, Ordering::Release);
+            return;
+        };
+        let inner = self.inner.clone();
+        handle.spawn(async move {
+            // Errors are not reported, the next call to `get_token()` retries
+            // the refresh (after a backoff), and reports the error if the
+            // token has expired.
+            let result = inner.refresh().await;
+            {
+                let mut failures = inner
+                    .background_failures
+                    .lock()
+                    .expect("token cache lock is poisoned");
+                *failures = match result {
+                    Ok(_) => None,
+                    Err(_) => Some(Failures::next(failures.as_ref(), Instant::now())),
+                };
+            }
+            inner.background_refresh.store(false, Ordering::Release);
+        });
+    }
+}
+
+impl<T> Inner<T>
+where
+    T: TokenProvider,
+{
+    fn background_backoff(&self) -> bool {
+        let guard = self
+            .background_failures
+            .lock()
+            .expect("token cache lock is poisoned");
+        guard
+            .as_ref()
+            .is_some_and(|f| Instant::now() < f.retry_after)
+    }
+
+    fn cached(&self) -> CachedToken {
+        self.cached_with_generation().0
+    }
+
+    fn cached_with_generation(&self) -> (CachedToken, u64) {
+        let guard = self.token.lock().expect("token cache lock is poisoned");
+        match guard.as_ref() {
+            None => (CachedToken::Expired, 0),
+            Some(c) => (classify(c, OffsetDateTime::now_utc()), c.generation),
+        }
+    }
+
+    async fn refresh(&self) -> Result<Token> {
+        let (_, generation) = self.cached_with_generation();
+        let _guard = self.refresh.lock().await;
+        // Another task may have refreshed the token while we waited. Use it,
+        // even if it is short-lived and already inside the refresh window.
+        let current = match self.cached_with_generation() {
+            (CachedToken::Fresh(token), _) => return Ok(token),
+            (CachedToken::Stale(token), g) if g != generation => return Ok(token),
+            (_, g) => g,
+        };
+        let token = self.provider.get_token().await?;
+        *self.token.lock().expect("token cache lock is poisoned") = Some(Cached {
+            token: token.clone(),
+            fetched_at: OffsetDateTime::now_utc(),
+            generation: current + 1,
+        });
+        Ok(token)
+    }
+}
+
+fn classify(cached: &Cached, now: OffsetDateTime) -> CachedToken {
+    let token = &cached.token;
+    let Some(e) = token.expires_at else {
+        return CachedToken::Fresh(token.clone());
+    };
+    let lifetime = Duration::try_from(e - cached.fetched_at).unwrap_or(Duration::ZERO);
+    let window = REFRESH_WINDOW.min(lifetime / 2);
+    match e {
+        e if now + EXPIRATION_SLACK >= e => CachedToken::Expired,
+        e if now + window >= e => CachedToken::Stale(token.clone()),
+        _ => CachedToken::Fresh(token.clone()),
+    }
+}
+
+#[async_trait::async_trait]
+impl<T> TokenProvider for TokenCache<T>
+where
+    T: TokenProvider + 'static,
+{
+    async fn get_token(&self) -> Result<Token> {
+        match self.inner.cached() {
+            CachedToken::Fresh(token) => Ok(token),
+            CachedToken::Stale(token) => {
+                self.refresh_in_background();
+                Ok(token)
+            }
+            CachedToken::Expired => self.inner.refresh().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::errors::CredentialError;
+    use crate::token::test::MockTokenProvider;
+    use std::sync::atomic::AtomicUsize;
+
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    fn token(value: &str, expires_at: Option<OffsetDateTime>) -> Token {
+        Token {
+            token: value.to_string(),
+            token_type: "Bearer".to_string(),
+            expires_at,
+            metadata: None,
+        }
+    }
+
+    fn cached(token: Token, fetched_at: OffsetDateTime) -> Cached {
+        Cached {
+            token,
+            fetched_at,
+            generation: 1,
+        }
+    }
+
+    #[test]
+    fn classify_tokens() {
+        let now = OffsetDateTime::now_utc();
+        let t = token("t", None);
+        assert_eq!(
+            classify(&cached(t.clone(), now), now),
+            CachedToken::Fresh(t)
+        );
+
+        let t = token("t", Some(now + Duration::from_secs(3600)));
+        assert_eq!(
+            classify(&cached(t.clone(), now), now),
+            CachedToken::Fresh(t)
+        );
+
+        let fetched_at = now - Duration::from_secs(3540);
+        let t = token("t", Some(now + Duration::from_secs(60)));
+        assert_eq!(
+            classify(&cached(t.clone(), fetched_at), now),
+            CachedToken::Stale(t)
+        );
+
+        let t = token("t", Some(now + Duration::from_secs(5)));
+        assert_eq!(classify(&cached(t, fetched_at), now), CachedToken::Expired);
+
+        let t = token("t", Some(now - Duration::from_secs(5)));
+        assert_eq!(classify(&cached(t, fetched_at), now), CachedToken::Expired);
+    }
+
+    #[test]
+    fn classify_short_lived_tokens() {
+        let now = OffsetDateTime::now_utc();
+        // The refresh window is half the lifetime of short-lived tokens.
+        let t = token("t", Some(now + Duration::from_secs(120)));
+        assert_eq!(
+            classify(&cached(t.clone(), now), now),
+            CachedToken::Fresh(t)
+        );
+
+        let fetched_at = now - Duration::from_secs(70);
+        let t = token("t", Some(now + Duration::from_secs(50)));
+        assert_eq!(
+            classify(&cached(t.clone(), fetched_at), now),
+            CachedToken::Stale(t)
+        );
+    }
+
+    #[test]
+    fn background_failures_backoff() {
+        let now = Instant::now();
+        let f = Failures::next(None, now);
+        assert_eq!(f.count, 1);
+        assert_eq!(f.retry_after, now + BACKGROUND_RETRY_INITIAL_DELAY);
+        let f = Failures::next(Some(&f), now);
+        assert_eq!(f.count, 2);
+        assert_eq!(f.retry_after, now + BACKGROUND_RETRY_INITIAL_DELAY * 2);
+        let f = Failures {
+            count: 100,
+            retry_after: now,
+        };
+        let f = Failures::next(Some(&f), now);
+        assert_eq!(f.retry_after, now + BACKGROUND_RETRY_MAXIMUM_DELAY);
+    }
+
+    #[tokio::test]
+    async fn reuses_fresh_token() -> TestResult {
+        let expected = token(
+            "test-token",
+            Some(OffsetDateTime::now_utc() + Duration::from_secs(3600)),
+        );
+        let clone = expected.clone();
+        let mut mock = MockTokenProvider::new();
+        mock.expect_get_token().times(1).return_once(|| Ok(clone));
+
+        let cache = TokenCache::new(mock);
+        for _ in 0..3 {
+            assert_eq!(cache.get_token().await?, expected);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reuses_token_without_expiration() -> TestResult {
+        let expected = token("test-token", None);
+        let clone = expected.clone();
+        let mut mock = MockTokenProvider::new();
+        mock.expect_get_token().times(1).return_once(|| Ok(clone));
+
+        let cache = TokenCache::new(mock);
+        for _ in 0..3 {
+            assert_eq!(cache.get_token().await?, expected);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn refreshes_expired_token() -> TestResult {
+        let mut seq = mockall::Sequence::new();
+        let mut mock = MockTokenProvider::new();
+        mock.expect_get_token()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|| {
+                Ok(token(
+                    "expired",
+                    Some(OffsetDateTime::now_utc() - Duration::from_secs(1)),
+                ))
+            });
+        mock.expect_get_token()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|| {
+                Ok(token(
+                    "fresh",
+                    Some(OffsetDateTime::now_utc() + Duration::from_secs(3600)),
+                ))
+            });
+
+        let cache = TokenCache::new(mock);
+        assert_eq!(cache.get_token().await?.token, "expired");
+        assert_eq!(cache.get_token().await?.token, "fresh");
+        assert_eq!(cache.get_token().await?.token, "fresh");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn errors_are_not_cached() -> TestResult {
+        let mut seq = mockall::Sequence::new();
+        let mut mock = MockTokenProvider::new();
+        mock.expect_get_token()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|| Err(CredentialError::retryable("try again")));
+        mock.expect_get_token()
+            .times(1)
+            .in_sequence(&mut seq)
+            .return_once(|| Ok(token("test-token", None)));
+
+        let cache = TokenCache::new(mock);
+        let e = cache.get_token().await.err().unwrap();
+        assert!(e.is_retryable(), "{e:?}");
+        assert_eq!(cache.get_token().await?.token, "test-token");
+        Ok(())
+    }
+
+    #[derive(Debug)]
+    struct FakeTokenProvider {
+        calls: Arc<AtomicUsize>,
+        delay: Duration,
+        lifetime: Duration,
+    }
+
+    #[async_trait::async_trait]
+    impl TokenProvider for FakeTokenProvider {
+        async fn get_token(&self) -> Result<Token> {
+            let count = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            tokio::time::sleep(self.delay).await;
+            Ok(token(
+                &format!("token-{count}"),
+                Some(OffsetDateTime::now_utc() + self.lifetime),
+            ))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_refreshes_are_coalesced() -> TestResult {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = Arc::new(TokenCache::new(FakeTokenProvider {
+            calls: calls.clone(),
+            delay: Duration::from_millis(100),
+            lifetime: Duration::from_secs(3600),
+        }));
+
+        let tasks: Vec<_> = (0..16)
+            .map(|_| {
+                let cache = cache.clone();
+                tokio::spawn(async move { cache.get_token().await })
+            })
+            .collect();
+        for t in tasks {
+            assert_eq!(t.await??.token, "token-1");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn stale_token_refreshes_in_background() -> TestResult {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = TokenCache::new(FakeTokenProvider {
+            calls: calls.clone(),
+            delay: Duration::from_millis(100),
+            lifetime: Duration::from_secs(3600),
+        });
+        // Seed the cache with a token inside the refresh window.
+        let now = OffsetDateTime::now_utc();
+        *cache.inner.token.lock().unwrap() = Some(cached(
+            token("token-0", Some(now + Duration::from_secs(60))),
+            now - Duration::from_secs(3540),
+        ));
+
+        // The stale token is returned without waiting for the refresh.
+        let start = std::time::Instant::now();
+        assert_eq!(cache.get_token().await?.token, "token-0");
+        assert_eq!(cache.get_token().await?.token, "token-0");
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        while calls.load(Ordering::SeqCst) < 1
+            || cache.inner.background_refresh.load(Ordering::SeqCst)
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.get_token().await?.token, "token-1");
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn short_lived_token_is_fetched_once() -> TestResult {
+        let calls = Arc::new(AtomicUsize::new(0));
+        // The token lifetime is shorter than `REFRESH_WINDOW`.
+        let cache = TokenCache::new(FakeTokenProvider {
+            calls: calls.clone(),
+            delay: Duration::from_millis(10),
+            lifetime: Duration::from_secs(120),
+        });
+        for _ in 0..5 {
+            assert_eq!(cache.get_token().await?.token, "token-1");
+        }
+        // Give any (unexpected) background refresh a chance to run.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(!cache.inner.background_refresh.load(Ordering::SeqCst));
+        Ok(())
+    }
+
+    #[derive(Debug)]
+    struct FailingTokenProvider {
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl TokenProvider for FailingTokenProvider {
+        async fn get_token(&self) -> Result<Token> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Err(CredentialError::retryable("try again"))
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_background_refresh_backs_off() -> TestResult {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let cache = TokenCache::new(FailingTokenProvider {
+            calls: calls.clone(),
+        });
+        // Seed the cache with a token inside the refresh window.
+        let now = OffsetDateTime::now_utc();
+        *cache.inner.token.lock().unwrap() = Some(cached(
+            token("token-0", Some(now + Duration::from_secs(60))),
+            now - Duration::from_secs(3540),
+        ));
+        // Calls the cache, and gives any background refresh a chance to run.
+        let get_token = || async {
+            let token = cache.get_token().await;
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            token
+        };
+
+        for _ in 0..10 {
+            assert_eq!(get_token().await?.token, "token-0");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        tokio::time::sleep(BACKGROUND_RETRY_INITIAL_DELAY).await;
+        for _ in 0..10 {
+            assert_eq!(get_token().await?.token, "token-0");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // The delay doubles after each consecutive failure.
+        tokio::time::sleep(BACKGROUND_RETRY_INITIAL_DELAY).await;
+        assert_eq!(get_token().await?.token, "token-0");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        tokio::time::sleep(BACKGROUND_RETRY_INITIAL_DELAY).await;
+        assert_eq!(get_token().await?.token, "token-0");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        Ok(())
+    }
+}
@@ -18,6 +18,7 @@ use crate::error::HttpError;
 use crate::error::ServiceError;
 use crate::exponential_backoff::ExponentialBackoff;
 use crate::loop_state::LoopState;
+use crate::mtls;
 use crate::options;
 use crate::polling_backoff_policy::PollingBackoffPolicy;
 use crate::polling_policy::Aip194Strict;
@@ -25,14 +26,21 @@ use crate::polling_policy::PollingPolicy;
 use crate::retry_policy::RetryPolicy;
 use crate::retry_throttler::RetryThrottlerWrapped;
 use crate::Result;
-use auth::credentials::{create_access_token_credential, Credential};
+use auth::credentials::Credential;
 use std::sync::Arc;
+use tokio::sync::OnceCell;
+
+mod universe_domain;
 
 #[derive(Clone, Debug)]
 pub struct ReqwestClient {
     inner: reqwest::Client,
     cred: Credential,
     endpoint: String,
+    universe_domain: String,
+    // Successful universe domain checks are cached, and shared by all the
+    // clones of this client.
+    universe_domain_checked: Arc<OnceCell<()>>,
     retry_policy: Option<Arc<dyn RetryPolicy>>,
     backoff_policy: Option<Arc<dyn BackoffPolicy>>,
     retry_throttler: RetryThrottlerWrapped,
@@ -42,21 +50,44 @@ pub struct ReqwestClient {
 
 impl ReqwestClient {
     pub async fn new(config: ClientConfig, default_endpoint: &str) -> Result<Self> {
-        let inner = reqwest::Client::new();
+        let universe_domain = universe_domain::resolve(config.universe_domain)?;
+        let default_endpoint = universe_domain::endpoint(default_endpoint, &universe_domain);
+        let (certificate, endpoint) = mtls::resolve(
+            config.client_certificate,
+            config.endpoint,
+            &default_endpoint,
+        )
+        .await?;
+        let inner = match certificate {
+            None => reqwest::Client::new(),
+            Some(c) => {
+                c.check_private_key()?;
+                let identity = reqwest::Identity::from_pkcs8_pem(&c.cert_chain, &c.private_key)
+                    .map_err(Error::other)?;
+                reqwest::Client::builder()
+                    .identity(identity)
+                    .build()
+                    .map_err(Error::other)?
+            }
+        };
         let cred = if let Some(c) = config.cred {
             c
         } else {
-            create_access_token_credential()
+            // Service account credentials restrict their tokens to this
+            // service. The default endpoint identifies the service, even if
+            // the application uses a private or mTLS endpoint.
+            auth::credentials::Builder::new()
+                .with_service_endpoint(&default_endpoint)
+                .build()
                 .await
                 .map_err(Error::authentication)?
         };
-        let endpoint = config
-            .endpoint
-            .unwrap_or_else(|| default_endpoint.to_string());
         Ok(Self {
             inner,
             cred,
             endpoint,
+            universe_domain,
+            universe_domain_checked: Arc::new(OnceCell::new()),
             retry_policy: config.retry_policy,
             backoff_policy: config.backoff_policy,
             retry_throttler: config.retry_throttler,
@@ -76,6 +107,12 @@ impl ReqwestClient {
         body: Option<I>,
         options: crate::options::RequestOptions,
     ) -> Result<O> {
+        self.universe_domain_checked
+            .get_or_try_init(|| async {
+                let credential_universe_domain = self.cred.get_universe_domain().await;
+                universe_domain::check(&self.universe_domain, credential_universe_domain)
+            })
+            .await?;
         let auth_headers = self
             .cred
             .get_headers()
@@ -0,0 +1,163 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Universe domain resolution for client endpoints.
+//!
+//! Google Cloud services are deployed in one or more *universes*. The default
+//! universe domain is `googleapis.com`, and the default endpoints in the
+//! client libraries are templates, where the universe domain replaces the
+//! `googleapis.com` suffix. For example, in the `example.com` universe,
+//! `https://secretmanager.googleapis.com/` becomes
+//! `https://secretmanager.example.com/`.
+
+use crate::error::Error;
+use crate::Result;
+
+pub(crate) const DEFAULT_UNIVERSE_DOMAIN: &str = "googleapis.com";
+const UNIVERSE_DOMAIN_VAR: &str = "GOOGLE_CLOUD_UNIVERSE_DOMAIN";
+
+/// Returns the universe domain configured by the application, or in the
+/// `GOOGLE_CLOUD_UNIVERSE_DOMAIN` environment variable.
+pub(crate) fn resolve(configured: Option<String>) -> Result<String> {
+    let universe_domain = configured
+        .or_else(|| {
+            std::env::var(UNIVERSE_DOMAIN_VAR)
+                .ok()
+                .filter(|v| !v.is_empty())
+        })
+        .unwrap_or_else(|| DEFAULT_UNIVERSE_DOMAIN.to_string());
+    if universe_domain.trim().is_empty() {
+        return Err(Error::other("the universe domain cannot be empty"));
+    }
+    Ok(universe_domain)
+}
+
+/// Returns the default endpoint of a service in the given universe.
+pub(crate) fn endpoint(default_endpoint: &str, universe_domain: &str) -> String {
+    let (scheme, rest) = default_endpoint
+        .split_once("://")
+        .unwrap_or(("", default_endpoint));
+    let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let Some(service) = host.strip_suffix(&format!(".{DEFAULT_UNIVERSE_DOMAIN}")) else {
+        return default_endpoint.to_string();
+    };
+    let host = format!("{service}.{universe_domain}");
+    match scheme {
+        "" => format!("{host}{path}"),
+        s => format!("{s}://{host}{path}"),
+    }
+}
+
+/// Verifies the credentials are valid in the client's universe domain.
+///
+/// Credentials that do not report a universe domain are not checked.
+pub(crate) fn check(
+    universe_domain: &str,
+    credential_universe_domain: Option<String>,
+) -> Result<()> {
+    match credential_universe_domain {
+        Some(u) if u != universe_domain => Err(Error::authentication(format!(
+            "the client universe domain ({universe_domain}) does not match the universe domain of the credentials ({u}). \
+            Configure the universe domain with `ClientConfig::set_universe_domain()`, or the {UNIVERSE_DOMAIN_VAR} environment variable, \
+            if not configured the universe domain defaults to {DEFAULT_UNIVERSE_DOMAIN}"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::ErrorKind;
+    use test_case::test_case;
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    #[test_case(
+        "https://secretmanager.googleapis.com/",
+        "googleapis.com",
+        "https://secretmanager.googleapis.com/";
+        "default universe"
+    )]
+    #[test_case(
+        "https://secretmanager.googleapis.com/",
+        "example.com",
+        "https://secretmanager.example.com/";
+        "with trailing slash"
+    )]
+    #[test_case(
+        "https://secretmanager.googleapis.com",
+        "example.com",
+        "https://secretmanager.example.com";
+        "without trailing slash"
+    )]
+    #[test_case(
+        "secretmanager.googleapis.com",
+        "example.com",
+        "secretmanager.example.com"
+    )]
+    #[test_case("http://127.0.0.1:8080/", "example.com", "http://127.0.0.1:8080/")]
+    #[test_case(
+        "https://notgoogleapis.com/",
+        "example.com",
+        "https://notgoogleapis.com/"
+    )]
+    fn templated_endpoints(default_endpoint: &str, universe_domain: &str, want: &str) {
+        assert_eq!(endpoint(default_endpoint, universe_domain), want);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn resolve_universe_domain() -> TestResult {
+        // This test must run serially because `std::env::remove_var` and
+        // `std::env::set_var` are unsafe otherwise.
+        unsafe {
+            std::env::remove_var(UNIVERSE_DOMAIN_VAR);
+        }
+        assert_eq!(resolve(None)?, DEFAULT_UNIVERSE_DOMAIN);
+        assert_eq!(resolve(Some("configured.com".into()))?, "configured.com");
+
+        unsafe {
+            std::env::set_var(UNIVERSE_DOMAIN_VAR, "from-env.com");
+        }
+        assert_eq!(resolve(None)?, "from-env.com");
+        assert_eq!(resolve(Some("configured.com".into()))?, "configured.com");
+
+        // Empty values in the environment are ignored.
+        unsafe {
+            std::env::set_var(UNIVERSE_DOMAIN_VAR, "");
+        }
+        assert_eq!(resolve(None)?, DEFAULT_UNIVERSE_DOMAIN);
+        let e = resolve(Some("".into())).err().unwrap();
+        assert!(e.to_string().contains("empty"), "{e}");
+
+        unsafe {
+            std::env::remove_var(UNIVERSE_DOMAIN_VAR);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn check_universe_domain() {
+        assert!(check("googleapis.com", Some("googleapis.com".into())).is_ok());
+        assert!(check("example.com", Some("example.com".into())).is_ok());
+        assert!(check("example.com", None).is_ok());
+
+        let e = check("example.com", Some("googleapis.com".into()))
+            .err()
+            .unwrap();
+        assert_eq!(e.kind(), ErrorKind::Authentication);
+        assert!(e.to_string().contains("example.com"), "{e}");
+        assert!(e.to_string().contains("googleapis.com"), "{e}");
+    }
+}
@@ -26,6 +26,7 @@
 //! [RequestOptionsBuilder] trait where applications can override some defaults.
 
 use crate::backoff_policy::{BackoffPolicy, BackoffPolicyArg};
+use crate::mtls::ClientCertificate;
 use crate::polling_backoff_policy::{PollingBackoffPolicy, PollingBackoffPolicyArg};
 use crate::polling_policy::{PollingPolicy, PollingPolicyArg};
 use crate::retry_policy::{RetryPolicy, RetryPolicyArg};
@@ -227,7 +228,9 @@ where
 /// the retry policies, and/or other behaviors of the client.
 pub struct ClientConfig {
     pub(crate) endpoint: Option<String>,
+    pub(crate) universe_domain: Option<String>,
     pub(crate) cred: Option<Credential>,
+    pub(crate) client_certificate: Option<ClientCertificate>,
     pub(crate) tracing: bool,
     pub(crate) retry_policy: Option<Arc<dyn RetryPolicy>>,
     pub(crate) backoff_policy: Option<Arc<dyn BackoffPolicy>>,
@@ -244,6 +247,17 @@ impl ClientConfig {
         Self::default()
     }
 
+    /// Returns a [ClientConfig] authenticating requests with an [API key].
+    ///
+    /// The key is sent in the `x-goog-api-key` header. Application Default
+    /// Credentials are not used with this configuration.
+    ///
+    /// [API key]: https://cloud.google.com/docs/authentication/api-keys
+    pub fn with_api_key<T: Into<String>>(v: T) -> Self {
+        Self::new()
+            .set_credential(auth::credentials::api_key_credential::create_api_key_credential(v))
+    }
+
     pub fn tracing_enabled(&self) -> bool {
         if self.tracing {
             return true;
@@ -259,6 +273,22 @@ impl ClientConfig {
         self
     }
 
+    /// Sets the [universe domain] for the client.
+    ///
+    /// The default endpoint for the service uses this universe domain,
+    /// instead of `googleapis.com`. If not set, the client uses the
+    /// `GOOGLE_CLOUD_UNIVERSE_DOMAIN` environment variable, or
+    /// `googleapis.com` if the variable is not set.
+    ///
+    /// Before the first request, the client verifies the credentials are
+    /// valid in the same universe domain.
+    ///
+    /// [universe domain]: https://cloud.google.com/docs/authentication/troubleshoot-adc#universe-domain
+    pub fn set_universe_domain<T: Into<String>>(mut self, v: T) -> Self {
+        self.universe_domain = Some(v.into());
+        self
+    }
+
     /// Enables tracing.
     pub fn enable_tracing(mut self) -> Self {
         self.tracing = true;
@@ -277,6 +307,16 @@ impl ClientConfig {
         self
     }
 
+    /// Configure the client certificate for mutual TLS (mTLS).
+    ///
+    /// Requests use the mTLS variant of the default endpoint, unless
+    /// `GOOGLE_API_USE_MTLS_ENDPOINT` is `never`. See [crate::mtls] for
+    /// details.
+    pub fn set_client_certificate<T: Into<Option<ClientCertificate>>>(mut self, v: T) -> Self {
+        self.client_certificate = v.into();
+        self
+    }
+
     /// Configure the retry policy.
     pub fn set_retry_policy<V: Into<RetryPolicyArg>>(mut self, v: V) -> Self {
         self.retry_policy = Some(v.into().0);
@@ -314,7 +354,9 @@ impl std::default::Default for ClientConfig {
         use std::sync::{Arc, Mutex};
         Self {
             endpoint: None,
+            universe_domain: None,
             cred: None,
+            client_certificate: None,
             tracing: false,
             retry_policy: None,
             backoff_policy: None,
@@ -491,6 +533,14 @@ mod test {
         );
     }
 
+    #[test]
+    fn config_universe_domain() {
+        let config = ClientConfig::new();
+        assert_eq!(config.universe_domain, None);
+        let config = config.set_universe_domain("example.com");
+        assert_eq!(config.universe_domain.as_deref(), Some("example.com"));
+    }
+
     #[tokio::test]
     async fn config_credentials() -> Result {
         let config =
@@ -501,6 +551,26 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn config_api_key() -> Result {
+        let config = ClientConfig::with_api_key("test-api-key");
+        let cred = config.cred.unwrap();
+        let headers = cred.get_headers().await?;
+        assert_eq!(headers.len(), 1, "{headers:?}");
+        assert_eq!(headers[0].0, "x-goog-api-key");
+        assert_eq!(headers[0].1, "test-api-key");
+        Ok(())
+    }
+
+    #[test]
+    fn config_client_certificate() {
+        let config = ClientConfig::new();
+        assert!(config.client_certificate.is_none());
+        let config =
+            config.set_client_certificate(ClientCertificate::from_pem("test-cert", "test-key"));
+        assert!(config.client_certificate.is_some());
+    }
+
     #[test]
     fn config_retry_policy() {
         let config = ClientConfig::new().set_retry_policy(LimitedAttemptCount::new(5));
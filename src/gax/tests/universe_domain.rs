@@ -0,0 +1,135 @@
+// Copyright 2025 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use auth::credentials::{Credential, CredentialTrait};
+use auth::errors::CredentialError;
+use auth::token::Token;
+use gax::error::ErrorKind;
+use gax::http_client::ReqwestClient;
+use gax::options::*;
+use gcp_sdk_gax as gax;
+use http::header::{HeaderName, HeaderValue};
+use serde_json::json;
+
+type AuthResult<T> = std::result::Result<T, CredentialError>;
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+mockall::mock! {
+    #[derive(Debug)]
+    Credential {}
+
+    impl CredentialTrait for Credential {
+        async fn get_token(&self) -> AuthResult<Token>;
+        async fn get_headers(&self) -> AuthResult<Vec<(HeaderName, HeaderValue)>>;
+        async fn get_universe_domain(&self) -> Option<String>;
+    }
+}
+
+#[tokio::test]
+async fn templated_default_endpoint() -> Result<()> {
+    let config = ClientConfig::new()
+        .set_credential(auth::credentials::testing::test_credentials())
+        .set_universe_domain("example.com");
+    let client = ReqwestClient::new(config, "https://secretmanager.googleapis.com").await?;
+    let request = client
+        .builder(reqwest::Method::GET, "/v1/test".into())
+        .build()?;
+    assert_eq!(
+        request.url().as_str(),
+        "https://secretmanager.example.com/v1/test"
+    );
+
+    // Explicitly configured endpoints are not changed.
+    let config = ClientConfig::new()
+        .set_credential(auth::credentials::testing::test_credentials())
+        .set_universe_domain("example.com")
+        .set_endpoint("https://private.googleapis.com");
+    let client = ReqwestClient::new(config, "https://secretmanager.googleapis.com").await?;
+    let request = client
+        .builder(reqwest::Method::GET, "/v1/test".into())
+        .build()?;
+    assert_eq!(
+        request.url().as_str(),
+        "https://private.googleapis.com/v1/test"
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn universe_domain_mismatch() -> Result<()> {
+    let (endpoint, _server) = echo_server::start().await?;
+
+    // The credentials are not used to authenticate the request.
+    let mut mock = MockCredential::new();
+    mock.expect_get_universe_domain()
+        .return_const(Some("googleapis.com".to_string()));
+    mock.expect_get_headers().never();
+
+    let config = ClientConfig::new()
+        .set_credential(Credential::from(mock))
+        .set_universe_domain("example.com");
+    let client = ReqwestClient::new(config, &endpoint).await?;
+    let builder = client.builder(reqwest::Method::GET, "/echo".into());
+    let response = client
+        .execute::<_, serde_json::Value>(builder, Some(json!({})), RequestOptions::default())
+        .await;
+    let err = response.err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::Authentication);
+    assert!(err.to_string().contains("example.com"), "{err}");
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn universe_domain_match() -> Result<()> {
+    let (endpoint, _server) = echo_server::start().await?;
+
+    // The universe domain is only checked once.
+    let mut mock = MockCredential::new();
+    mock.expect_get_universe_domain()
+        .times(1)
+        .return_const(Some("example.com".to_string()));
+    mock.expect_get_headers()
+        .times(2)
+        .returning(|| Ok(Vec::new()));
+
+    let config = ClientConfig::new()
+        .set_credential(Credential::from(mock))
+        .set_universe_domain("example.com");
+    let client = ReqwestClient::new(config, &endpoint).await?;
+    for _ in 0..2 {
+        let builder = client.builder(reqwest::Method::GET, "/echo".into());
+        let _: serde_json::Value = client
+            .execute(builder, Some(json!({})), RequestOptions::default())
+            .await?;
+    }
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn universe_domain_api_key() -> Result<()> {
+    let (endpoint, _server) = echo_server::start().await?;
+
+    // API keys are not tied to a universe domain.
+    let config = ClientConfig::with_api_key("test-api-key").set_universe_domain("example.com");
+    let client = ReqwestClient::new(config, &endpoint).await?;
+    let builder = client.builder(reqwest::Method::GET, "/echo".into());
+    let response: serde_json::Value = client
+        .execute(builder, Some(json!({})), RequestOptions::default())
+        .await?;
+    assert_eq!(
+        response["headers"]["x-goog-api-key"].as_str(),
+        Some("test-api-key")
+    );
+    Ok(())
+}